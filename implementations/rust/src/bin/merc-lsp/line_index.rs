@@ -0,0 +1,58 @@
+use lsp_types::{Position, Range};
+
+/// Converts between byte offsets and LSP positions,
+/// whose characters are counted in UTF-16 code units.
+pub(crate) struct LineIndex<'a> {
+    text: &'a str,
+    /// The byte offset of the start of each line
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub(crate) fn new(text: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
+        Self { text, line_starts }
+    }
+
+    pub(crate) fn position(&self, offset: usize) -> Position {
+        let offset = offset.min(self.text.len());
+        let line = self.line_starts.partition_point(|start| *start <= offset) - 1;
+        let character = self.text[self.line_starts[line]..offset]
+            .encode_utf16()
+            .count();
+        Position::new(line as u32, character as u32)
+    }
+
+    /// The byte offset of the given position, clamped to the end of its line.
+    pub(crate) fn offset(&self, position: Position) -> usize {
+        let Some(line_start) = self.line_starts.get(position.line as usize) else {
+            return self.text.len();
+        };
+        let line = self.text[*line_start..]
+            .split('\n')
+            .next()
+            .unwrap_or_default();
+        let mut utf16_count = 0;
+        let length = line
+            .char_indices()
+            .find(|(_, char)| {
+                let is_reached = utf16_count >= position.character as usize;
+                utf16_count += char.len_utf16();
+                is_reached
+            })
+            .map(|(index, _)| index)
+            .unwrap_or(line.len());
+        line_start + length
+    }
+
+    pub(crate) fn range(&self, span: &merc::Span) -> Range {
+        Range::new(self.position(span.start()), self.position(span.end()))
+    }
+
+    /// The range that covers the whole text.
+    pub(crate) fn full_range(&self) -> Range {
+        Range::new(Position::new(0, 0), self.position(self.text.len()))
+    }
+}
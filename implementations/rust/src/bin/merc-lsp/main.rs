@@ -0,0 +1,844 @@
+use std::collections::HashMap;
+
+use line_index::LineIndex;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, RequestId, Response};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _,
+        PublishDiagnostics, ShowMessage,
+    },
+    request::{
+        Completion, DocumentSymbolRequest, Formatting, GotoDefinition, References, Rename,
+        Request as _,
+    },
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionResponse, CompletionTextEdit,
+    Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, DocumentSymbol,
+    DocumentSymbolResponse, Documentation, GotoDefinitionResponse, Location, MessageType,
+    NumberOrString, OneOf, PublishDiagnosticsParams, ServerCapabilities, ShowMessageParams,
+    SymbolKind, TextDocumentPositionParams, TextDocumentSyncCapability, TextDocumentSyncKind,
+    TextEdit, Url, WorkspaceEdit,
+};
+
+mod line_index;
+
+/// A language server of MERC, which communicates through the standard input and output.
+fn main() -> anyhow::Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+    run(connection)?;
+    io_threads.join()?;
+    Ok(())
+}
+
+fn run(connection: Connection) -> anyhow::Result<()> {
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        references_provider: Some(OneOf::Left(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        rename_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec![".".to_string(), "=".to_string()]),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let params: lsp_types::InitializeParams =
+        serde_json::from_value(connection.initialize(serde_json::to_value(capabilities)?)?)?;
+
+    // The schema is configured with the initialization options, such as `{"schema": "/path/to/schema.json"}`
+    let schema = match params
+        .initialization_options
+        .as_ref()
+        .and_then(|options| options.get("schema")?.as_str())
+        .map(merc::Schema::from_file)
+        .transpose()
+    {
+        Ok(schema) => schema,
+        Err(error) => {
+            connection
+                .sender
+                .send(Message::Notification(Notification::new(
+                    ShowMessage::METHOD.to_string(),
+                    ShowMessageParams {
+                        typ: MessageType::ERROR,
+                        message: error.to_string(),
+                    },
+                )))?;
+            None
+        }
+    };
+
+    let mut documents: HashMap<Url, String> = HashMap::new();
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    return Ok(());
+                }
+                let response = handle_request(&documents, schema.as_ref(), request);
+                connection.sender.send(Message::Response(response))?;
+            }
+            Message::Notification(notification) => {
+                let Some((uri, text)) = handle_notification(&mut documents, notification)? else {
+                    continue;
+                };
+                let params = PublishDiagnosticsParams::new(
+                    uri.clone(),
+                    text.as_deref()
+                        .map(|text| diagnostics(&uri, text, schema.as_ref()))
+                        .unwrap_or_default(),
+                    None,
+                );
+                connection
+                    .sender
+                    .send(Message::Notification(Notification::new(
+                        PublishDiagnostics::METHOD.to_string(),
+                        params,
+                    )))?;
+            }
+            Message::Response(_) => {}
+        }
+    }
+    Ok(())
+}
+
+fn handle_request(
+    documents: &HashMap<Url, String>,
+    schema: Option<&merc::Schema>,
+    request: Request,
+) -> Response {
+    let id = request.id.clone();
+    let result = match request.method.as_str() {
+        Completion::METHOD => extract::<Completion>(request).map(|(_, params)| {
+            let position = params.text_document_position;
+            let items = schema
+                .zip(documents.get(&position.text_document.uri))
+                .map(|(schema, text)| completions(text, position.position, schema));
+            serde_json::json!(items.map(CompletionResponse::Array))
+        }),
+        Formatting::METHOD => extract::<Formatting>(request).map(|(_, params)| {
+            let edits = documents
+                .get(&params.text_document.uri)
+                .and_then(|text| formatting(text));
+            serde_json::json!(edits)
+        }),
+        DocumentSymbolRequest::METHOD => {
+            extract::<DocumentSymbolRequest>(request).map(|(_, params)| {
+                let uri = &params.text_document.uri;
+                let symbols = documents
+                    .get(uri)
+                    .map(|text| DocumentSymbolResponse::Nested(document_symbols(uri, text)));
+                serde_json::json!(symbols)
+            })
+        }
+        References::METHOD => extract::<References>(request).map(|(_, params)| {
+            let position = params.text_document_position;
+            let locations =
+                find_references(documents, &position).map(|(uri, index, references)| {
+                    references
+                        .references
+                        .iter()
+                        .map(|reference| Location::new(uri.clone(), index.range(&reference.access)))
+                        .collect::<Vec<_>>()
+                });
+            serde_json::json!(locations)
+        }),
+        GotoDefinition::METHOD => extract::<GotoDefinition>(request).map(|(_, params)| {
+            let position = params.text_document_position_params;
+            let location = find_references(documents, &position).map(|(uri, index, references)| {
+                GotoDefinitionResponse::Scalar(Location::new(
+                    uri,
+                    index.range(&references.parent_inferred_at),
+                ))
+            });
+            serde_json::json!(location)
+        }),
+        Rename::METHOD => {
+            let params = match extract::<Rename>(request) {
+                Ok((_, params)) => params,
+                Err(error) => return Response::new_err(id, ErrorCode::InvalidParams as i32, error),
+            };
+            let position = params.text_document_position;
+            let Some((uri, index, references)) = find_references(documents, &position) else {
+                return Response::new_ok(id, serde_json::Value::Null);
+            };
+            let text = &documents[&uri];
+            match merc::rename(text, &references.path, &params.new_name) {
+                Ok(renamed) => {
+                    let edit = TextEdit::new(index.full_range(), renamed);
+                    Ok(serde_json::json!(WorkspaceEdit::new(HashMap::from([(
+                        uri,
+                        vec![edit]
+                    )]))))
+                }
+                Err(error) => {
+                    return Response::new_err(
+                        id,
+                        ErrorCode::RequestFailed as i32,
+                        error.to_string(),
+                    )
+                }
+            }
+        }
+        method => {
+            return Response::new_err(
+                id,
+                ErrorCode::MethodNotFound as i32,
+                format!("Unsupported method {method}"),
+            )
+        }
+    };
+    match result {
+        Ok(result) => Response::new_ok(id, result),
+        Err(error) => Response::new_err(id, ErrorCode::InvalidParams as i32, error),
+    }
+}
+
+fn extract<R: lsp_types::request::Request>(
+    request: Request,
+) -> Result<(RequestId, R::Params), String> {
+    request
+        .extract(R::METHOD)
+        .map_err(|error| error.to_string())
+}
+
+/// Find the entries sharing the path prefix under the given position.
+fn find_references<'a>(
+    documents: &'a HashMap<Url, String>,
+    position: &TextDocumentPositionParams,
+) -> Option<(Url, LineIndex<'a>, merc::PathReferences)> {
+    let uri = &position.text_document.uri;
+    let text = documents.get(uri)?;
+    let index = LineIndex::new(text);
+    let references = merc::find_references(text, index.offset(position.position)).ok()??;
+    Some((uri.clone(), index, references))
+}
+
+/// Update the documents according to the notification.
+///
+/// Returns the document whose diagnostics should be published,
+/// where the text is `None` if the document is closed.
+fn handle_notification(
+    documents: &mut HashMap<Url, String>,
+    notification: Notification,
+) -> anyhow::Result<Option<(Url, Option<String>)>> {
+    Ok(match notification.method.as_str() {
+        DidOpenTextDocument::METHOD => {
+            let params: lsp_types::DidOpenTextDocumentParams =
+                serde_json::from_value(notification.params)?;
+            let document = params.text_document;
+            documents.insert(document.uri.clone(), document.text.clone());
+            Some((document.uri, Some(document.text)))
+        }
+        DidChangeTextDocument::METHOD => {
+            let params: lsp_types::DidChangeTextDocumentParams =
+                serde_json::from_value(notification.params)?;
+            // Only full synchronization is supported, so the last change is the whole text
+            let Some(change) = params.content_changes.into_iter().last() else {
+                return Ok(None);
+            };
+            let uri = params.text_document.uri;
+            documents.insert(uri.clone(), change.text.clone());
+            Some((uri, Some(change.text)))
+        }
+        DidCloseTextDocument::METHOD => {
+            let params: lsp_types::DidCloseTextDocumentParams =
+                serde_json::from_value(notification.params)?;
+            let uri = params.text_document.uri;
+            documents.remove(&uri);
+            Some((uri, None))
+        }
+        _ => None,
+    })
+}
+
+fn diagnostics(uri: &Url, text: &str, schema: Option<&merc::Schema>) -> Vec<Diagnostic> {
+    let path = file_path(uri);
+    let loader = merc::FileSystemLoader;
+    let result = match schema {
+        Some(schema) => merc::check_with_schema_and_includes(&path, text, schema, &loader),
+        None => merc::check_with_includes(&path, text, &loader),
+    };
+    let Err(merc::FileErrors { errors, sources }) = result else {
+        return Vec::new();
+    };
+    let index = LineIndex::new(text);
+    // The errors of included files are reported at the `@include` that loads them
+    let range = |span: &merc::Span| {
+        index.range(
+            &sources
+                .span_in_first_file(span)
+                .unwrap_or_else(|| span.clone()),
+        )
+    };
+    let locate = |span: &merc::Span| match sources.position(span) {
+        Some((path, line, column)) if sources.span_in_first_file(span).as_ref() != Some(span) => {
+            format!(" (in {path}:{line}:{column})")
+        }
+        _ => String::new(),
+    };
+    errors
+        .iter()
+        .map(|error| Diagnostic {
+            range: range(&error.span()),
+            severity: Some(DiagnosticSeverity::ERROR),
+            code: Some(NumberOrString::String(error.code().to_string())),
+            source: Some("merc".to_string()),
+            message: format!(
+                "{}: {}{}",
+                error.title(),
+                error.message(),
+                locate(&error.span())
+            ),
+            related_information: error.related().map(|(span, message)| {
+                vec![DiagnosticRelatedInformation {
+                    location: Location::new(uri.clone(), range(span)),
+                    message: format!("{message}{}", locate(span)),
+                }]
+            }),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// The path of the document, against which the paths of its `@include` are resolved.
+fn file_path(uri: &Url) -> String {
+    match uri.to_file_path() {
+        Ok(path) => path.display().to_string(),
+        Err(()) => uri.path().to_string(),
+    }
+}
+
+fn completions(
+    text: &str,
+    position: lsp_types::Position,
+    schema: &merc::Schema,
+) -> Vec<CompletionItem> {
+    let index = LineIndex::new(text);
+    merc::complete(text, index.offset(position), schema)
+        .into_iter()
+        .map(|completion| CompletionItem {
+            kind: Some(match completion.kind {
+                merc::CompletionKind::Property => CompletionItemKind::PROPERTY,
+                merc::CompletionKind::EnumMember => CompletionItemKind::ENUM_MEMBER,
+                merc::CompletionKind::Value => CompletionItemKind::VALUE,
+            }),
+            detail: completion.detail,
+            documentation: completion.documentation.map(Documentation::String),
+            text_edit: Some(CompletionTextEdit::Edit(TextEdit::new(
+                index.range(&completion.replace),
+                completion.insert_text,
+            ))),
+            label: completion.label,
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Format the whole document, returns `None` if the document is invalid.
+fn formatting(text: &str) -> Option<Vec<TextEdit>> {
+    let formatted = merc::format(text, &merc::FormatOptions::default()).ok()?;
+    Some(vec![TextEdit::new(
+        LineIndex::new(text).full_range(),
+        format!("{formatted}\n"),
+    )])
+}
+
+/// The evaluated tree of the document, which is empty if the document is invalid.
+///
+/// The entries of included files are located at the `@include` that loads them.
+fn document_symbols(uri: &Url, text: &str) -> Vec<DocumentSymbol> {
+    let Ok((value, sources)) =
+        merc::check_with_sources(&file_path(uri), text, &merc::FileSystemLoader)
+    else {
+        return Vec::new();
+    };
+    let index = LineIndex::new(text);
+    children_symbols(&index, &sources, &value)
+}
+
+fn children_symbols(
+    index: &LineIndex,
+    sources: &merc::Sources,
+    value: &merc::Value,
+) -> Vec<DocumentSymbol> {
+    let Some(map_like) = value.as_map_like() else {
+        return Vec::new();
+    };
+    map_like
+        .iter()
+        .enumerate()
+        .map(|(position, (key, value))| {
+            let name = match map_like.kind() {
+                merc::MapKind::Array => position.to_string(),
+                merc::MapKind::Object | merc::MapKind::Map => key.string_value(),
+            };
+            let children = children_symbols(index, sources, value);
+            let span = value.inferred_at();
+            let selection_range = index.range(
+                &sources
+                    .span_in_first_file(span)
+                    .unwrap_or_else(|| span.clone()),
+            );
+            // The range of a parent covers the ranges of all its children
+            let range = children.iter().fold(selection_range, |range, child| {
+                lsp_types::Range::new(
+                    range.start.min(child.range.start),
+                    range.end.max(child.range.end),
+                )
+            });
+            #[allow(deprecated)]
+            DocumentSymbol {
+                name,
+                detail: Some(value.typ().display().to_string()),
+                kind: symbol_kind(value.typ()),
+                tags: None,
+                deprecated: None,
+                range,
+                selection_range,
+                children: value.as_map_like().map(|_| children),
+            }
+        })
+        .collect()
+}
+
+fn symbol_kind(typ: merc::Type) -> SymbolKind {
+    match typ {
+        merc::Type::Object | merc::Type::Map => SymbolKind::OBJECT,
+        merc::Type::Array => SymbolKind::ARRAY,
+        merc::Type::String | merc::Type::Datetime => SymbolKind::STRING,
+        merc::Type::Integer | merc::Type::Decimal => SymbolKind::NUMBER,
+        merc::Type::Null => SymbolKind::NULL,
+        merc::Type::Boolean => SymbolKind::BOOLEAN,
+        merc::Type::Enum => SymbolKind::ENUM_MEMBER,
+    }
+}
+
+#[cfg(test)]
+mod test_lsp {
+    use super::*;
+    use lsp_types::{
+        notification::{Exit, Initialized},
+        request::{Initialize, Shutdown},
+    };
+    use pretty_assertions::assert_eq;
+
+    /// A client that talks to a server running in another thread.
+    struct Client {
+        connection: Connection,
+        server: Option<std::thread::JoinHandle<anyhow::Result<()>>>,
+        next_id: i32,
+    }
+
+    impl Client {
+        fn new() -> Self {
+            Self::with_initialization_options(serde_json::Value::Null)
+        }
+
+        fn with_initialization_options(options: serde_json::Value) -> Self {
+            let (server, connection) = Connection::memory();
+            let mut client = Self {
+                connection,
+                server: Some(std::thread::spawn(move || run(server))),
+                next_id: 0,
+            };
+            client.request::<Initialize>(
+                serde_json::json!({ "capabilities": {}, "initializationOptions": options }),
+            );
+            client.notify::<Initialized>(serde_json::json!({}));
+            client
+        }
+
+        fn request<R: lsp_types::request::Request>(
+            &mut self,
+            params: serde_json::Value,
+        ) -> serde_json::Value {
+            self.response::<R>(params).result.unwrap()
+        }
+
+        fn request_error<R: lsp_types::request::Request>(
+            &mut self,
+            params: serde_json::Value,
+        ) -> lsp_server::ResponseError {
+            self.response::<R>(params).error.unwrap()
+        }
+
+        fn response<R: lsp_types::request::Request>(
+            &mut self,
+            params: serde_json::Value,
+        ) -> Response {
+            self.next_id += 1;
+            let id = RequestId::from(self.next_id);
+            self.connection
+                .sender
+                .send(Message::Request(Request::new(
+                    id.clone(),
+                    R::METHOD.to_string(),
+                    params,
+                )))
+                .unwrap();
+            match self.connection.receiver.recv().unwrap() {
+                Message::Response(response) => {
+                    assert_eq!(response.id, id);
+                    response
+                }
+                message => panic!("Expected a response, got {message:?}"),
+            }
+        }
+
+        fn notify<N: lsp_types::notification::Notification>(&self, params: serde_json::Value) {
+            self.connection
+                .sender
+                .send(Message::Notification(Notification::new(
+                    N::METHOD.to_string(),
+                    params,
+                )))
+                .unwrap();
+        }
+
+        fn open(&self, text: &str) -> PublishDiagnosticsParams {
+            self.open_uri(URI, text)
+        }
+
+        fn open_uri(&self, uri: &str, text: &str) -> PublishDiagnosticsParams {
+            self.notify::<DidOpenTextDocument>(serde_json::json!({
+                "textDocument": {
+                    "uri": uri, "languageId": "merc", "version": 1, "text": text
+                }
+            }));
+            self.receive_diagnostics()
+        }
+
+        fn receive_diagnostics(&self) -> PublishDiagnosticsParams {
+            match self.connection.receiver.recv().unwrap() {
+                Message::Notification(notification) => {
+                    assert_eq!(notification.method, PublishDiagnostics::METHOD);
+                    serde_json::from_value(notification.params).unwrap()
+                }
+                message => panic!("Expected a notification, got {message:?}"),
+            }
+        }
+
+        fn shutdown(mut self) {
+            self.request::<Shutdown>(serde_json::Value::Null);
+            self.notify::<Exit>(serde_json::Value::Null);
+            self.server.take().unwrap().join().unwrap().unwrap();
+        }
+    }
+
+    const URI: &str = "file:///config.merc";
+
+    #[test]
+    fn publish_diagnostics() {
+        let client = Client::new();
+        let published = client.open(".x = 1\n.x = 2\n.y = 'hello");
+        let diagnostics = published
+            .diagnostics
+            .iter()
+            .map(|diagnostic| {
+                (
+                    diagnostic.range,
+                    diagnostic.message.as_str(),
+                    diagnostic.related_information.as_ref().map(|related| {
+                        related
+                            .iter()
+                            .map(|related| related.location.range)
+                            .collect::<Vec<_>>()
+                    }),
+                )
+            })
+            .collect::<Vec<_>>();
+        let range = |line, start, end| {
+            lsp_types::Range::new(Position::new(line, start), Position::new(line, end))
+        };
+        use lsp_types::Position;
+        assert_eq!(
+            diagnostics,
+            [
+                (
+                    range(1, 5, 6),
+                    "Duplicate Assignment: Attempting to assign a new value at the same path is not allowed.",
+                    Some(vec![range(0, 5, 6)])
+                ),
+                (
+                    range(2, 5, 6),
+                    "Unterminated String: unterminated `'` string",
+                    None
+                ),
+            ]
+        );
+
+        // Diagnostics are cleared once the errors are fixed
+        client.notify::<DidChangeTextDocument>(serde_json::json!({
+            "textDocument": { "uri": URI, "version": 2 },
+            "contentChanges": [{ "text": ".x = 1\n.y = 'hello'" }]
+        }));
+        assert_eq!(client.receive_diagnostics().diagnostics, []);
+        client.shutdown();
+    }
+
+    #[test]
+    fn references_and_definition() {
+        let mut client = Client::new();
+        client.open(".materials{metal}.reflectivity = 1.0\n.materials{plastic}.reflectivity = 0.5\n.materials{metal}.metallic = true");
+        // On `{metal}` of the last line
+        let position = serde_json::json!({
+            "textDocument": { "uri": URI },
+            "position": { "line": 2, "character": 13 },
+        });
+        let ranges = |locations: serde_json::Value| {
+            serde_json::from_value::<Vec<Location>>(locations)
+                .unwrap()
+                .into_iter()
+                .map(|location| {
+                    let range = location.range;
+                    (range.start.line, range.start.character, range.end.character)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut params = position.clone();
+        params["context"] = serde_json::json!({ "includeDeclaration": true });
+        assert_eq!(
+            ranges(client.request::<References>(params)),
+            [(0, 10, 17), (2, 10, 17)]
+        );
+        // The type of `.materials` is inferred as a map at the first `{...}`
+        assert_eq!(
+            ranges(serde_json::json!([
+                client.request::<GotoDefinition>(position)
+            ])),
+            [(0, 10, 17)]
+        );
+        client.shutdown();
+    }
+
+    #[test]
+    fn rename() {
+        let mut client = Client::new();
+        client.open(".materials{metal}.reflectivity = 1.0\n.materials{plastic}.reflectivity = 0.5\n.materials{metal}.metallic = true");
+        let params = |new_name: &str| {
+            serde_json::json!({
+                "textDocument": { "uri": URI },
+                "position": { "line": 2, "character": 13 },
+                "newName": new_name,
+            })
+        };
+        let edit: WorkspaceEdit =
+            serde_json::from_value(client.request::<Rename>(params("steel"))).unwrap();
+        let edits = &edit.changes.unwrap()[&Url::parse(URI).unwrap()];
+        assert_eq!(
+            edits[0].new_text,
+            ".materials{steel}.reflectivity = 1.0\n.materials{plastic}.reflectivity = 0.5\n.materials{steel}.metallic = true"
+        );
+
+        let error = client.request_error::<Rename>(params("plastic"));
+        assert_eq!(error.message, "The path .materials{plastic} already exists");
+        client.shutdown();
+    }
+
+    #[test]
+    fn formatting_and_document_symbols() {
+        let mut client = Client::new();
+        client.open(".b = 'é'\n.a.x = 1\n.a.y[+] = true");
+        let text_document = serde_json::json!({ "textDocument": { "uri": URI } });
+
+        let edits: Vec<TextEdit> =
+            serde_json::from_value(client.request::<Formatting>(serde_json::json!({
+                "textDocument": { "uri": URI },
+                "options": { "tabSize": 2, "insertSpaces": true }
+            })))
+            .unwrap();
+        assert_eq!(
+            edits
+                .iter()
+                .map(|edit| (edit.range.end, edit.new_text.as_str()))
+                .collect::<Vec<_>>(),
+            [(
+                lsp_types::Position::new(2, 14),
+                ".a.x = 1\n.a.y[+] = true\n.b = 'é'\n"
+            )]
+        );
+
+        let symbols: Vec<DocumentSymbol> =
+            serde_json::from_value(client.request::<DocumentSymbolRequest>(text_document)).unwrap();
+        fn describe(symbol: &DocumentSymbol) -> String {
+            let children = symbol
+                .children
+                .iter()
+                .flatten()
+                .map(describe)
+                .collect::<Vec<_>>();
+            format!(
+                "{} ({}) {}:{}-{}:{} [{}]",
+                symbol.name,
+                symbol.detail.as_deref().unwrap_or_default(),
+                symbol.range.start.line,
+                symbol.range.start.character,
+                symbol.range.end.line,
+                symbol.range.end.character,
+                children.join(", ")
+            )
+        }
+        assert_eq!(
+            symbols.iter().map(describe).collect::<Vec<_>>(),
+            [
+                "b (String) 0:5-0:8 []",
+                "a (Object) 1:2-2:14 [x (Integer) 1:7-1:8 [], y (Array) 2:4-2:14 [0 (Boolean) 2:10-2:14 []]]"
+            ]
+        );
+        client.shutdown();
+    }
+
+    #[test]
+    fn includes() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("base.merc");
+        std::fs::write(&base, ".name = 'base'").unwrap();
+        let uri = Url::from_file_path(dir.path().join("main.merc")).unwrap();
+        let mut client = Client::new();
+        let text = "@include 'base.merc'\n.port = 80";
+        assert_eq!(client.open_uri(uri.as_str(), text).diagnostics, []);
+
+        // The entries of the included file are located at the `@include`
+        let symbols: Vec<DocumentSymbol> =
+            serde_json::from_value(client.request::<DocumentSymbolRequest>(serde_json::json!({
+                "textDocument": { "uri": uri }
+            })))
+            .unwrap();
+        assert_eq!(
+            symbols
+                .iter()
+                .map(|symbol| (symbol.name.as_str(), symbol.range))
+                .collect::<Vec<_>>(),
+            [("name", range(0, 9, 20)), ("port", range(1, 8, 10)),]
+        );
+
+        // So are the errors of the included file, which name its location
+        std::fs::write(&base, ".name = 'base'\n.name = 'again'").unwrap();
+        client.notify::<DidChangeTextDocument>(serde_json::json!({
+            "textDocument": { "uri": uri, "version": 2 },
+            "contentChanges": [{ "text": text }]
+        }));
+        let diagnostics = client.receive_diagnostics().diagnostics;
+        let base = base.display();
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| (diagnostic.range, diagnostic.message.clone()))
+                .collect::<Vec<_>>(),
+            [(
+                range(0, 9, 20),
+                format!("Duplicate Assignment: Attempting to assign a new value at the same path is not allowed. (in {base}:2:9)")
+            )]
+        );
+        client.shutdown();
+    }
+
+    fn range(line: u32, start: u32, end: u32) -> lsp_types::Range {
+        lsp_types::Range::new(
+            lsp_types::Position::new(line, start),
+            lsp_types::Position::new(line, end),
+        )
+    }
+
+    #[test]
+    fn schema_completion_and_diagnostics() {
+        let dir = tempfile::tempdir().unwrap();
+        let schema = dir.path().join("schema.json");
+        std::fs::write(
+            &schema,
+            r#"{
+              "properties": {
+                "machines": {
+                  "additionalProperties": {
+                    "properties": {
+                      "size": { "enum": ["small", "large"], "description": "The size of the machine" }
+                    }
+                  }
+                }
+              }
+            }"#,
+        )
+        .unwrap();
+        let mut client = Client::with_initialization_options(
+            serde_json::json!({ "schema": schema.to_str().unwrap() }),
+        );
+
+        let published = client.open(".machines{api}.size = 'medium'\n.machines{web}.");
+        assert_eq!(
+            published
+                .diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.code.clone())
+                .collect::<Vec<_>>(),
+            // The incomplete entry hides the schema violation
+            [Some(NumberOrString::String("E0004".to_string()))]
+        );
+
+        let complete = |client: &mut Client, line, character| {
+            let response: Option<CompletionResponse> =
+                serde_json::from_value(client.request::<Completion>(serde_json::json!({
+                    "textDocument": { "uri": URI },
+                    "position": { "line": line, "character": character }
+                })))
+                .unwrap();
+            let Some(CompletionResponse::Array(items)) = response else {
+                panic!("Expected completion items")
+            };
+            items
+                .into_iter()
+                .map(|item| {
+                    let Some(CompletionTextEdit::Edit(edit)) = item.text_edit else {
+                        panic!("Expected a text edit")
+                    };
+                    (item.label, item.documentation, edit.range, edit.new_text)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            complete(&mut client, 1, 15),
+            [(
+                "size".to_string(),
+                Some(Documentation::String("The size of the machine".to_string())),
+                lsp_types::Range::new(
+                    lsp_types::Position::new(1, 15),
+                    lsp_types::Position::new(1, 15)
+                ),
+                "size".to_string()
+            )]
+        );
+        assert_eq!(
+            complete(&mut client, 0, 23)
+                .into_iter()
+                .map(|(_, _, range, new_text)| (range, new_text))
+                .collect::<Vec<_>>(),
+            ["'small'", "'large'"].map(|value| (
+                lsp_types::Range::new(
+                    lsp_types::Position::new(0, 22),
+                    lsp_types::Position::new(0, 23)
+                ),
+                value.to_string()
+            ))
+        );
+
+        client.notify::<DidChangeTextDocument>(serde_json::json!({
+            "textDocument": { "uri": URI, "version": 2 },
+            "contentChanges": [{ "text": ".machines.api.size = 'medium'" }]
+        }));
+        assert_eq!(
+            client
+                .receive_diagnostics()
+                .diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.message.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "Wrong Access Kind: `api` is a user-defined key",
+                "Schema Violation: expected one of `\"small\"`, `\"large\"`, found \"medium\""
+            ]
+        );
+        client.shutdown();
+    }
+}
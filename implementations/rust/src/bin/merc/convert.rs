@@ -0,0 +1,65 @@
+use crate::Format;
+
+/// Convert the given source, read from `path`, from one format to another.
+///
+/// Every conversion goes through JSON, except MERC to MERC, which formats the source,
+/// and conversions between MERC and YAML or TOML, which keep comments.
+/// The files included by MERC sources are loaded relative to `path`,
+/// and their entries are converted along with the entries of the source, except when formatting it.
+/// The schema, if any, decides which JSON objects become MERC maps,
+/// and `numbers` decides how MERC numbers that JSON cannot represent exactly are converted.
+pub(crate) fn convert(
+    path: &str,
+    source: &str,
+    from: Format,
+    to: Format,
+    schema: Option<&merc::Schema>,
+    numbers: merc::JsonNumbers,
+) -> Result<String, String> {
+    let loader = &merc::FileSystemLoader;
+    match (from, to, schema) {
+        (Format::Merc, Format::Merc, _) => {
+            merc::check_with_includes(path, source, loader).map_err(|errors| errors.display())?;
+            return merc::format_merc(source, None);
+        }
+        (Format::Yaml, Format::Merc, None) => return merc::yaml_to_merc_string(source),
+        (Format::Toml, Format::Merc, None) => return merc::toml_to_merc_string(source),
+        (Format::Merc, Format::Yaml, _) => {
+            return merc::merc_to_yaml_string_with_loader(path, source, loader)
+        }
+        (Format::Merc, Format::Toml, _) => {
+            return merc::merc_to_toml_string_with_loader(path, source, loader)
+        }
+        _ => {}
+    }
+    let json = match from {
+        Format::Merc => merc::merc_to_json_string_with_loader(path, source, loader, numbers)?,
+        Format::Json => source.to_string(),
+        Format::Yaml => merc::yaml_to_json_string(source)?,
+        Format::Toml => merc::toml_to_json_string(source)?,
+    };
+    match to {
+        Format::Merc => match schema {
+            Some(schema) => merc::json_to_merc_string_with_schema(&json, schema),
+            None => merc::json_to_merc_string(&json),
+        },
+        Format::Json => Ok(json),
+        Format::Yaml => merc::json_to_yaml_string(&json),
+        Format::Toml => merc::json_to_toml_string(&json),
+    }
+}
+
+/// Evaluate the given source, read from `path`, into a MERC value, keeping the comments of YAML and TOML.
+///
+/// The files included by MERC sources are loaded relative to `path`.
+pub(crate) fn evaluate(path: &str, source: &str, from: Format) -> Result<merc::Value, String> {
+    match from {
+        Format::Merc => merc::check_with_includes(path, source, &merc::FileSystemLoader)
+            .map_err(|errors| errors.display()),
+        Format::Json => serde_json::from_str(source)
+            .map_err(|error| error.to_string())
+            .and_then(|json| merc::Value::from_json(json).map_err(|error| error.to_string())),
+        Format::Yaml => merc::yaml_to_merc(source).map_err(|error| error.to_string()),
+        Format::Toml => merc::toml_to_merc(source).map_err(|error| error.to_string()),
+    }
+}
@@ -0,0 +1,1108 @@
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use clap::{Parser, Subcommand, ValueEnum};
+
+mod convert;
+
+/// The MErcilessly Redundant Config language
+#[derive(Parser, Debug)]
+#[command(name = "merc", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Format MERC files in place.
+    /// Reads from the standard input and writes to the standard output if no file is given.
+    Fmt {
+        /// Do not write anything, exit with a non-zero code if any file is not formatted
+        #[arg(long)]
+        check: bool,
+        /// The order in which entries are printed
+        #[arg(long, value_enum, default_value_t = Order::Sorted)]
+        order: Order,
+        files: Vec<PathBuf>,
+    },
+    /// Parse and evaluate MERC files, along with the files they include,
+    /// exit with a non-zero code if any of them is invalid.
+    /// Reads from the standard input if no file is given.
+    Check {
+        /// A JSON Schema file to validate the files against
+        #[arg(long)]
+        schema: Option<PathBuf>,
+        /// Rewrite the accesses whose kind disagrees with the schema, such as `.key` for a map,
+        /// writing to the standard output if no file is given
+        #[arg(long, requires = "schema")]
+        fix: bool,
+        files: Vec<PathBuf>,
+    },
+    /// Convert between MERC, JSON, YAML and TOML.
+    /// Reads from the standard input if no file is given.
+    Convert {
+        /// The format of the inputs, inferred from the file extension if omitted
+        #[arg(long)]
+        from: Option<Format>,
+        #[arg(long)]
+        to: Format,
+        /// Write the output of each file next to it, with the extension of the target format,
+        /// instead of printing to the standard output
+        #[arg(long, short)]
+        write: bool,
+        /// A JSON Schema file, used to convert the objects that it describes as maps into MERC maps
+        #[arg(long)]
+        schema: Option<PathBuf>,
+        /// How MERC numbers that JSON readers cannot represent exactly are converted
+        #[arg(long, value_enum, default_value_t = JsonNumbers::Number)]
+        json_numbers: JsonNumbers,
+        files: Vec<PathBuf>,
+    },
+    /// Assign a value at a path, keeping the rest of the files intact.
+    /// Reads from the standard input and writes to the standard output if no file is given.
+    Set {
+        /// For example, `.machines{api}.size`, or `.excludes[+]` to append to an array
+        path: String,
+        /// A MERC value, for example `'t3-large'`, `1` or `#fast`
+        #[arg(allow_hyphen_values = true)]
+        value: String,
+        /// Treat the value as a plain string, which will be quoted
+        #[arg(long, short)]
+        string: bool,
+        files: Vec<PathBuf>,
+    },
+    /// Remove every entry under a path, keeping the rest of the files intact.
+    /// Reads from the standard input and writes to the standard output if no file is given.
+    Unset { path: String, files: Vec<PathBuf> },
+    /// Move every entry under a path to another path, keeping the rest of the files intact.
+    /// Reads from the standard input and writes to the standard output if no file is given.
+    Mv {
+        from: String,
+        to: String,
+        files: Vec<PathBuf>,
+    },
+    /// Rename the key of the last access of a path in every entry under the path,
+    /// for example `merc rename '.materials{metal}' steel`.
+    /// Reads from the standard input and writes to the standard output if no file is given.
+    Rename {
+        path: String,
+        new_key: String,
+        files: Vec<PathBuf>,
+    },
+    /// Print the values that a query matches, for example `merc get '.machines{*}.size' config.merc`,
+    /// exit with a non-zero code if nothing matches.
+    /// A query is a path where any access can be a wildcard: `.*`, `{*}` or `[*]`,
+    /// and `.**` matches every value under a path, for example `.**.size`.
+    /// Reads from the standard input if no file is given.
+    Get {
+        query: String,
+        /// The format of the inputs, inferred from the file extension if omitted
+        #[arg(long)]
+        from: Option<Format>,
+        #[arg(long, short, value_enum, default_value_t = Output::Merc)]
+        output: Output,
+        files: Vec<PathBuf>,
+    },
+    /// Print the entries that differ between two files, ignoring the order of entries and formatting,
+    /// exit with a non-zero code if there is any difference.
+    /// The files can be in any supported format, for example `merc diff config.merc config.json`.
+    Diff {
+        /// The format of both files, inferred from their extensions if omitted
+        #[arg(long)]
+        from: Option<Format>,
+        old: PathBuf,
+        new: PathBuf,
+    },
+    /// Overlay MERC files into one and print it, where later files override the values of earlier files,
+    /// for example `merc merge base.merc prod.merc`.
+    Merge {
+        /// Keep assigning the same path twice within a single file an error
+        #[arg(long)]
+        strict: bool,
+        /// Print the file, line and column that provided each value above its entry
+        #[arg(long)]
+        provenance: bool,
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Merge the changes of two MERC files into the one of our side, for Git.
+    /// Exit with a non-zero code if both sides change an entry differently,
+    /// leaving conflict markers around the conflicting entries where they are.
+    /// The comments, includes and order of entries of our side are kept,
+    /// unless a change cannot be applied to it, such as a change of an array item,
+    /// in which case the merge is printed anew with sorted entries.
+    ///
+    /// To use it, add `*.merc merge=merc` to `.gitattributes`,
+    /// and set `merge.merc.driver` to `merc merge-driver %O %A %B` in the Git config.
+    MergeDriver {
+        /// The common ancestor
+        base: PathBuf,
+        /// Our side, which is overwritten with the result
+        ours: PathBuf,
+        /// Their side
+        theirs: PathBuf,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Order {
+    /// Sort entries by their keys
+    Sorted,
+    /// Keep entries in the order they appear in the source
+    Preserve,
+    /// Keep entries in source order, but place entries sharing a path prefix together
+    Grouped,
+}
+
+impl From<Order> for merc::EntryOrder {
+    fn from(order: Order) -> Self {
+        match order {
+            Order::Sorted => merc::EntryOrder::Sorted,
+            Order::Preserve => merc::EntryOrder::Preserve,
+            Order::Grouped => merc::EntryOrder::Grouped,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum JsonNumbers {
+    /// Keep every digit in a JSON number
+    Number,
+    /// Keep every digit in a JSON string
+    String,
+    /// Fail with an error
+    Error,
+}
+
+impl From<JsonNumbers> for merc::JsonNumbers {
+    fn from(numbers: JsonNumbers) -> Self {
+        match numbers {
+            JsonNumbers::Number => merc::JsonNumbers::Number,
+            JsonNumbers::String => merc::JsonNumbers::String,
+            JsonNumbers::Error => merc::JsonNumbers::Error,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Output {
+    /// The entries under each match, with their full paths
+    Merc,
+    /// Each match as JSON
+    Json,
+    /// Each scalar without quotes, and each object, map or array as JSON in a single line
+    Raw,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Merc,
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl Format {
+    fn from_path(path: &Path) -> Option<Format> {
+        match path.extension()?.to_str()? {
+            "merc" => Some(Format::Merc),
+            "json" => Some(Format::Json),
+            "yaml" | "yml" => Some(Format::Yaml),
+            "toml" => Some(Format::Toml),
+            _ => None,
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Format::Merc => "merc",
+            Format::Json => "json",
+            Format::Yaml => "yaml",
+            Format::Toml => "toml",
+        }
+    }
+}
+
+/// A file, or the standard input if `path` is `None`.
+struct Input {
+    path: Option<PathBuf>,
+}
+
+impl Input {
+    fn from_files(files: Vec<PathBuf>) -> Vec<Input> {
+        if files.is_empty() {
+            vec![Input { path: None }]
+        } else {
+            files
+                .into_iter()
+                .map(|path| Input { path: Some(path) })
+                .collect()
+        }
+    }
+
+    fn name(&self) -> String {
+        match &self.path {
+            Some(path) => path.display().to_string(),
+            None => "<stdin>".to_string(),
+        }
+    }
+
+    fn read(&self, stdin: &mut impl Read) -> anyhow::Result<String> {
+        match &self.path {
+            Some(path) => std::fs::read_to_string(path)
+                .map_err(|error| anyhow::anyhow!("Failed to read {}: {error}", path.display())),
+            None => {
+                let mut content = String::new();
+                stdin.read_to_string(&mut content)?;
+                Ok(content)
+            }
+        }
+    }
+}
+
+/// Ensure the given content ends with exactly one newline.
+fn with_trailing_newline(content: &str) -> String {
+    format!("{}\n", content.trim_end_matches('\n'))
+}
+
+fn run(
+    cli: Cli,
+    stdin: &mut impl Read,
+    stdout: &mut impl Write,
+    stderr: &mut impl Write,
+) -> anyhow::Result<ExitCode> {
+    let mut success = true;
+    match cli.command {
+        Command::Fmt {
+            check,
+            order,
+            files,
+        } => {
+            let options = merc::FormatOptions::new(order.into());
+            for input in Input::from_files(files) {
+                let source = input.read(stdin)?;
+                // Only the source is formatted, but the files that it includes must also be valid
+                let checked =
+                    merc::check_with_includes(&input.name(), &source, &merc::FileSystemLoader)
+                        .map_err(|errors| errors.display());
+                let formatted =
+                    match checked.and_then(|_| merc::format_merc(&source, Some(options))) {
+                        Ok(formatted) => with_trailing_newline(&formatted),
+                        Err(error) => {
+                            writeln!(stderr, "{}:\n{error}", input.name())?;
+                            success = false;
+                            continue;
+                        }
+                    };
+                if check {
+                    if formatted != source {
+                        writeln!(stderr, "{} is not formatted", input.name())?;
+                        success = false;
+                    }
+                } else {
+                    match &input.path {
+                        Some(path) => {
+                            if formatted != source {
+                                std::fs::write(path, formatted)?
+                            }
+                        }
+                        None => write!(stdout, "{formatted}")?,
+                    }
+                }
+            }
+        }
+        Command::Check { schema, fix, files } => {
+            let schema = schema.map(merc::Schema::from_file).transpose()?;
+            for input in Input::from_files(files) {
+                let mut source = input.read(stdin)?;
+                if let (Some(schema), true) = (&schema, fix) {
+                    // An invalid source is left intact, its errors are reported below
+                    if let Ok(fixed) = merc::fix_accesses(&source, schema) {
+                        match &input.path {
+                            Some(path) if fixed != source => std::fs::write(path, &fixed)?,
+                            Some(_) => {}
+                            None => write!(stdout, "{fixed}")?,
+                        }
+                        source = fixed;
+                    }
+                }
+                let loader = &merc::FileSystemLoader;
+                let result = match &schema {
+                    Some(schema) => {
+                        merc::check_with_schema_and_includes(&input.name(), &source, schema, loader)
+                    }
+                    None => merc::check_with_includes(&input.name(), &source, loader),
+                };
+                if let Err(errors) = result {
+                    writeln!(stderr, "{}:\n{}", input.name(), errors.display())?;
+                    success = false;
+                }
+            }
+        }
+        Command::Convert {
+            from,
+            to,
+            write,
+            schema,
+            json_numbers,
+            files,
+        } => {
+            let schema = schema.map(merc::Schema::from_file).transpose()?;
+            for input in Input::from_files(files) {
+                let Some(from) = from.or_else(|| input.path.as_deref().and_then(Format::from_path))
+                else {
+                    anyhow::bail!(
+                        "Unable to infer the format of {}, please specify --from",
+                        input.name()
+                    )
+                };
+                let source = input.read(stdin)?;
+                let output = match convert::convert(
+                    &input.name(),
+                    &source,
+                    from,
+                    to,
+                    schema.as_ref(),
+                    json_numbers.into(),
+                ) {
+                    Ok(output) => with_trailing_newline(&output),
+                    Err(error) => {
+                        writeln!(stderr, "{}:\n{error}", input.name())?;
+                        success = false;
+                        continue;
+                    }
+                };
+                match (&input.path, write) {
+                    (Some(path), true) => {
+                        std::fs::write(path.with_extension(to.extension()), output)?
+                    }
+                    (None, true) => anyhow::bail!("--write cannot be used with the standard input"),
+                    (_, false) => write!(stdout, "{output}")?,
+                }
+            }
+        }
+        Command::Set {
+            path,
+            value,
+            string,
+            files,
+        } => {
+            let value = if string {
+                merc::MercString::from(value.as_str()).display()
+            } else {
+                value
+            };
+            success = edit_files(files, stdin, stdout, stderr, |source| {
+                merc::set(source, &path, &value)
+            })?
+        }
+        Command::Unset { path, files } => {
+            success = edit_files(files, stdin, stdout, stderr, |source| {
+                merc::unset(source, &path)
+            })?
+        }
+        Command::Mv { from, to, files } => {
+            success = edit_files(files, stdin, stdout, stderr, |source| {
+                merc::mv(source, &from, &to)
+            })?
+        }
+        Command::Rename {
+            path,
+            new_key,
+            files,
+        } => {
+            success = edit_files(files, stdin, stdout, stderr, |source| {
+                merc::rename(source, &path, &new_key)
+            })?
+        }
+        Command::Get {
+            query,
+            from,
+            output,
+            files,
+        } => {
+            let mut found = false;
+            for input in Input::from_files(files) {
+                let from = from
+                    .or_else(|| input.path.as_deref().and_then(Format::from_path))
+                    .unwrap_or(Format::Merc);
+                let value = match convert::evaluate(&input.name(), &input.read(stdin)?, from) {
+                    Ok(value) => value,
+                    Err(error) => {
+                        writeln!(stderr, "{}:\n{error}", input.name())?;
+                        success = false;
+                        continue;
+                    }
+                };
+                let matches = merc::query(&value, &query)
+                    .map_err(|error| anyhow::anyhow!(error.display(&query)))?;
+                found |= !matches.is_empty();
+                match output {
+                    // A match under another match, such as with `.**`, is printed as part of the other one
+                    Output::Merc if !matches.is_empty() => {
+                        writeln!(stdout, "{}", merc::print_matches(&matches))?
+                    }
+                    Output::Merc => {}
+                    Output::Json => {
+                        for matched in matches {
+                            let json = matched.value.clone().into_json();
+                            writeln!(stdout, "{}", serde_json::to_string_pretty(&json)?)?;
+                        }
+                    }
+                    Output::Raw => {
+                        for matched in matches {
+                            let raw = match matched.raw() {
+                                Some(raw) => raw,
+                                None => serde_json::to_string(&matched.value.clone().into_json())?,
+                            };
+                            writeln!(stdout, "{raw}")?;
+                        }
+                    }
+                }
+            }
+            success &= found;
+        }
+        Command::Diff { from, old, new } => {
+            let mut values = Vec::new();
+            let mut formats = Vec::new();
+            for input in [Input { path: Some(old) }, Input { path: Some(new) }] {
+                let from = from
+                    .or_else(|| input.path.as_deref().and_then(Format::from_path))
+                    .unwrap_or(Format::Merc);
+                formats.push(from);
+                match convert::evaluate(&input.name(), &input.read(stdin)?, from) {
+                    Ok(value) => values.push(value),
+                    Err(error) => {
+                        writeln!(stderr, "{}:\n{error}", input.name())?;
+                        return Ok(ExitCode::FAILURE);
+                    }
+                }
+            }
+            let changes = if formats.iter().all(|format| format == &Format::Merc) {
+                merc::diff(&values[0], &values[1])
+            } else {
+                merc::diff_across_formats(&values[0], &values[1])
+            };
+            for change in changes {
+                writeln!(stdout, "{change}")?;
+                success = false;
+            }
+        }
+        Command::Merge {
+            strict,
+            provenance,
+            files,
+        } => {
+            let mut overlay = merc::Overlay::new(strict);
+            let mut sources = Vec::new();
+            for input in Input::from_files(files) {
+                let source = input.read(stdin)?;
+                match overlay.add_layer_with_includes(
+                    &input.name(),
+                    &source,
+                    &merc::FileSystemLoader,
+                ) {
+                    Ok(layer) => sources.push(layer),
+                    Err(errors) => {
+                        writeln!(stderr, "{}:\n{}", input.name(), errors.display())?;
+                        success = false;
+                    }
+                }
+            }
+            if !success {
+                return Ok(ExitCode::FAILURE);
+            }
+            let output = if provenance {
+                overlay
+                    .provenance()
+                    .into_iter()
+                    .map(|provenance| {
+                        let position = sources[provenance.layer].position(&provenance.span);
+                        let (path, line, column) = position.unwrap_or_default();
+                        format!(
+                            "# {path}:{line}:{column}\n{} = {}",
+                            provenance.path, provenance.value
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            } else {
+                overlay.value().map(merc::Value::print).unwrap_or_default()
+            };
+            write!(stdout, "{}", with_trailing_newline(&output))?;
+        }
+        Command::MergeDriver { base, ours, theirs } => {
+            let mut values = Vec::new();
+            let mut sources = Vec::new();
+            for input in [&base, &ours, &theirs].map(|path| Input {
+                path: Some(path.clone()),
+            }) {
+                let source = input.read(stdin)?;
+                // Only the entries of each file are merged, as the files that they include are left unchanged
+                match merc::check(&source) {
+                    Ok(value) => values.push(value),
+                    // Our side is left intact, so that Git reports a conflict on the whole file
+                    Err(errors) => {
+                        writeln!(stderr, "{}:\n{}", input.name(), errors.display(&source))?;
+                        return Ok(ExitCode::FAILURE);
+                    }
+                }
+                sources.push(source);
+            }
+            let merge = merc::merge(&values[0], &values[1], &values[2]);
+            let merged = match merge.apply(&sources[1]) {
+                Ok(merged) => merged,
+                // The merge is printed anew, which sorts the entries and drops the includes of our side
+                Err(error) if merge.value.is_some() || !merge.conflicts.is_empty() => {
+                    writeln!(
+                        stderr,
+                        "{}:\n{error}\nThe merge is printed with sorted entries and without the includes of our side",
+                        ours.display()
+                    )?;
+                    merge.print()
+                }
+                Err(error) => {
+                    writeln!(stderr, "{}:\n{error}", ours.display())?;
+                    return Ok(ExitCode::FAILURE);
+                }
+            };
+            std::fs::write(&ours, with_trailing_newline(&merged))?;
+            success = merge.conflicts.is_empty();
+        }
+    }
+    Ok(if success {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+/// Edit the given files in place, or the standard input to the standard output.
+///
+/// Returns `false` if any of the edits failed.
+fn edit_files(
+    files: Vec<PathBuf>,
+    stdin: &mut impl Read,
+    stdout: &mut impl Write,
+    stderr: &mut impl Write,
+    edit: impl Fn(&str) -> Result<String, merc::EditError>,
+) -> anyhow::Result<bool> {
+    let mut success = true;
+    for input in Input::from_files(files) {
+        let source = input.read(stdin)?;
+        match (edit(&source), &input.path) {
+            (Ok(edited), Some(path)) => std::fs::write(path, edited)?,
+            (Ok(edited), None) => write!(stdout, "{edited}")?,
+            (Err(error), _) => {
+                writeln!(stderr, "{}:\n{}", input.name(), error.display(&source))?;
+                success = false;
+            }
+        }
+    }
+    Ok(success)
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(
+        cli,
+        &mut std::io::stdin(),
+        &mut std::io::stdout(),
+        &mut std::io::stderr(),
+    ) {
+        Ok(exit_code) => exit_code,
+        Err(error) => {
+            eprintln!("{error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_cli {
+    use super::*;
+
+    fn run_cli(args: &[impl AsRef<str>], stdin: &str) -> (ExitCode, String, String) {
+        let cli =
+            Cli::try_parse_from(std::iter::once("merc").chain(args.iter().map(|arg| arg.as_ref())))
+                .unwrap();
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        let exit_code = run(cli, &mut stdin.as_bytes(), &mut stdout, &mut stderr).unwrap();
+        (
+            exit_code,
+            String::from_utf8(stdout).unwrap(),
+            String::from_utf8(stderr).unwrap(),
+        )
+    }
+
+    #[test]
+    fn fmt_stdin() {
+        let (exit_code, stdout, _) = run_cli(&["fmt"], ".b = 1\n.a = 2");
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+        assert_eq!(stdout, ".a = 2\n.b = 1\n");
+    }
+
+    #[test]
+    fn fmt_order() {
+        let input = ".b.y = 1\n.a = 2\n.b.x = 3";
+        let (_, stdout, _) = run_cli(&["fmt", "--order", "preserve"], input);
+        assert_eq!(stdout, ".b.y = 1\n.a = 2\n.b.x = 3\n");
+        let (_, stdout, _) = run_cli(&["fmt", "--order", "grouped"], input);
+        assert_eq!(stdout, ".b.y = 1\n.b.x = 3\n.a = 2\n");
+    }
+
+    #[test]
+    fn fmt_files_in_place_and_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let formatted = dir.path().join("formatted.merc");
+        let unformatted = dir.path().join("unformatted.merc");
+        std::fs::write(&formatted, ".a = 1\n").unwrap();
+        std::fs::write(&unformatted, ".b = 1\n.a = 2").unwrap();
+        let args = |check: bool| {
+            let mut args = vec!["fmt".to_string()];
+            if check {
+                args.push("--check".to_string())
+            }
+            args.push(formatted.display().to_string());
+            args.push(unformatted.display().to_string());
+            args
+        };
+
+        let (exit_code, _, stderr) = run_cli(&args(true), "");
+        assert_eq!(exit_code, ExitCode::FAILURE);
+        assert_eq!(
+            stderr,
+            format!("{} is not formatted\n", unformatted.display())
+        );
+
+        let (exit_code, _, _) = run_cli(&args(false), "");
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+        assert_eq!(
+            std::fs::read_to_string(&unformatted).unwrap(),
+            ".a = 2\n.b = 1\n"
+        );
+
+        let (exit_code, _, _) = run_cli(&args(true), "");
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+    }
+
+    #[test]
+    fn check_reports_diagnostics() {
+        let (exit_code, _, stderr) = run_cli(&["check"], ".x = 2\n.x = 3\n.y = 4\n.y = 5");
+        assert_eq!(exit_code, ExitCode::FAILURE);
+        assert!(stderr.starts_with("<stdin>:\nerror[E0102]: Duplicate Assignment"));
+        assert_eq!(stderr.matches("Duplicate Assignment").count(), 2);
+
+        let (exit_code, _, stderr) = run_cli(&["check"], ".x = 2\n.y = 3");
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+        assert_eq!(stderr, "");
+
+        let (exit_code, _, stderr) = run_cli(&["check"], "");
+        assert_eq!(exit_code, ExitCode::FAILURE);
+        assert_eq!(
+            stderr,
+            "<stdin>:\nerror[E0007]: Missing Entry\n = note: expected an entry, such as `.key = value`, found the end of the input\n"
+        );
+    }
+
+    #[test]
+    fn check_against_schema() {
+        let dir = tempfile::tempdir().unwrap();
+        let schema = dir.path().join("schema.json");
+        std::fs::write(
+            &schema,
+            r#"{"properties": {"port": {"type": "integer", "maximum": 65535}}}"#,
+        )
+        .unwrap();
+        let schema = schema.to_str().unwrap();
+
+        let (exit_code, _, stderr) = run_cli(&["check", "--schema", schema], ".port = 80000");
+        assert_eq!(exit_code, ExitCode::FAILURE);
+        assert!(stderr.starts_with("<stdin>:\nerror[E0301]: Schema Violation"));
+        assert!(stderr.contains("expected at most 65535, found 80000"));
+
+        let (exit_code, _, _) = run_cli(&["check", "--schema", schema], ".port = 8080");
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+    }
+
+    #[test]
+    fn check_fix_accesses() {
+        let dir = tempfile::tempdir().unwrap();
+        let schema = dir.path().join("schema.json");
+        std::fs::write(
+            &schema,
+            r#"{"properties": {"env": {"additionalProperties": {"type": "string"}}}}"#,
+        )
+        .unwrap();
+        let file = dir.path().join("config.merc");
+        std::fs::write(&file, ".env.HOME = '/root'\n").unwrap();
+        let args = [
+            "check",
+            "--schema",
+            schema.to_str().unwrap(),
+            "--fix",
+            file.to_str().unwrap(),
+        ];
+
+        let (exit_code, _, stderr) = run_cli(&args, "");
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+        assert_eq!(stderr, "");
+        assert_eq!(
+            std::fs::read_to_string(&file).unwrap(),
+            ".env{HOME} = '/root'\n"
+        );
+    }
+
+    #[test]
+    fn convert_stdin() {
+        let (exit_code, stdout, _) = run_cli(
+            &["convert", "--from", "merc", "--to", "yaml"],
+            ".x{y}[+] = 1",
+        );
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+        assert_eq!(stdout, "x:\n  y:\n  - 1\n");
+
+        let (exit_code, stdout, _) = run_cli(
+            &["convert", "--from", "toml", "--to", "merc"],
+            "[x]\ny = 2\n",
+        );
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+        assert_eq!(stdout, ".x.y = 2\n");
+    }
+
+    #[test]
+    fn set_unset_mv() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("config.merc");
+        let file_name = file.display().to_string();
+        std::fs::write(
+            &file,
+            "# API\n.machines{api}.size = 't3-medium'\n\n# Excludes\n.excludes[+] = 'x'\n",
+        )
+        .unwrap();
+
+        let (exit_code, _, _) = run_cli(
+            &["set", ".machines{api}.size", "'t3-large'", &file_name],
+            "",
+        );
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+        let (exit_code, _, _) = run_cli(&["set", "--string", ".excludes[+]", "y", &file_name], "");
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+        let (exit_code, _, _) =
+            run_cli(&["mv", ".machines{api}", ".machines{web}", &file_name], "");
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+        assert_eq!(
+            std::fs::read_to_string(&file).unwrap(),
+            "# API\n.machines{web}.size = 't3-large'\n\n# Excludes\n.excludes[+] = 'x'\n.excludes[+] = 'y'\n"
+        );
+
+        let (exit_code, _, _) = run_cli(&["unset", ".excludes", &file_name], "");
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+        assert_eq!(
+            std::fs::read_to_string(&file).unwrap(),
+            "# API\n.machines{web}.size = 't3-large'\n\n"
+        );
+
+        let (exit_code, stdout, stderr) = run_cli(
+            &["set", ".machines{web}.size.x", "1"],
+            ".machines{web}.size = 't3-large'",
+        );
+        assert_eq!(exit_code, ExitCode::FAILURE);
+        assert_eq!(stdout, "");
+        assert!(stderr.contains("Type Mismatch"));
+    }
+
+    #[test]
+    fn rename() {
+        let (exit_code, stdout, _) = run_cli(
+            &["rename", ".machines{api}", "web server"],
+            ".machines{api}.size = 't3-medium'\n.machines{api}.replicas = 1\n",
+        );
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+        assert_eq!(
+            stdout,
+            ".machines{'web server'}.size = 't3-medium'\n.machines{'web server'}.replicas = 1\n"
+        );
+    }
+
+    #[test]
+    fn convert_write_infers_input_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("config.yaml");
+        std::fs::write(&input, "x:\n  y: true\n").unwrap();
+        let (exit_code, _, _) = run_cli(
+            &[
+                "convert",
+                "--to",
+                "merc",
+                "--write",
+                &input.display().to_string(),
+            ],
+            "",
+        );
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("config.merc")).unwrap(),
+            ".x.y = true\n"
+        );
+    }
+
+    #[test]
+    fn diff() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = dir.path().join("old.merc");
+        let new = dir.path().join("new.json");
+        std::fs::write(&old, ".mode = #fast\n.machines{api}.size = 't3-medium'\n").unwrap();
+        std::fs::write(
+            &new,
+            r#"{"machines": {"api": {"size": "t3-large"}}, "mode": "fast"}"#,
+        )
+        .unwrap();
+        let (exit_code, stdout, _) = run_cli(
+            &[
+                "diff",
+                &old.display().to_string(),
+                &new.display().to_string(),
+            ],
+            "",
+        );
+        assert_eq!(exit_code, ExitCode::FAILURE);
+        assert_eq!(
+            stdout,
+            "- .machines{api}.size = 't3-medium'\n+ .machines.api.size = 't3-large'\n"
+        );
+
+        let (exit_code, stdout, _) = run_cli(
+            &[
+                "diff",
+                &old.display().to_string(),
+                &old.display().to_string(),
+            ],
+            "",
+        );
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+        assert_eq!(stdout, "");
+    }
+
+    #[test]
+    fn get() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("config.json");
+        std::fs::write(
+            &file,
+            r#"{"machines": {"api": {"size": "t3-large"}, "web": {"size": "t3-small"}}}"#,
+        )
+        .unwrap();
+        let file = file.display().to_string();
+
+        let (exit_code, stdout, _) = run_cli(&["get", ".machines.*.size", &file], "");
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+        assert_eq!(
+            stdout,
+            ".machines.api.size = 't3-large'\n.machines.web.size = 't3-small'\n"
+        );
+
+        let (exit_code, stdout, _) = run_cli(&["get", "-o", "raw", ".**.size", &file], "");
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+        assert_eq!(stdout, "t3-large\nt3-small\n");
+
+        let (exit_code, stdout, _) = run_cli(
+            &["get", "--output", "json", ".machines{*}"],
+            ".machines{api}.size = 't3-large'",
+        );
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+        assert_eq!(stdout, "{\n  \"size\": \"t3-large\"\n}\n");
+
+        let (exit_code, stdout, _) = run_cli(&["get", ".machines{*}", &file], "");
+        assert_eq!(exit_code, ExitCode::FAILURE);
+        assert_eq!(stdout, "");
+
+        let (exit_code, stdout, _) = run_cli(&["get", ".**"], ".l[+] = 1\n.m.n = 2\n");
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+        assert_eq!(stdout, ".l[+] = 1\n.m.n = 2\n");
+    }
+
+    #[test]
+    fn check_loads_includes() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("main.merc");
+        std::fs::write(&main, "@include 'common.merc'\n.region = 'us'\n").unwrap();
+        std::fs::write(dir.path().join("common.merc"), ".region = 'eu'\n").unwrap();
+        let (exit_code, _, stderr) = run_cli(&["check", &main.display().to_string()], "");
+        assert_eq!(exit_code, ExitCode::FAILURE);
+        assert!(stderr.contains("Duplicate Assignment"));
+        assert!(stderr.contains(&format!(
+            "{}:1:11",
+            dir.path().join("common.merc").display()
+        )));
+    }
+
+    #[test]
+    fn commands_load_includes() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("main.merc");
+        let main = main.display().to_string();
+        std::fs::write(
+            &main,
+            "@include 'db.merc' at .services{db}\n.region = 'eu'\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("db.merc"), ".port = 5432\n").unwrap();
+
+        let (exit_code, stdout, _) = run_cli(&["convert", "--to", "json", &main], "");
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+        assert_eq!(
+            stdout,
+            "{\n  \"region\": \"eu\",\n  \"services\": {\n    \"db\": {\n      \"port\": 5432\n    }\n  }\n}\n"
+        );
+
+        let (exit_code, stdout, _) = run_cli(&["get", ".services{db}.port", &main], "");
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+        assert_eq!(stdout, ".services{db}.port = 5432\n");
+
+        let old = dir.path().join("old.merc").display().to_string();
+        std::fs::write(&old, ".services{db}.port = 5433\n.region = 'eu'\n").unwrap();
+        let (exit_code, stdout, _) = run_cli(&["diff", &old, &main], "");
+        assert_eq!(exit_code, ExitCode::FAILURE);
+        assert_eq!(
+            stdout,
+            "- .services{db}.port = 5433\n+ .services{db}.port = 5432\n"
+        );
+
+        // A cycle is reported instead of being ignored
+        std::fs::write(
+            dir.path().join("db.merc"),
+            "@include 'main.merc'\n.port = 5432\n",
+        )
+        .unwrap();
+        let (exit_code, _, stderr) = run_cli(&["get", ".region", &main], "");
+        assert_eq!(exit_code, ExitCode::FAILURE);
+        assert!(stderr.contains("This include forms a cycle"), "{stderr}");
+    }
+
+    #[test]
+    fn merge() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("base.merc");
+        let prod = dir.path().join("prod.merc");
+        std::fs::write(&base, ".size = 't3-medium'\n.replicas = 1\n").unwrap();
+        std::fs::write(&prod, ".size = 't3-large'\n").unwrap();
+        let (exit_code, stdout, _) = run_cli(
+            &[
+                "merge",
+                &base.display().to_string(),
+                &prod.display().to_string(),
+            ],
+            "",
+        );
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+        assert_eq!(stdout, ".replicas = 1\n.size = 't3-large'\n");
+
+        let (exit_code, stdout, _) = run_cli(
+            &[
+                "merge",
+                "--provenance",
+                &base.display().to_string(),
+                &prod.display().to_string(),
+            ],
+            "",
+        );
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+        assert_eq!(
+            stdout,
+            format!(
+                "# {}:2:13\n.replicas = 1\n# {}:1:9\n.size = 't3-large'\n",
+                base.display(),
+                prod.display()
+            )
+        );
+    }
+
+    #[test]
+    fn merge_driver() {
+        let dir = tempfile::tempdir().unwrap();
+        let [base, ours, theirs] = ["base", "ours", "theirs"].map(|name| dir.path().join(name));
+        std::fs::write(&base, ".a = 1\n.b = 1\n").unwrap();
+        std::fs::write(&ours, ".a = 2\n.b = 1\n").unwrap();
+        std::fs::write(&theirs, ".a = 1\n.b = 2\n").unwrap();
+        let args = [
+            "merge-driver".to_string(),
+            base.display().to_string(),
+            ours.display().to_string(),
+            theirs.display().to_string(),
+        ];
+        let (exit_code, _, _) = run_cli(&args, "");
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+        assert_eq!(std::fs::read_to_string(&ours).unwrap(), ".a = 2\n.b = 2\n");
+
+        std::fs::write(&theirs, ".a = 3\n.b = 2\n").unwrap();
+        let (exit_code, _, _) = run_cli(&args, "");
+        assert_eq!(exit_code, ExitCode::FAILURE);
+        assert_eq!(
+            std::fs::read_to_string(&ours).unwrap(),
+            "<<<<<<< ours\n.a = 2\n=======\n.a = 3\n>>>>>>> theirs\n.b = 2\n"
+        );
+
+        // The markers replace the conflicting entries where they are, along with their comments
+        std::fs::write(&base, ".a = 1\n.c{x} = 1\n.b = 1\n").unwrap();
+        std::fs::write(&ours, ".a = 1\n# The c\n.c{x} = 2\n.b = 1\n.c{y} = 2\n").unwrap();
+        std::fs::write(&theirs, ".a = 2\n.c{x} = 3\n.b = 1\n").unwrap();
+        let (exit_code, _, _) = run_cli(&args, "");
+        assert_eq!(exit_code, ExitCode::FAILURE);
+        assert_eq!(
+            std::fs::read_to_string(&ours).unwrap(),
+            ".a = 2\n<<<<<<< ours\n# The c\n.c{x} = 2\n=======\n.c{x} = 3\n>>>>>>> theirs\n.b = 1\n.c{y} = 2\n"
+        );
+    }
+
+    #[test]
+    fn merge_driver_keeps_comments_and_includes() {
+        let dir = tempfile::tempdir().unwrap();
+        let [base, ours, theirs] = ["base", "ours", "theirs"].map(|name| dir.path().join(name));
+        std::fs::write(
+            &base,
+            "@include 'db.merc'\n.b = 1\n.excludes[+] = 'dist/'\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &ours,
+            "# Settings\n@include 'db.merc'\n\n# The second\n.b = 1\n.a = 2 # The first\n.excludes[+] = 'dist/'\n\n# End\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &theirs,
+            "@include 'db.merc'\n.b = 2\n.excludes[+] = 'dist/'\n.excludes[+] = 'target/'\n",
+        )
+        .unwrap();
+        let args = [
+            "merge-driver".to_string(),
+            base.display().to_string(),
+            ours.display().to_string(),
+            theirs.display().to_string(),
+        ];
+        let (exit_code, _, stderr) = run_cli(&args, "");
+        assert_eq!(stderr, "");
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+        assert_eq!(
+            std::fs::read_to_string(&ours).unwrap(),
+            "# Settings\n@include 'db.merc'\n\n# The second\n.b = 2\n.a = 2 # The first\n.excludes[+] = 'dist/'\n.excludes[+] = 'target/'\n\n# End\n"
+        );
+
+        // Changing an item of an array cannot be located in our side, which is then printed anew
+        std::fs::write(&base, ".a = 1\n.x[+] = 'a'\n.b = 1\n").unwrap();
+        std::fs::write(&ours, "# Settings\n.a = 2\n.x[+] = 'a'\n.b = 1\n").unwrap();
+        std::fs::write(&theirs, ".a = 1\n.x[+] = 'z'\n.b = 3\n").unwrap();
+        let (exit_code, _, stderr) = run_cli(&args, "");
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+        assert!(stderr.contains("- .x[0] = 'a'"), "{stderr}");
+        assert_eq!(
+            std::fs::read_to_string(&ours).unwrap(),
+            "# Settings\n.a = 2\n.b = 3\n.x[+] = 'z'\n"
+        );
+    }
+}
@@ -0,0 +1,210 @@
+//! Completion of paths and values, driven by a JSON Schema.
+//!
+//! The entry under the cursor is usually incomplete, such as `.machines{api}.`,
+//! so only the text between the start of its line and the cursor is parsed.
+use crate::{parser, Identifier, MercString, Schema, Span};
+use itertools::Itertools;
+
+/// A suggestion for the text under the cursor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+    /// The type of the suggested property or value, such as `string`
+    pub detail: Option<String>,
+    /// The `description` of the schema of the suggestion
+    pub documentation: Option<String>,
+    /// The text that replaces [`Completion::replace`]
+    pub insert_text: String,
+    /// The span to be replaced, which covers the part of the key or value that is already typed
+    pub replace: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionKind {
+    /// A property defined by the schema, such as `size` after `.machines{api}.`
+    Property,
+    /// A member of the `enum` of the schema
+    EnumMember,
+    /// Any other value, such as `true` or a skeleton string `''`
+    Value,
+}
+
+/// Complete the path or the value under the given byte offset.
+///
+/// After an access such as `.machines{api}.`, the properties of the schema at that path are suggested,
+/// where map keys and array keys match any key, as they do when the entries are evaluated.
+/// After `=`, the members of `enum`, `true` and `false`, or a skeleton string are suggested.
+pub fn complete(merc: &str, offset: usize, schema: &Schema) -> Vec<Completion> {
+    let line_start = merc[..offset].rfind('\n').map_or(0, |index| index + 1);
+    let indentation = merc[line_start..offset].len() - merc[line_start..offset].trim_start().len();
+    let entry_start = line_start + indentation;
+    let text = &merc[entry_start..offset];
+    if !text.starts_with(['.', '{', '[']) {
+        return Vec::new();
+    }
+    match equals_sign(text) {
+        Some(index) => {
+            let value_start = entry_start + index + 1;
+            let typed = &merc[value_start..offset];
+            let typed_start = value_start + typed.len() - typed.trim_start().len();
+            // A value must be separated from `=`
+            let separator = if typed_start == value_start { " " } else { "" };
+            resolve(schema, &text[..index])
+                .map(|schema_at| {
+                    value_completions(schema, schema_at, separator, Span::new(typed_start, offset))
+                })
+                .unwrap_or_default()
+        }
+        None => {
+            let typed = text.trim_end_matches(|char: char| {
+                char.is_alphanumeric() || char == '-' || char == '_'
+            });
+            let Some(path) = typed.strip_suffix('.') else {
+                return Vec::new();
+            };
+            let replace = Span::new(entry_start + typed.len(), offset);
+            resolve(schema, path)
+                .map(|schema_at| property_completions(schema, schema_at, replace))
+                .unwrap_or_default()
+        }
+    }
+}
+
+/// The byte index of the first `=` that is not quoted.
+fn equals_sign(text: &str) -> Option<usize> {
+    let mut quote = None;
+    text.char_indices().find_map(|(index, char)| {
+        match (quote, char) {
+            (None, '\'' | '"') => quote = Some(char),
+            (Some(opening), _) if opening == char => quote = None,
+            (None, '=') => return Some(index),
+            _ => {}
+        }
+        None
+    })
+}
+
+/// The schema of the value at the given path, where an empty path refers to the root.
+fn resolve<'a>(schema: &'a Schema, path: &str) -> Option<&'a serde_json::Value> {
+    let path = path.trim();
+    if path.is_empty() {
+        return Some(schema.root());
+    }
+    parser::parse_path(path)
+        .ok()?
+        .iter()
+        .try_fold(schema.root(), |schema_at, access| {
+            schema
+                .child_schema(schema_at, &access.kind)
+                .map(|(_, _, child)| child)
+        })
+}
+
+fn property_completions(
+    schema: &Schema,
+    schema_at: &serde_json::Value,
+    replace: Span,
+) -> Vec<Completion> {
+    schema
+        .keywords(schema_at)
+        .into_iter()
+        .filter_map(|keywords| keywords.get("properties")?.as_object())
+        .flatten()
+        .unique_by(|(key, _)| *key)
+        .map(|(key, property)| {
+            let keywords = schema.keywords(property);
+            let keyword = |name: &str| keywords.iter().find_map(|keywords| keywords.get(name));
+            let insert_text = Identifier::from_str(key)
+                .map(|identifier| identifier.display())
+                .unwrap_or_else(|_| key.clone());
+            Completion {
+                label: key.clone(),
+                kind: CompletionKind::Property,
+                detail: keyword("type").map(type_name),
+                documentation: keyword("description")
+                    .and_then(|description| description.as_str())
+                    .map(str::to_string),
+                insert_text,
+                replace: replace.clone(),
+            }
+        })
+        .collect()
+}
+
+fn value_completions(
+    schema: &Schema,
+    schema_at: &serde_json::Value,
+    separator: &str,
+    replace: Span,
+) -> Vec<Completion> {
+    let keywords = schema.keywords(schema_at);
+    let keyword = |name: &str| keywords.iter().find_map(|keywords| keywords.get(name));
+    let documentation = keyword("description")
+        .and_then(|description| description.as_str())
+        .map(str::to_string);
+    let completion = |kind, value: String, detail: &str| Completion {
+        label: value.clone(),
+        kind,
+        detail: Some(detail.to_string()),
+        documentation: documentation.clone(),
+        insert_text: format!("{separator}{value}"),
+        replace: replace.clone(),
+    };
+
+    let members = keyword("enum")
+        .and_then(|members| members.as_array())
+        .into_iter()
+        .flatten()
+        .chain(keyword("const"))
+        .collect_vec();
+    if !members.is_empty() {
+        return members
+            .into_iter()
+            .filter_map(|member| {
+                let (value, detail) = match member {
+                    serde_json::Value::String(string) => {
+                        (MercString::from(string.as_str()).display(), "string")
+                    }
+                    serde_json::Value::Number(number) => (number.to_string(), "number"),
+                    serde_json::Value::Bool(boolean) => (boolean.to_string(), "boolean"),
+                    serde_json::Value::Null => ("null".to_string(), "null"),
+                    serde_json::Value::Array(_) | serde_json::Value::Object(_) => return None,
+                };
+                Some(completion(CompletionKind::EnumMember, value, detail))
+            })
+            .collect();
+    }
+
+    let types = match keyword("type") {
+        Some(serde_json::Value::String(typ)) => vec![typ.as_str()],
+        Some(serde_json::Value::Array(types)) => {
+            types.iter().filter_map(|typ| typ.as_str()).collect()
+        }
+        _ => Vec::new(),
+    };
+    types
+        .into_iter()
+        .flat_map(|typ| match typ {
+            "boolean" => vec!["true", "false"],
+            "null" => vec!["null"],
+            "string" => vec!["''"],
+            _ => Vec::new(),
+        })
+        .map(|value| {
+            let detail = match value {
+                "true" | "false" => "boolean",
+                "null" => "null",
+                _ => "string",
+            };
+            completion(CompletionKind::Value, value.to_string(), detail)
+        })
+        .collect()
+}
+
+fn type_name(typ: &serde_json::Value) -> String {
+    match typ {
+        serde_json::Value::Array(types) => types.iter().filter_map(|typ| typ.as_str()).join(" | "),
+        typ => typ.as_str().unwrap_or_default().to_string(),
+    }
+}
@@ -0,0 +1,365 @@
+//! A lossless concrete syntax tree of MERC.
+//!
+//! Unlike [`crate::Parsed`], the concrete syntax tree keeps every token of the source,
+//! including whitespaces, newlines and comments, so that the source can be reproduced byte-for-byte
+//! by concatenating its tokens.
+use crate::{parse_error::ParseError, parser::Rule, Access, Span};
+use pest::{iterators::Pair, Parser};
+
+/// Parse the given source into a lossless concrete syntax tree.
+pub fn parse_cst(input: &str) -> Result<Cst, crate::Error> {
+    let file = crate::parser::MercParser::parse(Rule::file, input)
+        .map_err(|error| crate::Error::ParseError(Box::new(ParseError::new(error, input))))?
+        .next()
+        .unwrap();
+    let mut root = CstNode::from_pair(file, input);
+
+    // The `file` rule might not cover the whole input, for example when the input is empty
+    let leading = tokenize_gap(input, 0, root.span.start());
+    let trailing = tokenize_gap(input, root.span.end(), input.len());
+    root.children = leading
+        .into_iter()
+        .chain(std::mem::take(&mut root.children))
+        .chain(trailing)
+        .collect();
+    root.span = Span::new(0, input.len());
+    Ok(Cst(root))
+}
+
+#[derive(Debug, Clone)]
+pub struct Cst(CstNode);
+
+impl Cst {
+    pub fn root(&self) -> &CstNode {
+        &self.0
+    }
+
+    /// The entries of this file, in source order.
+    pub fn entries(&self) -> impl Iterator<Item = CstEntry<'_>> {
+        self.0.child_nodes().filter_map(|node| match node.kind {
+            NodeKind::Entry => Some(CstEntry(node)),
+            _ => None,
+        })
+    }
+
+    /// The comments after the last entry of this file.
+    pub fn trailing_comments(&self) -> impl Iterator<Item = &CstToken> {
+        self.0
+            .child_nodes()
+            .filter(|node| node.kind == NodeKind::Comments)
+            .flat_map(|node| node.tokens())
+            .filter(|token| token.kind == TokenKind::Comment)
+    }
+
+    /// Every token of this file, in source order.
+    pub fn tokens(&self) -> impl Iterator<Item = &CstToken> {
+        self.0.tokens()
+    }
+}
+
+impl std::fmt::Display for Cst {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CstNode {
+    kind: NodeKind,
+    span: Span,
+    children: Vec<CstElement>,
+}
+
+#[derive(Debug, Clone)]
+pub enum CstElement {
+    Node(CstNode),
+    Token(CstToken),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    File,
+    /// The comments of an entry, or the comments at the end of the file
+    Comments,
+    Entry,
+    /// `@include 'db.merc'`, optionally followed by `at` and a path
+    Include,
+    Accesses,
+    ObjectAccess,
+    MapAccess,
+    ArrayAccessImplicit,
+    ArrayAccessExplicit,
+    Enum,
+}
+
+#[derive(Debug, Clone)]
+pub struct CstToken {
+    kind: TokenKind,
+    span: Span,
+    text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// Spaces and tabs
+    Whitespace,
+    Newline,
+    Comment,
+    /// `.`
+    Dot,
+    /// `{`
+    LeftBrace,
+    /// `}`
+    RightBrace,
+    /// `[`
+    LeftBracket,
+    /// `]`
+    RightBracket,
+    /// `+`
+    Plus,
+    /// `=`
+    Equals,
+    /// `#`
+    Hash,
+    UnquotedIdentifier,
+    /// Any kind of string literal, including its quotes
+    String,
+    Number,
+    Boolean,
+    Null,
+    Datetime,
+    /// `@include` or `at`
+    Keyword,
+}
+
+impl CstNode {
+    pub fn kind(&self) -> NodeKind {
+        self.kind
+    }
+
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    pub fn children(&self) -> &[CstElement] {
+        &self.children
+    }
+
+    pub fn child_nodes(&self) -> impl Iterator<Item = &CstNode> {
+        self.children.iter().filter_map(|child| match child {
+            CstElement::Node(node) => Some(node),
+            CstElement::Token(_) => None,
+        })
+    }
+
+    /// Every token under this node, in source order.
+    pub fn tokens(&self) -> Box<dyn Iterator<Item = &CstToken> + '_> {
+        Box::new(self.children.iter().flat_map(|child| match child {
+            CstElement::Node(node) => node.tokens(),
+            CstElement::Token(token) => Box::new(std::iter::once(token)),
+        }))
+    }
+
+    fn from_pair(pair: Pair<Rule>, input: &str) -> CstNode {
+        let span: Span = pair.as_span().into();
+        let kind = match pair.as_rule() {
+            Rule::file => NodeKind::File,
+            Rule::comments => NodeKind::Comments,
+            Rule::entry => NodeKind::Entry,
+            Rule::include => NodeKind::Include,
+            Rule::accesses | Rule::include_accesses => NodeKind::Accesses,
+            Rule::object_access => NodeKind::ObjectAccess,
+            Rule::map_access => NodeKind::MapAccess,
+            Rule::array_access_implicit => NodeKind::ArrayAccessImplicit,
+            Rule::array_access_explicit => NodeKind::ArrayAccessExplicit,
+            Rule::r#enum => NodeKind::Enum,
+            rule => unreachable!("rule = {rule:?}"),
+        };
+        let mut children = Vec::new();
+        let mut position = span.start();
+        for inner in pair.into_inner() {
+            if inner.as_rule() == Rule::EOI {
+                continue;
+            }
+            let inner_span: Span = inner.as_span().into();
+            children.extend(tokenize_gap(input, position, inner_span.start()));
+            position = inner_span.end();
+            children.push(CstElement::from_pair(inner, input));
+        }
+        children.extend(tokenize_gap(input, position, span.end()));
+        CstNode {
+            kind,
+            span,
+            children,
+        }
+    }
+}
+
+impl std::fmt::Display for CstNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.tokens()
+            .try_for_each(|token| write!(f, "{}", token.text))
+    }
+}
+
+impl CstElement {
+    pub fn span(&self) -> &Span {
+        match self {
+            CstElement::Node(node) => node.span(),
+            CstElement::Token(token) => token.span(),
+        }
+    }
+
+    fn from_pair(pair: Pair<Rule>, input: &str) -> CstElement {
+        let token_kind = match pair.as_rule() {
+            Rule::comment => TokenKind::Comment,
+            Rule::unquoted_identifier => TokenKind::UnquotedIdentifier,
+            Rule::string => TokenKind::String,
+            Rule::number | Rule::integer => TokenKind::Number,
+            Rule::boolean => TokenKind::Boolean,
+            Rule::null => TokenKind::Null,
+            Rule::datetime => TokenKind::Datetime,
+            Rule::include_keyword | Rule::at_keyword => TokenKind::Keyword,
+            _ => return CstElement::Node(CstNode::from_pair(pair, input)),
+        };
+        CstElement::Token(CstToken {
+            kind: token_kind,
+            span: pair.as_span().into(),
+            text: pair.as_str().to_string(),
+        })
+    }
+}
+
+impl CstToken {
+    pub fn kind(&self) -> TokenKind {
+        self.kind
+    }
+
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn is_trivia(&self) -> bool {
+        matches!(
+            self.kind,
+            TokenKind::Whitespace | TokenKind::Newline | TokenKind::Comment
+        )
+    }
+}
+
+/// A view of a [`CstNode`] of kind [`NodeKind::Entry`].
+#[derive(Debug, Clone, Copy)]
+pub struct CstEntry<'a>(&'a CstNode);
+
+impl<'a> CstEntry<'a> {
+    pub fn node(&self) -> &'a CstNode {
+        self.0
+    }
+
+    /// The span of this entry, including its comments.
+    pub fn span(&self) -> &'a Span {
+        self.0.span()
+    }
+
+    /// The comments above this entry.
+    pub fn comments(&self) -> impl Iterator<Item = &'a CstToken> {
+        self.child(NodeKind::Comments)
+            .into_iter()
+            .flat_map(|node| node.tokens())
+            .filter(|token| token.kind == TokenKind::Comment)
+    }
+
+    /// The access nodes of this entry, e.g. `.x`, `{y}` and `[+]` of `.x{y}[+] = 1`.
+    pub fn accesses(&self) -> impl Iterator<Item = &'a CstNode> {
+        self.child(NodeKind::Accesses)
+            .into_iter()
+            .flat_map(|node| node.child_nodes())
+    }
+
+    /// The accesses of this entry with their keys, spanning the source of this tree.
+    pub fn path(&self) -> Result<Vec<Access>, crate::Error> {
+        let accesses = self
+            .child(NodeKind::Accesses)
+            .expect("an entry always has accesses");
+        let offset = accesses.span().start();
+        Ok(crate::parser::parse_path(&accesses.to_string())?
+            .into_iter()
+            .map(|access| Access {
+                span: Span::new(access.span.start() + offset, access.span.end() + offset),
+                kind: access.kind,
+            })
+            .collect())
+    }
+
+    /// The span from the first access until the last access.
+    pub fn accesses_span(&self) -> &'a Span {
+        self.child(NodeKind::Accesses)
+            .expect("an entry always has accesses")
+            .span()
+    }
+
+    /// The value of this entry, which is the last element of an entry.
+    pub fn value(&self) -> &'a CstElement {
+        self.0
+            .children
+            .iter()
+            .rev()
+            .find(|child| !matches!(child, CstElement::Token(token) if token.is_trivia()))
+            .expect("an entry always has a value")
+    }
+
+    fn child(&self, kind: NodeKind) -> Option<&'a CstNode> {
+        self.0.child_nodes().find(|node| node.kind == kind)
+    }
+}
+
+/// Tokenize the text between the given byte offsets that is not covered by any pair,
+/// which only consists of whitespaces, newlines and punctuations.
+fn tokenize_gap(input: &str, start: usize, end: usize) -> Vec<CstElement> {
+    let mut tokens: Vec<CstToken> = Vec::new();
+    let mut offset = start;
+    let text = &input[start..end];
+    let mut chars = text.char_indices().peekable();
+    while let Some((index, char)) = chars.next() {
+        let kind = match char {
+            ' ' | '\t' => TokenKind::Whitespace,
+            '\n' => TokenKind::Newline,
+            '\r' => {
+                if let Some((_, '\n')) = chars.peek() {
+                    chars.next();
+                }
+                TokenKind::Newline
+            }
+            '.' => TokenKind::Dot,
+            '{' => TokenKind::LeftBrace,
+            '}' => TokenKind::RightBrace,
+            '[' => TokenKind::LeftBracket,
+            ']' => TokenKind::RightBracket,
+            '+' => TokenKind::Plus,
+            '=' => TokenKind::Equals,
+            '#' => TokenKind::Hash,
+            char => unreachable!("unexpected character {char:?} between pairs"),
+        };
+        let next_index = chars.peek().map(|(index, _)| *index).unwrap_or(text.len());
+        let token_text = &text[index..next_index];
+        offset = start + next_index;
+        match tokens.last_mut() {
+            // Consecutive whitespaces are merged into one token
+            Some(last) if kind == TokenKind::Whitespace && last.kind == TokenKind::Whitespace => {
+                last.text.push_str(token_text);
+                last.span = Span::new(last.span.start(), offset);
+            }
+            _ => tokens.push(CstToken {
+                kind,
+                span: Span::new(start + index, offset),
+                text: token_text.to_string(),
+            }),
+        }
+    }
+    debug_assert_eq!(offset, end);
+    tokens.into_iter().map(CstElement::Token).collect()
+}
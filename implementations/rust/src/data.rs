@@ -1,10 +1,14 @@
-use crate::parser::{Access, AccessKind, MercString, Parsed, Span, StringKind};
+use crate::parser::{Access, AccessKind, MercParser, MercString, Parsed, Rule, Span, StringKind};
 use annotate_snippets::{Annotation, Level};
+use bigdecimal::{BigDecimal, ToPrimitive, Zero};
 use indexmap::IndexMap;
 use itertools::Itertools;
 
+/// An evaluated MERC value.
+///
+/// Every value remembers the span at which its type was first inferred.
 #[derive(Clone, Debug)]
-pub(crate) struct Value {
+pub struct Value {
     kind: ValueKind,
     inferred_at: Span,
 }
@@ -22,12 +26,53 @@ enum ValueKind {
 }
 
 #[derive(Clone, Debug)]
-enum ValueScalarKind {
+pub enum ValueScalarKind {
     String(MercString),
-    Integer(isize),
-    Number(serde_json::Number),
+    Number(Number),
     Null,
     Boolean(bool),
+    Enum(Identifier),
+    /// A datetime, a local datetime, a local date or a local time
+    Datetime(Datetime),
+}
+
+impl ValueScalarKind {
+    /// The MERC literal of this scalar, such as `'hello'` or `#fast`.
+    pub(crate) fn display(&self) -> String {
+        match self {
+            ValueScalarKind::String(string) => string.display(),
+            ValueScalarKind::Number(number) => number.to_string(),
+            ValueScalarKind::Null => "null".to_string(),
+            ValueScalarKind::Boolean(boolean) => boolean.to_string(),
+            ValueScalarKind::Enum(identifier) => format!("#{}", identifier.display()),
+            ValueScalarKind::Datetime(datetime) => datetime.to_string(),
+        }
+    }
+
+    /// Whether both scalars are the same value, regardless of how they are written,
+    /// such as `'a'` and `"a"`, or `1.0` and `1`.
+    /// An enum is the same as a string of its name, as they are the same in other formats.
+    pub(crate) fn is_same(&self, other: &ValueScalarKind) -> bool {
+        match (self, other) {
+            (
+                ValueScalarKind::String(_) | ValueScalarKind::Enum(_),
+                ValueScalarKind::String(_) | ValueScalarKind::Enum(_),
+            ) => self.text() == other.text(),
+            (ValueScalarKind::Number(a), ValueScalarKind::Number(b)) => a.value == b.value,
+            (ValueScalarKind::Null, ValueScalarKind::Null) => true,
+            (ValueScalarKind::Boolean(a), ValueScalarKind::Boolean(b)) => a == b,
+            (ValueScalarKind::Datetime(a), ValueScalarKind::Datetime(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    fn text(&self) -> Option<&str> {
+        match self {
+            ValueScalarKind::String(string) => Some(string.as_str()),
+            ValueScalarKind::Enum(identifier) => Some(identifier.as_str()),
+            _ => None,
+        }
+    }
 }
 
 impl ValueKind {
@@ -46,12 +91,16 @@ impl ValueKind {
             }) => Type::Array,
             ValueKind::Scalar { kind, .. } => match kind {
                 ValueScalarKind::String(_) => Type::String,
-                ValueScalarKind::Integer(_) => Type::Integer,
+                ValueScalarKind::Number(number) if number.is_integer_literal() => Type::Integer,
                 ValueScalarKind::Number(_) => Type::Decimal,
                 ValueScalarKind::Null => Type::Null,
                 ValueScalarKind::Boolean(_) => Type::Boolean,
+                ValueScalarKind::Enum(_) => Type::Enum,
+                ValueScalarKind::Datetime(_) => Type::Datetime,
             },
-            ValueKind::Uninitialized => unreachable!(),
+            ValueKind::Uninitialized => {
+                unreachable!("uninitialized values do not escape the evaluation")
+            }
         }
     }
 
@@ -59,79 +108,94 @@ impl ValueKind {
         matches!(self, ValueKind::Scalar { .. })
     }
 
-    fn into_json(self) -> serde_json::Value {
-        match self {
-            ValueKind::MapLike(map_like) => map_like.into_json(),
+    fn into_json(
+        self,
+        inferred_at: Span,
+        numbers: JsonNumbers,
+    ) -> Result<serde_json::Value, EvaluateError> {
+        Ok(match self {
+            ValueKind::MapLike(map_like) => map_like.into_json(numbers)?,
             ValueKind::Scalar { kind, .. } => match kind {
                 ValueScalarKind::String(string) => serde_json::Value::String(string.string_value()),
-                ValueScalarKind::Integer(integer) => serde_json::Value::Number(integer.into()),
-                ValueScalarKind::Number(decimal) => str::parse(&decimal.to_string())
-                    .map(serde_json::Value::Number)
-                    .unwrap_or_else(|_| serde_json::Value::String(decimal.to_string())),
+                ValueScalarKind::Number(number) => number
+                    .to_json(numbers)
+                    .ok_or(EvaluateError::InexactNumber { span: inferred_at })?,
                 ValueScalarKind::Null => serde_json::Value::Null,
                 ValueScalarKind::Boolean(boolean) => serde_json::Value::Bool(boolean),
+                ValueScalarKind::Enum(identifier) => {
+                    serde_json::Value::String(identifier.string_value())
+                }
+                ValueScalarKind::Datetime(datetime) => {
+                    serde_json::Value::String(datetime.to_string())
+                }
             },
-            ValueKind::Uninitialized => unreachable!(),
-        }
+            ValueKind::Uninitialized => {
+                unreachable!("uninitialized values do not escape the evaluation")
+            }
+        })
     }
 
-    fn to_string_entries(&self, parent_path: &str) -> Vec<StringEntry> {
+    fn to_string_entries(
+        &self,
+        parent_path: &str,
+        inferred_at: &Span,
+        order: EntryOrder,
+    ) -> Vec<StringEntry> {
         match self {
             ValueKind::MapLike(map) => map
                 .map
                 .iter()
-                .enumerate()
-                .sorted_by_key(|(index, (key, _))| match map.kind {
-                    MapKind::Object | MapKind::Map => key.string_value(),
-                    MapKind::Array => index.to_string(),
+                .sorted_by_key(|(key, _)| match (order, &map.kind) {
+                    (EntryOrder::Sorted, MapKind::Object | MapKind::Map) => key.string_value(),
+                    // Array items keep their positions, as the sort is stable,
+                    // and keys are kept in the order of their first appearance
+                    (EntryOrder::Sorted, MapKind::Array)
+                    | (EntryOrder::Preserve | EntryOrder::Grouped, _) => String::new(),
                 })
-                .flat_map(|(_, (key, value))| {
+                .flat_map(|(key, value)| {
                     let path = match map.kind {
                         MapKind::Object => format!(".{}", key.display()),
                         MapKind::Map => format!("{{{}}}", key.display()),
                         MapKind::Array => format!("[{}]", key.display()),
                     };
-                    value
-                        .kind
-                        .to_string_entries(&format!("{parent_path}{path}"))
+                    value.kind.to_string_entries(
+                        &format!("{parent_path}{path}"),
+                        &value.inferred_at,
+                        order,
+                    )
                 })
                 .collect(),
-            ValueKind::Scalar { comment, kind } => {
-                let entry = match kind {
-                    ValueScalarKind::String(s) => format!("{parent_path} = {}", s.display()),
-                    ValueScalarKind::Integer(i) => format!("{parent_path} = {:?}", i),
-                    ValueScalarKind::Number(d) => {
-                        format!("{parent_path} = {}", serde_json::to_string(d).unwrap())
-                    }
-                    ValueScalarKind::Null => {
-                        format!("{parent_path} = null")
-                    }
-                    ValueScalarKind::Boolean(b) => format!("{parent_path} = {:?}", b),
-                };
-                Some(StringEntry {
-                    comment: comment.clone(),
-                    entry,
-                })
-                .into_iter()
-                .collect_vec()
+            ValueKind::Scalar { comment, kind } => Some(StringEntry {
+                comment: comment.clone(),
+                entry: format!("{parent_path} = {}", kind.display()),
+                position: inferred_at.start(),
+            })
+            .into_iter()
+            .collect_vec(),
+            ValueKind::Uninitialized => {
+                unreachable!("uninitialized values do not escape the evaluation")
             }
-            ValueKind::Uninitialized => unreachable!(),
         }
     }
 }
 
+/// An object, a map or an array.
+///
+/// All of them are represented as an ordered map,
+/// the keys of an array are solely used to construct the array.
 #[derive(Debug, Clone)]
-struct MapLike {
+pub struct MapLike {
     kind: MapKind,
     map: IndexMap<MapKey, Value>,
 }
 
 #[derive(Debug, Clone)]
-pub(crate) enum MapKey {
+pub enum MapKey {
     Implicit(MapKeyImplicit),
     Explicit(Identifier),
 }
 
+use std::collections::HashSet;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 static COUNTER: AtomicUsize = AtomicUsize::new(0);
@@ -141,7 +205,7 @@ fn increment_counter() -> usize {
 }
 
 #[derive(Ord, PartialOrd, Eq, PartialEq, Debug, Clone, Copy, Hash, Default)]
-pub(crate) struct MapKeyImplicit(usize);
+pub struct MapKeyImplicit(usize);
 impl MapKeyImplicit {
     pub(crate) fn new() -> MapKeyImplicit {
         MapKeyImplicit(increment_counter())
@@ -152,14 +216,27 @@ impl MapKeyImplicit {
     }
 }
 impl MapKey {
-    fn display(&self) -> String {
+    /// Choose the key of the `index`-th item of an array.
+    ///
+    /// If the item only needs one line of MERC to be represented
+    /// then use implicit keys, otherwise use explicit keys.
+    pub(crate) fn array_item(index: usize, item: &Value) -> MapKey {
+        match &item.kind {
+            ValueKind::MapLike(map_like) if map_like.map.len() > 1 => {
+                MapKey::Explicit(Identifier::Unquoted(index.to_string()))
+            }
+            _ => MapKey::Implicit(MapKeyImplicit::new()),
+        }
+    }
+
+    pub fn display(&self) -> String {
         match self {
             MapKey::Implicit(_) => "+".to_string(),
             MapKey::Explicit(identifier) => identifier.display(),
         }
     }
 
-    fn string_value(&self) -> String {
+    pub fn string_value(&self) -> String {
         match self {
             MapKey::Implicit(index) => index.string_value(),
             MapKey::Explicit(identifier) => identifier.string_value(),
@@ -185,8 +262,179 @@ impl PartialEq for MapKey {
 }
 impl Eq for MapKey {}
 
+/// A number, which keeps every digit of its literal however large or precise it is,
+/// so that it is printed exactly as it is written.
 #[derive(Debug, Clone)]
-pub(crate) enum Identifier {
+pub struct Number {
+    literal: String,
+    value: BigDecimal,
+}
+
+impl Number {
+    /// Parse a literal of the grammar of MERC numbers, which is the grammar of JSON numbers.
+    ///
+    /// Returns `None` if the literal is invalid, or if its exponent is out of range.
+    pub(crate) fn from_literal(literal: &str) -> Option<Number> {
+        use pest::Parser;
+        let is_number =
+            MercParser::parse(Rule::number, literal).is_ok_and(|pairs| pairs.as_str() == literal);
+        if !is_number {
+            return None;
+        }
+        Some(Number {
+            literal: literal.to_string(),
+            value: literal.parse().ok()?,
+        })
+    }
+
+    pub(crate) fn from_json(number: &serde_json::Number) -> Number {
+        Number::from_literal(&number.to_string()).expect("JSON numbers are MERC numbers")
+    }
+
+    /// Returns `None` for infinity and NaN, which are not numbers of MERC.
+    pub fn from_f64(float: f64) -> Option<Number> {
+        serde_json::Number::from_f64(float).map(|number| Number::from_json(&number))
+    }
+
+    /// Whether the literal is an integer literal, such as `42` but not `42.0`.
+    fn is_integer_literal(&self) -> bool {
+        !self.literal.contains(['.', 'e', 'E'])
+    }
+
+    /// Whether the value is an integer, such as `42` or `42.0`.
+    pub fn is_integer(&self) -> bool {
+        self.value.is_integer()
+    }
+
+    /// Returns `None` if the literal is not an integer literal, or if it does not fit.
+    pub fn as_i64(&self) -> Option<i64> {
+        self.is_integer_literal()
+            .then(|| self.value.to_i64())
+            .flatten()
+    }
+
+    /// Returns `None` if the literal is not an integer literal, or if it does not fit.
+    pub fn as_u64(&self) -> Option<u64> {
+        self.is_integer_literal()
+            .then(|| self.value.to_u64())
+            .flatten()
+    }
+
+    /// The nearest float, or `None` if the number is too large for a float.
+    pub fn as_f64(&self) -> Option<f64> {
+        self.value.to_f64().filter(|float| float.is_finite())
+    }
+
+    /// The JSON number that JSON readers read as this number exactly, if any.
+    pub(crate) fn exact_json(&self) -> Option<serde_json::Number> {
+        if let Some(integer) = self.as_i64() {
+            return Some(integer.into());
+        }
+        if let Some(integer) = self.as_u64() {
+            return Some(integer.into());
+        }
+        let float = self.as_f64()?;
+        // Zero is checked separately, as comparing a tiny exponent with zero is expensive
+        let is_exact = if float == 0.0 {
+            self.value.is_zero()
+        } else {
+            float.to_string().parse::<BigDecimal>().ok()? == self.value
+        };
+        is_exact
+            .then(|| serde_json::Number::from_f64(float))
+            .flatten()
+    }
+
+    fn to_json(&self, numbers: JsonNumbers) -> Option<serde_json::Value> {
+        if let Some(number) = self.exact_json() {
+            return Some(serde_json::Value::Number(number));
+        }
+        match numbers {
+            JsonNumbers::Number => Some(
+                self.as_f64()
+                    .and_then(serde_json::Number::from_f64)
+                    .map(serde_json::Value::Number)
+                    .unwrap_or_else(|| serde_json::Value::String(self.literal.clone())),
+            ),
+            JsonNumbers::String => Some(serde_json::Value::String(self.literal.clone())),
+            JsonNumbers::Error => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.literal)
+    }
+}
+
+macro_rules! number_from_integer {
+    ($($integer:ty),*) => {
+        $(impl From<$integer> for Number {
+            fn from(integer: $integer) -> Self {
+                Number {
+                    literal: integer.to_string(),
+                    value: integer.into(),
+                }
+            }
+        })*
+    };
+}
+
+number_from_integer!(i8, i16, i32, i64, i128, u8, u16, u32, u64, u128);
+
+/// A datetime, a local datetime, a local date or a local time, such as `1979-05-27T07:32:00Z`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Datetime(toml_edit::Datetime);
+
+impl Datetime {
+    /// Parse a literal of the grammar of MERC datetimes, which is the grammar of TOML datetimes.
+    ///
+    /// Returns `None` if the literal is invalid, or if a field is out of range, such as the day of `2024-02-30`.
+    pub(crate) fn from_literal(literal: &str) -> Option<Datetime> {
+        let datetime = literal.parse::<toml_edit::Datetime>().ok()?;
+        // 24:00:00 is accepted by the TOML parser, but it is not a valid time of RFC 3339
+        match datetime.time {
+            Some(time) if time.hour > 23 => None,
+            _ => Some(Datetime(datetime)),
+        }
+    }
+
+    pub(crate) fn from_toml(datetime: toml_edit::Datetime) -> Datetime {
+        Datetime(datetime)
+    }
+
+    pub(crate) fn as_toml(&self) -> &toml_edit::Datetime {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for Datetime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// How numbers that JSON readers cannot represent exactly, such as `123456789012345678901234567890`,
+/// are converted to JSON.
+///
+/// Integers that fit in 64 bits, and numbers that a float represents exactly, are always JSON numbers.
+#[wasm_bindgen::prelude::wasm_bindgen]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum JsonNumbers {
+    /// Convert into JSON numbers with every digit, see [`crate::merc_to_json_string_with`].
+    /// As a [`serde_json::Value`] cannot hold them, [`Value::into_json_with`] converts them into
+    /// the nearest float, or into strings if they are too large for a float
+    #[default]
+    Number,
+    /// Convert into JSON strings, which keep every digit
+    String,
+    /// Reject with [`EvaluateError::InexactNumber`]
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub enum Identifier {
     Quoted(MercString),
     Unquoted(String),
 }
@@ -202,13 +450,17 @@ impl PartialEq for Identifier {
 }
 impl Eq for Identifier {}
 impl Identifier {
-    fn string_value(&self) -> String {
+    pub fn string_value(&self) -> String {
+        self.as_str().to_string()
+    }
+
+    pub fn as_str(&self) -> &str {
         match self {
-            Identifier::Quoted(string_kind) => string_kind.string_value(),
-            Identifier::Unquoted(string) => string.to_string(),
+            Identifier::Quoted(string) => string.as_str(),
+            Identifier::Unquoted(string) => string,
         }
     }
-    fn display(&self) -> String {
+    pub fn display(&self) -> String {
         match self {
             Identifier::Quoted(string) => {
                 let s = string.string_value();
@@ -222,7 +474,7 @@ impl Identifier {
         }
     }
 
-    fn from_str(key: &str) -> Result<Identifier, EvaluateError> {
+    pub(crate) fn from_str(key: &str) -> Result<Identifier, EvaluateError> {
         Ok(if Self::needs_quote(key) {
             Identifier::Quoted(MercString::new(
                 StringKind::SinglelineRaw,
@@ -236,10 +488,48 @@ impl Identifier {
 
     fn needs_quote(key: &str) -> bool {
         !key.chars()
-            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
     }
 }
 impl MapLike {
+    pub fn kind(&self) -> &MapKind {
+        &self.kind
+    }
+
+    /// Get the value of the given key.
+    ///
+    /// Note that implicit keys (`[+]`) of an array cannot be looked up by name,
+    /// use [`MapLike::values`] instead.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.map
+            .get(&MapKey::Explicit(Identifier::Unquoted(key.to_string())))
+    }
+
+    /// Iterate the entries in the order of their first appearance.
+    pub fn iter(&self) -> impl Iterator<Item = (&MapKey, &Value)> {
+        self.map.iter()
+    }
+
+    pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = (&MapKey, &mut Value)> {
+        self.map.iter_mut()
+    }
+
+    pub(crate) fn set_kind(&mut self, kind: MapKind) {
+        self.kind = kind
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.map.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
     fn new(kind: MapKind) -> Self {
         Self {
             kind,
@@ -250,12 +540,7 @@ impl MapLike {
     fn set(self, key: MapKey, tail: &[Access], value: Value) -> Result<Self, EvaluateError> {
         let mut map = self.map;
         let map = if let Some(current_value) = map.get_mut(&key) {
-            if current_value.is_scalar() && value.is_scalar() {
-                return Err(EvaluateError::DuplicateAssignment {
-                    previously_assigned_at: current_value.inferred_at.clone(),
-                    now_assigned_again_at: value.inferred_at,
-                });
-            }
+            check_reassignment(current_value, tail, &value)?;
             *current_value = current_value.clone().set(tail, value)?;
             map
         } else {
@@ -268,47 +553,247 @@ impl MapLike {
         })
     }
 
-    fn into_json(self) -> serde_json::Value {
-        match self.kind {
+    fn into_json(self, numbers: JsonNumbers) -> Result<serde_json::Value, EvaluateError> {
+        Ok(match self.kind {
             MapKind::Object | MapKind::Map => serde_json::Value::Object(
                 self.map
                     .into_iter()
-                    .map(|(key, value)| (key.string_value(), value.into_json()))
-                    .collect(),
+                    .map(|(key, value)| Ok((key.string_value(), value.into_json_with(numbers)?)))
+                    .collect::<Result<_, EvaluateError>>()?,
             ),
             MapKind::Array => serde_json::Value::Array(
                 self.map
                     .into_iter()
-                    .map(|(_, value)| value.into_json())
-                    .collect(),
+                    .map(|(_, value)| value.into_json_with(numbers))
+                    .collect::<Result<_, EvaluateError>>()?,
             ),
-        }
+        })
     }
 }
 
-#[derive(Debug, Clone)]
-enum MapKind {
+/// Check that a path which is already assigned can be assigned again with the given remaining accesses,
+/// which is only the case if both the existing value and the new assignment are map-likes.
+///
+/// The kinds of map-likes are checked by [`Value::set`] as the remaining accesses are applied.
+fn check_reassignment(
+    current_value: &Value,
+    tail: &[Access],
+    value: &Value,
+) -> Result<(), EvaluateError> {
+    match (current_value.is_scalar(), tail.first()) {
+        (true, None) => Err(EvaluateError::DuplicateAssignment {
+            previously_assigned_at: current_value.inferred_at.clone(),
+            now_assigned_again_at: value.inferred_at.clone(),
+        }),
+        // A scalar was assigned at this path, but now it is accessed as a map-like
+        (true, Some(access)) => Err(EvaluateError::TypeMismatch(Box::new(
+            TypeMismatch::scalar_accessed(
+                current_value.typ(),
+                current_value.inferred_at.clone(),
+                access.kind.typ(),
+                access.span.clone(),
+            ),
+        ))),
+        // A map-like was inferred at this path, but now a scalar is assigned to it,
+        // which would otherwise silently replace every entry under it
+        (false, None) => Err(EvaluateError::TypeMismatch(Box::new(
+            TypeMismatch::map_like_assigned(
+                current_value.typ(),
+                current_value.inferred_at.clone(),
+                value.typ(),
+                value.inferred_at.clone(),
+            ),
+        ))),
+        (false, Some(_)) => Ok(()),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MapKind {
     Object,
     Map,
     Array,
 }
 impl Value {
-    pub(crate) fn print(&self) -> String {
-        self.kind
-            .to_string_entries("")
+    /// The span at which the type of this value was first inferred.
+    pub fn inferred_at(&self) -> &Span {
+        &self.inferred_at
+    }
+
+    pub fn typ(&self) -> Type {
+        self.kind.typ()
+    }
+
+    /// The comment attached to the entry that assigned this value, if this value is a scalar.
+    pub fn comment(&self) -> Option<&str> {
+        match &self.kind {
+            ValueKind::Scalar { comment, .. } => comment.as_deref(),
+            _ => None,
+        }
+    }
+
+    pub fn as_scalar(&self) -> Option<&ValueScalarKind> {
+        match &self.kind {
+            ValueKind::Scalar { kind, .. } => Some(kind),
+            _ => None,
+        }
+    }
+
+    pub fn as_map_like(&self) -> Option<&MapLike> {
+        match &self.kind {
+            ValueKind::MapLike(map_like) => Some(map_like),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_map_like_mut(&mut self) -> Option<&mut MapLike> {
+        match &mut self.kind {
+            ValueKind::MapLike(map_like) => Some(map_like),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&MapLike> {
+        self.as_map_like()
+            .filter(|map_like| map_like.kind == MapKind::Object)
+    }
+
+    pub fn as_map(&self) -> Option<&MapLike> {
+        self.as_map_like()
+            .filter(|map_like| map_like.kind == MapKind::Map)
+    }
+
+    pub fn as_array(&self) -> Option<&MapLike> {
+        self.as_map_like()
+            .filter(|map_like| map_like.kind == MapKind::Array)
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self.as_scalar()? {
+            ValueScalarKind::String(string) => Some(string.as_str()),
+            _ => None,
+        }
+    }
+
+    /// The name of the enum member, for example `fast` for `#fast`.
+    pub fn as_enum(&self) -> Option<&str> {
+        match self.as_scalar()? {
+            ValueScalarKind::Enum(identifier) => Some(identifier.as_str()),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.as_scalar()? {
+            ValueScalarKind::Boolean(boolean) => Some(*boolean),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self.as_scalar()? {
+            ValueScalarKind::Number(number) => number.as_i64(),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self.as_scalar()? {
+            ValueScalarKind::Number(number) => number.as_f64(),
+            _ => None,
+        }
+    }
+
+    pub fn as_number(&self) -> Option<&Number> {
+        match self.as_scalar()? {
+            ValueScalarKind::Number(number) => Some(number),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self.as_scalar(), Some(ValueScalarKind::Null))
+    }
+
+    /// Get the child value that the given access refers to.
+    ///
+    /// Returns `None` for `[+]`, because it does not refer to an existing child.
+    pub(crate) fn child(&self, access: &AccessKind) -> Option<&Value> {
+        let (ValueKind::MapLike(map_like), Some(key)) = (&self.kind, access.key()) else {
+            return None;
+        };
+        map_like.map.get(&MapKey::Explicit(key.clone()))
+    }
+
+    /// Get the value of the given key, if this value is an object or a map.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.as_map_like()
+            .filter(|map_like| map_like.kind != MapKind::Array)?
+            .get(key)
+    }
+
+    pub fn print(&self) -> String {
+        self.print_with(&FormatOptions::default())
+    }
+
+    pub fn print_with(&self, options: &FormatOptions) -> String {
+        self.print_at_with("", options, &HashSet::new())
+    }
+
+    /// Like [`Value::print_with`], but unless entries are sorted,
+    /// a blank line is kept above the entries whose values start at the given byte offsets.
+    pub(crate) fn print_with_breaks(
+        &self,
+        options: &FormatOptions,
+        breaks: &HashSet<usize>,
+    ) -> String {
+        self.print_at_with("", options, breaks)
+    }
+
+    /// Print the entries under this value as if it were at the given path, such as `.machines{api}`.
+    pub(crate) fn print_at(&self, path: &str) -> String {
+        self.print_at_with(path, &FormatOptions::default(), &HashSet::new())
+    }
+
+    fn print_at_with(
+        &self,
+        path: &str,
+        options: &FormatOptions,
+        breaks: &HashSet<usize>,
+    ) -> String {
+        let entries = self
+            .kind
+            .to_string_entries(path, &self.inferred_at, options.order);
+        let entries = match options.order {
+            EntryOrder::Sorted | EntryOrder::Grouped => entries,
+            EntryOrder::Preserve => entries
+                .into_iter()
+                .sorted_by_key(|entry| entry.position)
+                .collect(),
+        };
+        // Blank lines only make sense if entries keep their order in the source
+        let keeps_breaks = options.order != EntryOrder::Sorted;
+        entries
             .into_iter()
-            .map(|StringEntry { comment, entry }| {
-                let comment = comment
-                    .as_ref()
-                    .map(|comment| format!("\n{}\n", comment))
-                    .unwrap_or_default();
-                format!("{comment}{entry}")
-            })
+            .map(
+                |StringEntry {
+                     comment,
+                     entry,
+                     position,
+                 }| {
+                    let comment = match comment {
+                        Some(comment) => format!("\n{}\n", comment),
+                        None if keeps_breaks && breaks.contains(&position) => "\n".to_string(),
+                        None => String::new(),
+                    };
+                    format!("{comment}{entry}")
+                },
+            )
             .join("\n")
             .trim()
             .to_string()
     }
-    pub(crate) fn from_json(json: serde_json::Value) -> Result<Value, EvaluateError> {
+    pub fn from_json(json: serde_json::Value) -> Result<Value, EvaluateError> {
         let result = match json {
             serde_json::Value::Null => Value {
                 inferred_at: Span::default(),
@@ -328,7 +813,7 @@ impl Value {
                 inferred_at: Span::default(),
                 kind: ValueKind::Scalar {
                     comment: None,
-                    kind: ValueScalarKind::Number(number),
+                    kind: ValueScalarKind::Number(Number::from_json(&number)),
                 },
             },
             serde_json::Value::String(string) => Value {
@@ -353,35 +838,8 @@ impl Value {
                                 .into_iter()
                                 .enumerate()
                                 .map(|(index, value)| {
-                                    // If the `value` only needs one line of MERC to be represented
-                                    // then use implicit keys, otherwise use explicit keys
-                                    let key = match &value {
-                                        serde_json::Value::Null
-                                        | serde_json::Value::Bool(_)
-                                        | serde_json::Value::Number(_)
-                                        | serde_json::Value::String(_) => {
-                                            MapKey::Implicit(MapKeyImplicit::new())
-                                        }
-                                        serde_json::Value::Array(values) => {
-                                            if values.len() <= 1 {
-                                                MapKey::Implicit(MapKeyImplicit::new())
-                                            } else {
-                                                MapKey::Explicit(Identifier::Unquoted(
-                                                    index.to_string(),
-                                                ))
-                                            }
-                                        }
-                                        serde_json::Value::Object(map) => {
-                                            if map.len() <= 1 {
-                                                MapKey::Implicit(MapKeyImplicit::new())
-                                            } else {
-                                                MapKey::Explicit(Identifier::Unquoted(
-                                                    index.to_string(),
-                                                ))
-                                            }
-                                        }
-                                    };
-                                    Ok((key, Value::from_json(value)?))
+                                    let value = Value::from_json(value)?;
+                                    Ok((MapKey::array_item(index, &value), value))
                                 })
                                 .collect::<Result<Vec<_>, _>>()?,
                         ),
@@ -410,6 +868,17 @@ impl Value {
         };
         Ok(result)
     }
+    /// Like [`Value::from_json`], but JSON objects that the schema describes as maps,
+    /// such as `{"additionalProperties": {"type": "string"}}`, become maps instead of objects.
+    pub fn from_json_with_schema(
+        json: serde_json::Value,
+        schema: &crate::Schema,
+    ) -> Result<Value, EvaluateError> {
+        let mut value = Value::from_json(json)?;
+        schema.assign_map_kinds(&mut value);
+        Ok(value)
+    }
+
     fn update(self, entry: crate::parser::Entry) -> Result<Value, EvaluateError> {
         self.set(
             &entry.accesses.into_iter().collect_vec(),
@@ -513,8 +982,65 @@ impl Value {
         }
     }
 
-    pub(crate) fn into_json(self) -> serde_json::Value {
-        self.kind.into_json()
+    pub fn into_json(self) -> serde_json::Value {
+        self.into_json_with(JsonNumbers::Number)
+            .expect("numbers are only rejected with JsonNumbers::Error")
+    }
+
+    /// Convert this value into JSON, where numbers that JSON readers cannot represent exactly
+    /// are converted as the given option says.
+    pub fn into_json_with(self, numbers: JsonNumbers) -> Result<serde_json::Value, EvaluateError> {
+        self.kind.into_json(self.inferred_at, numbers)
+    }
+
+    pub(crate) fn scalar(kind: ValueScalarKind) -> Value {
+        Value {
+            inferred_at: Span::default(),
+            kind: ValueKind::Scalar {
+                comment: None,
+                kind,
+            },
+        }
+    }
+
+    pub(crate) fn map_like(
+        kind: MapKind,
+        entries: impl IntoIterator<Item = (MapKey, Value)>,
+    ) -> Value {
+        Value {
+            inferred_at: Span::default(),
+            kind: ValueKind::MapLike(MapLike {
+                kind,
+                map: entries.into_iter().collect(),
+            }),
+        }
+    }
+
+    /// Attach the given comment above the existing comment of this value.
+    ///
+    /// Only scalars hold comments, so the comment of a map-like value is attached to its first scalar.
+    pub(crate) fn with_comment(mut self, comment: Option<String>) -> Value {
+        if let Some(comment) = comment {
+            self.prepend_comment(comment)
+        }
+        self
+    }
+
+    fn prepend_comment(&mut self, new_comment: String) {
+        match &mut self.kind {
+            ValueKind::Scalar { comment, .. } => {
+                *comment = Some(match comment.take() {
+                    Some(comment) => format!("{new_comment}\n{comment}"),
+                    None => new_comment,
+                })
+            }
+            ValueKind::MapLike(map_like) => {
+                if let Some(first) = map_like.map.values_mut().next() {
+                    first.prepend_comment(new_comment)
+                }
+            }
+            ValueKind::Uninitialized => {}
+        }
     }
 
     fn uninitialized() -> Value {
@@ -524,6 +1050,15 @@ impl Value {
         }
     }
 
+    /// An empty object if no entry is assigned to this value,
+    /// so that no uninitialized value escapes the evaluation.
+    fn initialized(self) -> Value {
+        match self.kind {
+            ValueKind::Uninitialized => Value::map_like(MapKind::Object, []),
+            _ => self,
+        }
+    }
+
     fn update_value(self, kind: ValueKind) -> Value {
         Value { kind, ..self }
     }
@@ -541,60 +1076,155 @@ impl AccessKind {
             AccessKind::ArrayAccessImplicit | AccessKind::ArrayAccessExplicit { .. } => Type::Array,
         }
     }
+
+    /// Render this access as MERC, quoting the key only if needed.
+    pub fn display(&self) -> String {
+        match self {
+            AccessKind::ObjectAccess { key } => format!(".{}", key.display()),
+            AccessKind::MapAccess { key } => format!("{{{}}}", key.display()),
+            AccessKind::ArrayAccessImplicit => "[+]".to_string(),
+            AccessKind::ArrayAccessExplicit { key } => format!("[{}]", key.display()),
+        }
+    }
+
+    /// The same kind of access with the given key, which is `None` for `[+]`.
+    pub(crate) fn renamed(&self, key: &str) -> Option<AccessKind> {
+        let key = Identifier::from_str(key).ok()?;
+        match self {
+            AccessKind::ObjectAccess { .. } => Some(AccessKind::ObjectAccess { key }),
+            AccessKind::MapAccess { .. } => Some(AccessKind::MapAccess { key }),
+            AccessKind::ArrayAccessExplicit { .. } => Some(AccessKind::ArrayAccessExplicit { key }),
+            AccessKind::ArrayAccessImplicit => None,
+        }
+    }
+
+    /// The key of this access, which is `None` for `[+]`.
+    pub fn key(&self) -> Option<&Identifier> {
+        match self {
+            AccessKind::ObjectAccess { key }
+            | AccessKind::MapAccess { key }
+            | AccessKind::ArrayAccessExplicit { key } => Some(key),
+            AccessKind::ArrayAccessImplicit => None,
+        }
+    }
+
+    /// Whether both accesses refer to the same child of the same parent.
+    ///
+    /// Implicit array accesses (`[+]`) never refer to the same child,
+    /// because each of them creates a new element.
+    pub fn refers_to_same_child(&self, other: &AccessKind) -> bool {
+        match (self, other) {
+            (AccessKind::ObjectAccess { key: a }, AccessKind::ObjectAccess { key: b })
+            | (AccessKind::MapAccess { key: a }, AccessKind::MapAccess { key: b })
+            | (
+                AccessKind::ArrayAccessExplicit { key: a },
+                AccessKind::ArrayAccessExplicit { key: b },
+            ) => a == b,
+            _ => false,
+        }
+    }
 }
 
 impl EvaluateError {
-    pub(crate) fn display(&self, source: &str) -> String {
-        use annotate_snippets::{Renderer, Snippet};
-        let annotations: Vec<Annotation> = self.annotations();
-        let title = self.title();
+    pub fn display(&self, source: &str) -> String {
+        render(self.code(), self.title(), self.annotations(), None, source)
+    }
 
-        let message = Level::Error.title(title).snippet(
-            annotations
-                .into_iter()
-                .fold(Snippet::source(source).fold(true), |result, annotation| {
-                    result.annotation(annotation)
-                }),
-        );
+    /// The span where the error occurred.
+    pub fn span(&self) -> &Span {
+        match self {
+            EvaluateError::TypeMismatch(type_mismatch) => type_mismatch.actual_type_inferred_at(),
+            EvaluateError::DuplicateAssignment {
+                now_assigned_again_at,
+                ..
+            } => now_assigned_again_at,
+            EvaluateError::StringUnescapeError { span, .. }
+            | EvaluateError::MultilineStringNotStartingWithNewline { span }
+            | EvaluateError::MultilineStringNotEndingWithNewline { span }
+            | EvaluateError::NumberOutOfRange { span }
+            | EvaluateError::InexactNumber { span }
+            | EvaluateError::InvalidDatetime { span }
+            | EvaluateError::UnresolvedInclude { span, .. }
+            | EvaluateError::IncludeCycle { span, .. } => span,
+        }
+    }
 
-        Renderer::plain().render(message).to_string()
+    /// The message of the primary span of this error, see [`EvaluateError::span`].
+    pub fn message(&self) -> &str {
+        match self {
+            EvaluateError::TypeMismatch(type_mismatch) => &type_mismatch.error_label,
+            EvaluateError::DuplicateAssignment { .. } => {
+                "Attempting to assign a new value at the same path is not allowed."
+            }
+            EvaluateError::StringUnescapeError { error, .. } => error,
+            EvaluateError::MultilineStringNotStartingWithNewline { .. } => {
+                "The content of a multiline string should start with a newline"
+            }
+            EvaluateError::MultilineStringNotEndingWithNewline { .. } => {
+                "The content of a multiline string should end with a newline"
+            }
+            EvaluateError::NumberOutOfRange { .. } => "The exponent of this number is out of range",
+            EvaluateError::InexactNumber { .. } => {
+                "This number cannot be represented exactly by JSON readers"
+            }
+            EvaluateError::InvalidDatetime { .. } => "This is not a valid date or time",
+            EvaluateError::UnresolvedInclude { error, .. }
+            | EvaluateError::IncludeCycle { error, .. } => error,
+        }
     }
 
-    fn annotations(&self) -> Vec<Annotation> {
+    /// The secondary span of this error and its message,
+    /// such as the span where a value was previously assigned.
+    pub fn related(&self) -> Option<(&Span, &str)> {
         match self {
-            EvaluateError::TypeMismatch(type_mismatch) => type_mismatch.annotations(),
+            EvaluateError::TypeMismatch(type_mismatch) => Some((
+                &type_mismatch.expected_type_inferred_at,
+                &type_mismatch.info_label,
+            )),
             EvaluateError::DuplicateAssignment {
                 previously_assigned_at,
-                now_assigned_again_at,
-            } => [
-                Level::Info
-                    .span(previously_assigned_at.byte_range())
-                    .label("A value was previously assigned at this path."),
-                Level::Error
-                    .span(now_assigned_again_at.byte_range())
-                    .label("Attempting to assign a new value at the same path is not allowed."),
-            ]
-            .into_iter()
-            .collect_vec(),
-            EvaluateError::StringUnescapeError { span, error } => {
-                [Level::Error.span(span.byte_range()).label(error)]
-                    .into_iter()
-                    .collect_vec()
-            }
-            EvaluateError::MultilineStringNotStartingWithNewline { span } => [Level::Error
-                .span(span.byte_range())
-                .label("The content of a multiline string should start with a newline")]
-            .into_iter()
-            .collect_vec(),
-            EvaluateError::MultilineStringNotEndingWithNewline { span } => [Level::Error
-                .span(span.byte_range())
-                .label("The content of a multiline string should end with a newline")]
+                ..
+            } => Some((
+                previously_assigned_at,
+                "A value was previously assigned at this path.",
+            )),
+            EvaluateError::StringUnescapeError { .. }
+            | EvaluateError::MultilineStringNotStartingWithNewline { .. }
+            | EvaluateError::MultilineStringNotEndingWithNewline { .. }
+            | EvaluateError::NumberOutOfRange { .. }
+            | EvaluateError::InexactNumber { .. }
+            | EvaluateError::InvalidDatetime { .. }
+            | EvaluateError::UnresolvedInclude { .. }
+            | EvaluateError::IncludeCycle { .. } => None,
+        }
+    }
+
+    fn annotations(&self) -> Vec<Annotation<'_>> {
+        self.related()
+            .map(|(span, message)| Level::Info.span(span.byte_range()).label(message))
             .into_iter()
-            .collect_vec(),
+            .chain([Level::Error
+                .span(self.span().byte_range())
+                .label(self.message())])
+            .collect_vec()
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            EvaluateError::TypeMismatch(_) => "E0101",
+            EvaluateError::DuplicateAssignment { .. } => "E0102",
+            EvaluateError::StringUnescapeError { .. } => "E0103",
+            EvaluateError::MultilineStringNotStartingWithNewline { .. }
+            | EvaluateError::MultilineStringNotEndingWithNewline { .. } => "E0104",
+            EvaluateError::InvalidDatetime { .. } => "E0105",
+            EvaluateError::NumberOutOfRange { .. } => "E0106",
+            EvaluateError::InexactNumber { .. } => "E0107",
+            EvaluateError::UnresolvedInclude { .. } => "E0108",
+            EvaluateError::IncludeCycle { .. } => "E0109",
         }
     }
 
-    fn title(&self) -> &'static str {
+    pub fn title(&self) -> &'static str {
         match self {
             EvaluateError::TypeMismatch(_) => "Type Mismatch",
             EvaluateError::DuplicateAssignment { .. } => "Duplicate Assignment",
@@ -603,12 +1233,17 @@ impl EvaluateError {
             | EvaluateError::MultilineStringNotEndingWithNewline { .. } => {
                 "Incorrect multi-line string format"
             }
+            EvaluateError::InvalidDatetime { .. } => "Invalid Datetime",
+            EvaluateError::NumberOutOfRange { .. } => "Number Out Of Range",
+            EvaluateError::InexactNumber { .. } => "Inexact Number",
+            EvaluateError::UnresolvedInclude { .. } => "Unresolved Include",
+            EvaluateError::IncludeCycle { .. } => "Include Cycle",
         }
     }
 }
 
 #[derive(Debug)]
-pub(crate) enum EvaluateError {
+pub enum EvaluateError {
     TypeMismatch(Box<TypeMismatch>),
     DuplicateAssignment {
         previously_assigned_at: Span,
@@ -624,26 +1259,45 @@ pub(crate) enum EvaluateError {
     MultilineStringNotEndingWithNewline {
         span: Span,
     },
+    /// A number whose exponent is too large to be represented, such as `1e99999999999999999999`
+    NumberOutOfRange {
+        span: Span,
+    },
+    /// A number that JSON readers cannot represent exactly, rejected by [`JsonNumbers::Error`]
+    InexactNumber {
+        span: Span,
+    },
+    /// A datetime whose fields are out of range, such as `2024-02-30`
+    InvalidDatetime {
+        span: Span,
+    },
+    /// An `@include` of a file that cannot be loaded
+    UnresolvedInclude {
+        span: Span,
+        error: String,
+    },
+    /// An `@include` of a file that is already being included, such as a file that includes itself
+    IncludeCycle {
+        span: Span,
+        error: String,
+    },
 }
 #[derive(Debug)]
-pub(crate) struct TypeMismatch {
+pub struct TypeMismatch {
     expected_type_inferred_at: Span,
     actual_type_inferred_at: Span,
     info_label: String,
     error_label: String,
 }
 impl TypeMismatch {
-    fn annotations(&self) -> Vec<Annotation> {
-        [
-            Level::Info
-                .span(self.expected_type_inferred_at.byte_range())
-                .label(&self.info_label),
-            Level::Error
-                .span(self.actual_type_inferred_at.byte_range())
-                .label(&self.error_label),
-        ]
-        .into_iter()
-        .collect_vec()
+    /// The span of the access that first inferred the type of the parent value.
+    pub fn expected_type_inferred_at(&self) -> &Span {
+        &self.expected_type_inferred_at
+    }
+
+    /// The span of the access that treats the parent value as a different type.
+    pub fn actual_type_inferred_at(&self) -> &Span {
+        &self.actual_type_inferred_at
     }
 
     fn new(
@@ -665,9 +1319,49 @@ impl TypeMismatch {
             ),
         }
     }
+
+    fn scalar_accessed(
+        expected_type: Type,
+        expected_type_inferred_at: Span,
+        actual_type: Type,
+        actual_type_inferred_at: Span,
+    ) -> Self {
+        Self {
+            info_label: format!(
+                "The type of the parent value was first inferred as {} due to this assignment.",
+                expected_type.display()
+            ),
+            ..Self::new(
+                expected_type,
+                expected_type_inferred_at,
+                actual_type,
+                actual_type_inferred_at,
+            )
+        }
+    }
+
+    fn map_like_assigned(
+        expected_type: Type,
+        expected_type_inferred_at: Span,
+        actual_type: Type,
+        actual_type_inferred_at: Span,
+    ) -> Self {
+        Self {
+            expected_type_inferred_at,
+            actual_type_inferred_at,
+            info_label: format!(
+                "The type of this value was first inferred as {} due to this access.",
+                expected_type.display()
+            ),
+            error_label: format!(
+                "Error: this assignment treats the value as {}, but it was inferred as a different type.",
+                actual_type.display()
+            ),
+        }
+    }
 }
-#[derive(Debug)]
-pub(crate) enum Type {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
     Map,
     Array,
     Object,
@@ -676,9 +1370,11 @@ pub(crate) enum Type {
     Decimal,
     Null,
     Boolean,
+    Enum,
+    Datetime,
 }
 impl Type {
-    fn display(&self) -> &'static str {
+    pub fn display(&self) -> &'static str {
         match self {
             Type::Map => "Map",
             Type::Array => "Array",
@@ -688,16 +1384,79 @@ impl Type {
             Type::Decimal => "Decimal",
             Type::Null => "Null",
             Type::Boolean => "Boolean",
+            Type::Enum => "Enum",
+            Type::Datetime => "Datetime",
         }
     }
 }
 
+/// Render a diagnostic of the given source with the given title and annotations.
+pub(crate) fn render(
+    code: &str,
+    title: &str,
+    annotations: Vec<Annotation>,
+    help: Option<&str>,
+    source: &str,
+) -> String {
+    use annotate_snippets::{Renderer, Snippet};
+    let message = Level::Error.title(title).id(code).snippet(
+        annotations
+            .into_iter()
+            .fold(Snippet::source(source).fold(true), |result, annotation| {
+                result.annotation(annotation)
+            }),
+    );
+    let message = match help {
+        Some(help) => message.footer(Level::Help.title(help)),
+        None => message,
+    };
+
+    Renderer::plain().render(message).to_string()
+}
+
+impl std::fmt::Display for EvaluateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.title())
+    }
+}
+
+impl std::error::Error for EvaluateError {}
+
 pub(crate) fn evaluate(parsed: Parsed) -> Result<Value, EvaluateError> {
     let result = Value::uninitialized();
     parsed
         .into_entries()
         .into_iter()
         .try_fold(result, |result, entry| result.update(entry))
+        .map(Value::initialized)
+}
+
+/// Evaluate every entry, skipping the entries that cause errors,
+/// so that every error can be reported at once.
+pub(crate) fn evaluate_recovering(parsed: Parsed) -> (Value, Vec<EvaluateError>) {
+    let mut errors = Vec::new();
+    let value =
+        parsed.into_entries().into_iter().fold(
+            Value::uninitialized(),
+            |result, entry| match result.clone().update(entry) {
+                Ok(value) => value,
+                Err(error) => {
+                    errors.push(error);
+                    result
+                }
+            },
+        );
+    (value.initialized(), errors)
+}
+
+/// Evaluate every entry into a value of its own, so that later entries can override earlier ones,
+/// along with the errors of the entries that cannot be evaluated.
+pub(crate) fn evaluate_entries(parsed: Parsed) -> (Vec<Value>, Vec<EvaluateError>) {
+    parsed
+        .into_entries()
+        .into_iter()
+        .map(|entry| Value::uninitialized().update(entry))
+        .partition_result()
 }
 
 fn evaluate_value(
@@ -706,10 +1465,11 @@ fn evaluate_value(
 ) -> Result<Value, EvaluateError> {
     let kind = match value.kind {
         crate::parser::ValueKind::String(string) => ValueScalarKind::String(string),
-        crate::parser::ValueKind::Integer(integer) => ValueScalarKind::Integer(integer),
-        crate::parser::ValueKind::Decimal(decimal) => ValueScalarKind::Number(decimal),
+        crate::parser::ValueKind::Number(number) => ValueScalarKind::Number(number),
         crate::parser::ValueKind::Boolean(boolean) => ValueScalarKind::Boolean(boolean),
         crate::parser::ValueKind::Null => ValueScalarKind::Null,
+        crate::parser::ValueKind::Enum(identifier) => ValueScalarKind::Enum(identifier),
+        crate::parser::ValueKind::Datetime(datetime) => ValueScalarKind::Datetime(datetime),
     };
     Ok(Value {
         kind: ValueKind::Scalar { kind, comment },
@@ -720,4 +1480,36 @@ fn evaluate_value(
 struct StringEntry {
     comment: Option<String>,
     entry: String,
+    /// The byte offset of the value of this entry in the source
+    position: usize,
+}
+
+/// Options of [`crate::format`].
+#[wasm_bindgen::prelude::wasm_bindgen]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FormatOptions {
+    pub order: EntryOrder,
+}
+
+#[wasm_bindgen::prelude::wasm_bindgen]
+impl FormatOptions {
+    #[wasm_bindgen::prelude::wasm_bindgen(constructor)]
+    pub fn new(order: EntryOrder) -> FormatOptions {
+        FormatOptions { order }
+    }
+}
+
+/// The order in which the formatter prints entries.
+#[wasm_bindgen::prelude::wasm_bindgen]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EntryOrder {
+    /// Sort entries by their keys, while array items keep their positions
+    #[default]
+    Sorted,
+    /// Keep entries in the order they appear in the source
+    Preserve,
+    /// Keep entries in the order they appear in the source,
+    /// but place entries sharing a path prefix together,
+    /// at the position where the prefix first appears
+    Grouped,
 }
@@ -0,0 +1,345 @@
+use crate::{MapKey, MapKind, Span, Value, ValueScalarKind};
+use annotate_snippets::Level;
+use serde::de::{
+    DeserializeSeed, Deserializer, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+    VariantAccess, Visitor,
+};
+
+/// Deserialize an instance of `T` from MERC source.
+pub fn from_str<T: serde::de::DeserializeOwned>(merc: &str) -> Result<T, crate::Error> {
+    let value = crate::evaluate(crate::parse(merc)?)?;
+    from_value(&value).map_err(|error| crate::Error::DeserializationError(Box::new(error)))
+}
+
+/// Deserialize an instance of `T` from an evaluated MERC value.
+pub fn from_value<'de, T: serde::Deserialize<'de>>(
+    value: &'de Value,
+) -> Result<T, DeserializeError> {
+    T::deserialize(value)
+}
+
+/// An error raised while deserializing a [`Value`] into a Rust type.
+///
+/// The error is located at the span where the offending value was inferred.
+#[derive(Debug)]
+pub struct DeserializeError {
+    message: String,
+    span: Option<Span>,
+}
+
+impl DeserializeError {
+    pub(crate) const CODE: &'static str = "E0201";
+    pub(crate) const TITLE: &'static str = "Deserialization Error";
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn span(&self) -> Option<&Span> {
+        self.span.as_ref()
+    }
+
+    pub fn display(&self, source: &str) -> String {
+        let annotations = self
+            .span
+            .iter()
+            .map(|span| Level::Error.span(span.byte_range()).label(&self.message))
+            .collect();
+        crate::data::render(Self::CODE, Self::TITLE, annotations, None, source)
+    }
+
+    /// Locate this error at the given span, unless it is already located.
+    fn at(self, span: &Span) -> Self {
+        Self {
+            span: self.span.or_else(|| Some(span.clone())),
+            ..self
+        }
+    }
+}
+
+impl std::fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for DeserializeError {}
+
+impl serde::de::Error for DeserializeError {
+    fn custom<T: std::fmt::Display>(message: T) -> Self {
+        Self {
+            message: message.to_string(),
+            span: None,
+        }
+    }
+}
+
+impl<'de> Deserializer<'de> for &'de Value {
+    type Error = DeserializeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let result = match (self.as_scalar(), self.as_map_like()) {
+            (Some(scalar), _) => match scalar {
+                ValueScalarKind::String(string) => visitor.visit_borrowed_str(string.as_str()),
+                ValueScalarKind::Number(number) => {
+                    if let Some(number) = number.as_u64() {
+                        visitor.visit_u64(number)
+                    } else if let Some(number) = number.as_i64() {
+                        visitor.visit_i64(number)
+                    } else if let Some(float) = number.as_f64() {
+                        visitor.visit_f64(float)
+                    } else {
+                        Err(serde::de::Error::custom(format!(
+                            "The number {number} is too large to be deserialized"
+                        )))
+                    }
+                }
+                ValueScalarKind::Null => visitor.visit_unit(),
+                ValueScalarKind::Boolean(boolean) => visitor.visit_bool(*boolean),
+                ValueScalarKind::Enum(identifier) => {
+                    visitor.visit_borrowed_str(identifier.as_str())
+                }
+                ValueScalarKind::Datetime(datetime) => visitor.visit_string(datetime.to_string()),
+            },
+            (None, Some(map_like)) => match map_like.kind() {
+                MapKind::Object | MapKind::Map => visitor.visit_map(MapDeserializer {
+                    entries: map_like.iter(),
+                    value: None,
+                }),
+                MapKind::Array => visitor.visit_seq(SeqDeserializer(map_like.values())),
+            },
+            (None, None) => unreachable!(),
+        };
+        result.map_err(|error| error.at(self.inferred_at()))
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        if self.is_null() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+        .map_err(|error| error.at(self.inferred_at()))
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor
+            .visit_newtype_struct(self)
+            .map_err(|error| error.at(self.inferred_at()))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let result = if let Some(variant) = self.as_enum().or_else(|| self.as_str()) {
+            // Unit variant, for example `.mode = #fast` or `.mode = 'fast'`
+            visitor.visit_enum(variant.into_deserializer())
+        } else if let Some((key, value)) = self
+            .as_map_like()
+            .filter(|map_like| map_like.kind() != &MapKind::Array && map_like.len() == 1)
+            .and_then(|map_like| map_like.iter().next())
+        {
+            // Externally tagged variant, for example `.mode{fast}.level = 1`
+            visitor.visit_enum(EnumDeserializer { key, value })
+        } else {
+            Err(serde::de::Error::invalid_type(
+                unexpected(self),
+                &"a string or a map with a single key",
+            ))
+        };
+        result.map_err(|error| error.at(self.inferred_at()))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+fn unexpected(value: &Value) -> serde::de::Unexpected<'_> {
+    use serde::de::Unexpected;
+    match (value.as_scalar(), value.as_map_like()) {
+        (Some(ValueScalarKind::String(string)), _) => Unexpected::Str(string.as_str()),
+        (Some(ValueScalarKind::Number(_)), _) => Unexpected::Other("number"),
+        (Some(ValueScalarKind::Null), _) => Unexpected::Unit,
+        (Some(ValueScalarKind::Boolean(boolean)), _) => Unexpected::Bool(*boolean),
+        (Some(ValueScalarKind::Enum(identifier)), _) => Unexpected::Str(identifier.as_str()),
+        (Some(ValueScalarKind::Datetime(_)), _) => Unexpected::Other("datetime"),
+        (None, Some(map_like)) => match map_like.kind() {
+            MapKind::Object | MapKind::Map => Unexpected::Map,
+            MapKind::Array => Unexpected::Seq,
+        },
+        (None, None) => unreachable!(),
+    }
+}
+
+struct MapDeserializer<'de, I: Iterator<Item = (&'de MapKey, &'de Value)>> {
+    entries: I,
+    value: Option<&'de Value>,
+}
+
+impl<'de, I: Iterator<Item = (&'de MapKey, &'de Value)>> MapAccess<'de>
+    for MapDeserializer<'de, I>
+{
+    type Error = DeserializeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        let Some((key, value)) = self.entries.next() else {
+            return Ok(None);
+        };
+        self.value = Some(value);
+        seed.deserialize(KeyDeserializer(key.string_value()))
+            .map(Some)
+            .map_err(|error| error.at(value.inferred_at()))
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        let value = self
+            .value
+            .take()
+            .expect("next_key_seed should be called before next_value_seed");
+        seed.deserialize(value)
+    }
+}
+
+struct SeqDeserializer<I>(I);
+
+impl<'de, I: Iterator<Item = &'de Value>> SeqAccess<'de> for SeqDeserializer<I> {
+    type Error = DeserializeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Self::Error> {
+        self.0
+            .next()
+            .map(|value| seed.deserialize(value))
+            .transpose()
+    }
+}
+
+struct EnumDeserializer<'de> {
+    key: &'de MapKey,
+    value: &'de Value,
+}
+
+impl<'de> EnumAccess<'de> for EnumDeserializer<'de> {
+    type Error = DeserializeError;
+    type Variant = &'de Value;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), Self::Error> {
+        let variant = seed
+            .deserialize(KeyDeserializer(self.key.string_value()))
+            .map_err(|error| error.at(self.value.inferred_at()))?;
+        Ok((variant, self.value))
+    }
+}
+
+impl<'de> VariantAccess<'de> for &'de Value {
+    type Error = DeserializeError;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        serde::Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, Self::Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_map(visitor)
+    }
+}
+
+/// Deserializes the keys of objects and maps.
+///
+/// Keys are always strings in MERC, but they are parsed on demand
+/// so that maps with non-string keys (e.g. `HashMap<u32, _>`) can be deserialized.
+struct KeyDeserializer(String);
+
+macro_rules! deserialize_parsed_key {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                match self.0.parse() {
+                    Ok(key) => visitor.$visit(key),
+                    Err(_) => visitor.visit_string(self.0),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for KeyDeserializer {
+    type Error = DeserializeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_string(self.0)
+    }
+
+    deserialize_parsed_key! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_enum(self.0.into_deserializer())
+    }
+
+    serde::forward_to_deserialize_any! {
+        i128 u128 f32 f64 char str string bytes byte_buf option unit unit_struct
+        seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
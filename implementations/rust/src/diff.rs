@@ -0,0 +1,204 @@
+//! Semantic diff of two values, which ignores the order of entries and how they are written.
+//!
+//! Changes are reported per leaf, as the lines of MERC that are removed and added,
+//! where array items are referred to by their positions, such as `.excludes[0]`.
+use crate::{
+    data::{Value, ValueScalarKind},
+    MapKind,
+};
+use itertools::{EitherOrBoth, Itertools};
+
+/// A leaf that is added, removed or changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Added {
+        path: String,
+        value: String,
+    },
+    Removed {
+        path: String,
+        value: String,
+    },
+    Changed {
+        path: String,
+        old: String,
+        new: String,
+    },
+}
+
+impl Change {
+    /// The path of the leaf, such as `.machines{api}.size`.
+    pub fn path(&self) -> &str {
+        match self {
+            Change::Added { path, .. }
+            | Change::Removed { path, .. }
+            | Change::Changed { path, .. } => path,
+        }
+    }
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::Added { path, value } => write!(f, "+ {path} = {value}"),
+            Change::Removed { path, value } => write!(f, "- {path} = {value}"),
+            Change::Changed { path, old, new } => {
+                write!(f, "- {path} = {old}\n+ {path} = {new}")
+            }
+        }
+    }
+}
+
+/// The changes from `old` to `new`, sorted by their paths.
+///
+/// Keys of objects and maps are compared regardless of their order, and array items by their positions.
+/// A value whose type changes, such as an object that becomes a map, or an enum that becomes a string,
+/// is reported as the removal of its old leaves and the addition of its new leaves.
+pub fn diff(old: &Value, new: &Value) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_values(Kinds::Strict, ("", ""), Some(old), Some(new), &mut changes);
+    changes
+}
+
+/// The changes from `old` to `new`, where either is read from a format other than MERC, see [`diff()`].
+///
+/// As other formats cannot tell objects from maps, or enums from strings,
+/// an object that becomes a map, or an enum that becomes a string of the same name, is not a change.
+pub fn diff_across_formats(old: &Value, new: &Value) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_values(Kinds::Loose, ("", ""), Some(old), Some(new), &mut changes);
+    changes
+}
+
+/// Whether objects and maps, or enums and strings, are told apart.
+#[derive(Clone, Copy, PartialEq)]
+enum Kinds {
+    Strict,
+    Loose,
+}
+
+/// Diff the values at their paths in the old and the new value, which differ where an object became a map.
+fn diff_values(
+    kinds: Kinds,
+    (old_path, new_path): (&str, &str),
+    old: Option<&Value>,
+    new: Option<&Value>,
+    changes: &mut Vec<Change>,
+) {
+    match (old, new) {
+        (Some(old), Some(new)) => {
+            if let (Some(old_scalar), Some(new_scalar)) = (old.as_scalar(), new.as_scalar()) {
+                let is_enum = |scalar: &ValueScalarKind| matches!(scalar, ValueScalarKind::Enum(_));
+                let is_same = old_scalar.is_same(new_scalar)
+                    && (kinds == Kinds::Loose || is_enum(old_scalar) == is_enum(new_scalar));
+                if is_same {
+                    return;
+                }
+                if old_path == new_path {
+                    changes.push(Change::Changed {
+                        path: new_path.to_string(),
+                        old: old_scalar.display(),
+                        new: new_scalar.display(),
+                    })
+                } else {
+                    diff_values(kinds, (old_path, new_path), Some(old), None, changes);
+                    diff_values(kinds, (old_path, new_path), None, Some(new), changes);
+                }
+                return;
+            }
+            let is_array = |kind: &MapKind| kind == &MapKind::Array;
+            match (old.as_map_like(), new.as_map_like()) {
+                (Some(old_map), Some(new_map))
+                    if match kinds {
+                        Kinds::Strict => old_map.kind() == new_map.kind(),
+                        Kinds::Loose => is_array(old_map.kind()) == is_array(new_map.kind()),
+                    } =>
+                {
+                    let pairs = children(old_path, old)
+                        .into_iter()
+                        .merge_join_by(children(new_path, new), |old_child, new_child| {
+                            old_child.order.cmp(&new_child.order)
+                        });
+                    for pair in pairs {
+                        match pair {
+                            EitherOrBoth::Both(old, new) => diff_values(
+                                kinds,
+                                (&old.path, &new.path),
+                                Some(old.value),
+                                Some(new.value),
+                                changes,
+                            ),
+                            EitherOrBoth::Left(old) => {
+                                diff_values(kinds, (&old.path, ""), Some(old.value), None, changes)
+                            }
+                            EitherOrBoth::Right(new) => {
+                                diff_values(kinds, ("", &new.path), None, Some(new.value), changes)
+                            }
+                        }
+                    }
+                }
+                _ => {
+                    diff_values(kinds, (old_path, new_path), Some(old), None, changes);
+                    diff_values(kinds, (old_path, new_path), None, Some(new), changes);
+                }
+            }
+        }
+        (Some(old), None) => changes.extend(
+            leaves(old_path, old)
+                .into_iter()
+                .map(|(path, value)| Change::Removed { path, value }),
+        ),
+        (None, Some(new)) => changes.extend(
+            leaves(new_path, new)
+                .into_iter()
+                .map(|(path, value)| Change::Added { path, value }),
+        ),
+        (None, None) => {}
+    }
+}
+
+struct Child<'a> {
+    /// The key of objects and maps, or the position of array items
+    order: (String, usize),
+    path: String,
+    value: &'a Value,
+}
+
+/// The children of the given value, sorted by their keys, or by their positions for arrays.
+fn children<'a>(path: &str, value: &'a Value) -> Vec<Child<'a>> {
+    let Some(map_like) = value.as_map_like() else {
+        return Vec::new();
+    };
+    map_like
+        .iter()
+        .enumerate()
+        .map(|(index, (key, value))| match map_like.kind() {
+            MapKind::Object => Child {
+                order: (key.string_value(), 0),
+                path: format!("{path}.{}", key.display()),
+                value,
+            },
+            MapKind::Map => Child {
+                order: (key.string_value(), 0),
+                path: format!("{path}{{{}}}", key.display()),
+                value,
+            },
+            MapKind::Array => Child {
+                order: (String::new(), index),
+                path: format!("{path}[{index}]"),
+                value,
+            },
+        })
+        .sorted_by(|a, b| a.order.cmp(&b.order))
+        .collect()
+}
+
+fn leaves(path: &str, value: &Value) -> Vec<(String, String)> {
+    match value.as_scalar() {
+        Some(scalar) => vec![(path.to_string(), scalar.display())],
+        None => children(path, value)
+            .into_iter()
+            .flat_map(|child| leaves(&child.path, child.value))
+            .collect(),
+    }
+}
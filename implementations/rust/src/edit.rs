@@ -0,0 +1,461 @@
+//! Format-preserving edits of MERC source.
+//!
+//! Edits only rewrite the entries under the given path,
+//! every other byte of the source, including comments and ordering, is kept intact.
+//! Entries are located with the lossless concrete syntax tree, see [`crate::parse_cst`].
+use crate::{parser, Access, AccessKind, Error, NodeKind, Schema, Span};
+use itertools::Itertools;
+
+/// Assign the given value at the given path.
+///
+/// If an entry already exists at the path, only its value is replaced,
+/// otherwise a new entry is inserted after the last entry that shares the longest prefix with the path.
+/// A path that ends with `[+]` always appends a new element.
+///
+/// The value must be a MERC value, for example `'t3-large'`, `1` or `#fast`.
+pub fn set(source: &str, path: &str, value: &str) -> Result<String, EditError> {
+    let entries = parse_source(source)?;
+    let path_accesses = parse_path(path)?;
+    parser::parse_standalone_value(value).map_err(|error| EditError::InvalidValue {
+        value: value.to_string(),
+        error,
+    })?;
+    let value = value.trim();
+
+    let existing = entries
+        .iter()
+        .find(|entry| is_exactly(&entry.accesses, &path_accesses));
+    let edit = match existing {
+        Some(entry) => TextEdit {
+            range: entry.value.byte_range(),
+            replacement: value.to_string(),
+        },
+        None => {
+            let anchor = entries
+                .iter()
+                .map(|entry| (common_prefix_length(entry, &path_accesses), entry))
+                // The last entry among those with the longest common prefix
+                .max_by_key(|(length, _)| *length)
+                .map(|(_, entry)| entry.span.end())
+                // A source without entries has includes, after which the entry is inserted
+                .or_else(|| includes_end(source))
+                .unwrap_or_default();
+            let position = line_end(source, anchor);
+            TextEdit {
+                range: position..position,
+                replacement: format!(
+                    "{}{} = {value}",
+                    newline_of(source),
+                    display_path(&path_accesses)
+                ),
+            }
+        }
+    };
+    validate(apply(source, vec![edit]))
+}
+
+/// Remove every entry under the given path, including their comments.
+///
+/// Removing every entry of a source without includes is an error,
+/// because a MERC file needs at least one entry or include.
+pub fn unset(source: &str, path: &str) -> Result<String, EditError> {
+    let entries = parse_source(source)?;
+    let path_accesses = parse_path(path)?;
+    let edits = entries
+        .iter()
+        .filter(|entry| starts_with(entry, &path_accesses))
+        .map(|entry| TextEdit {
+            range: removal_range(source, &entry.span),
+            replacement: String::new(),
+        })
+        .collect_vec();
+    if edits.is_empty() {
+        return Err(EditError::NotFound {
+            path: path.to_string(),
+        });
+    }
+    if edits.len() == entries.len() && includes_end(source).is_none() {
+        return Err(EditError::NoEntryLeft {
+            path: path.to_string(),
+        });
+    }
+    validate(apply(source, edits))
+}
+
+/// Move every entry under the path `from` to the path `to`.
+///
+/// For example, moving `.a{b}` to `.c` rewrites `.a{b}.d = 1` as `.c.d = 1`.
+/// Moving to a path that already has entries is an error, as it would merge two subtrees.
+pub fn mv(source: &str, from: &str, to: &str) -> Result<String, EditError> {
+    let entries = parse_source(source)?;
+    let from_accesses = parse_path(from)?;
+    let to_accesses = parse_path(to)?;
+    let moves_to_itself = is_exactly(&from_accesses, &to_accesses);
+    if !moves_to_itself && entries.iter().any(|entry| starts_with(entry, &to_accesses)) {
+        return Err(EditError::AlreadyExists {
+            path: display_path(&to_accesses),
+        });
+    }
+    let replacement = display_path(&to_accesses);
+    let edits = entries
+        .iter()
+        .filter(|entry| starts_with(entry, &from_accesses))
+        .map(|entry| {
+            let start = entry.accesses[0].span.start();
+            let end = entry.accesses[from_accesses.len() - 1].span.end();
+            TextEdit {
+                range: start..end,
+                replacement: replacement.clone(),
+            }
+        })
+        .collect_vec();
+    if edits.is_empty() {
+        return Err(EditError::NotFound {
+            path: from.to_string(),
+        });
+    }
+    validate(apply(source, edits))
+}
+
+/// Rename the key of the last access of the given path in every entry under the path.
+///
+/// For example, renaming `.materials{metal}` to `steel` rewrites `.materials{metal}.reflectivity = 1.0`
+/// as `.materials{steel}.reflectivity = 1.0`, while `.materials{plastic}.reflectivity = 0.5` is kept intact.
+/// The new key is quoted if required, and cannot be empty.
+pub fn rename(source: &str, path: &str, new_key: &str) -> Result<String, EditError> {
+    let entries = parse_source(source)?;
+    let path_accesses = parse_path(path)?;
+    if new_key.is_empty() {
+        return Err(EditError::EmptyKey {
+            path: path.to_string(),
+        });
+    }
+    let (last, parent) = path_accesses
+        .split_last()
+        .expect("a path always has at least one access");
+    let renamed = AccessKind::renamed(&last.kind, new_key).ok_or_else(|| EditError::NoKey {
+        path: path.to_string(),
+    })?;
+
+    // Renaming to an existing key would merge two subtrees
+    let renamed_path = parent
+        .iter()
+        .cloned()
+        .chain([Access {
+            span: last.span.clone(),
+            kind: renamed.clone(),
+        }])
+        .collect_vec();
+    if !last.kind.refers_to_same_child(&renamed)
+        && entries
+            .iter()
+            .any(|entry| starts_with(entry, &renamed_path))
+    {
+        return Err(EditError::AlreadyExists {
+            path: display_path(&renamed_path),
+        });
+    }
+
+    let replacement = renamed.display();
+    let edits = entries
+        .iter()
+        .filter(|entry| starts_with(entry, &path_accesses))
+        .map(|entry| TextEdit {
+            range: entry.accesses[parent.len()].span.byte_range(),
+            replacement: replacement.clone(),
+        })
+        .collect_vec();
+    if edits.is_empty() {
+        return Err(EditError::NotFound {
+            path: path.to_string(),
+        });
+    }
+    validate(apply(source, edits))
+}
+
+/// Rewrite every access whose kind disagrees with the schema,
+/// for example `.machines.api.size` as `.machines{api}.size` if `machines` is a map of the schema.
+///
+/// See [`Schema::validate_accesses`] for how the kinds are decided.
+/// Unlike other edits, the source only needs to be syntactically valid,
+/// because mixing both kinds of accesses is a type mismatch that this fixes.
+pub fn fix_accesses(source: &str, schema: &Schema) -> Result<String, EditError> {
+    let parsed = crate::parse(source).map_err(EditError::InvalidSource)?;
+    let edits = schema
+        .validate_accesses(&parsed)
+        .into_iter()
+        .filter_map(|violation| {
+            Some(TextEdit {
+                range: violation.span().byte_range(),
+                replacement: violation.fix()?.to_string(),
+            })
+        })
+        .collect_vec();
+    validate(apply(source, edits))
+}
+
+/// Whether any entry of the source is under the given path.
+pub(crate) fn has_entries(source: &str, path: &str) -> Result<bool, EditError> {
+    let entries = parse_source(source)?;
+    let path_accesses = parse_path(path)?;
+    Ok(entries
+        .iter()
+        .any(|entry| starts_with(entry, &path_accesses)))
+}
+
+/// Replace the entries under each of the given paths with the given text,
+/// placed where the first of these entries is, such as conflict markers.
+///
+/// Unlike other edits, the edited source is not validated, as the text needs not be MERC.
+pub(crate) fn replace_entries(
+    source: &str,
+    replacements: &[(&str, String)],
+) -> Result<String, EditError> {
+    let entries = parse_source(source)?;
+    let mut edits = Vec::new();
+    for (path, replacement) in replacements {
+        let path_accesses = parse_path(path)?;
+        let mut under_path = entries
+            .iter()
+            .filter(|entry| starts_with(entry, &path_accesses));
+        let first = under_path.next().ok_or_else(|| EditError::NotFound {
+            path: path.to_string(),
+        })?;
+        edits.push(TextEdit {
+            range: first.span.byte_range(),
+            replacement: replacement.clone(),
+        });
+        edits.extend(under_path.map(|entry| TextEdit {
+            range: removal_range(source, &entry.span),
+            replacement: String::new(),
+        }));
+    }
+    Ok(apply(source, edits))
+}
+
+#[derive(Debug)]
+pub enum EditError {
+    /// The source to be edited is not valid MERC
+    InvalidSource(Error),
+    InvalidPath {
+        path: String,
+        error: Error,
+    },
+    InvalidValue {
+        value: String,
+        error: Error,
+    },
+    /// No entry exists under the given path
+    NotFound {
+        path: String,
+    },
+    /// The last access of the path to be renamed has no key, such as `[+]`
+    NoKey {
+        path: String,
+    },
+    /// The new key of the path to be renamed is empty
+    EmptyKey {
+        path: String,
+    },
+    /// The path after renaming or moving already exists
+    AlreadyExists {
+        path: String,
+    },
+    /// The entries to be removed are every entry of the source
+    NoEntryLeft {
+        path: String,
+    },
+    /// The edited source is not valid MERC,
+    /// for example because the edit causes a duplicate assignment or a type mismatch.
+    Rejected {
+        edited: String,
+        error: Error,
+    },
+}
+
+impl EditError {
+    /// Render this error as a human-readable diagnostic, given the source that was being edited.
+    pub fn display(&self, source: &str) -> String {
+        match self {
+            EditError::InvalidSource(error) => error.display(source),
+            EditError::InvalidPath { path, error } => error.display(path),
+            EditError::InvalidValue { value, error } => error.display(value),
+            EditError::NotFound { .. }
+            | EditError::NoKey { .. }
+            | EditError::EmptyKey { .. }
+            | EditError::AlreadyExists { .. }
+            | EditError::NoEntryLeft { .. } => self.to_string(),
+            EditError::Rejected { edited, error } => format!(
+                "The edit is rejected because it makes the source invalid:\n{}",
+                error.display(edited)
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for EditError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EditError::InvalidSource(error) => write!(f, "Invalid source: {error}"),
+            EditError::InvalidPath { path, error } => write!(f, "Invalid path {path}: {error}"),
+            EditError::InvalidValue { value, error } => write!(f, "Invalid value {value}: {error}"),
+            EditError::NotFound { path } => write!(f, "No entry is found under the path {path}"),
+            EditError::NoKey { path } => write!(f, "The last access of the path {path} has no key"),
+            EditError::EmptyKey { path } => {
+                write!(f, "The path {path} cannot be renamed to an empty key")
+            }
+            EditError::AlreadyExists { path } => write!(f, "The path {path} already exists"),
+            EditError::NoEntryLeft { path } => write!(
+                f,
+                "Removing the entries under the path {path} leaves no entry, but a MERC file needs at least one"
+            ),
+            EditError::Rejected { error, .. } => write!(f, "The edit is rejected: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for EditError {}
+
+struct TextEdit {
+    range: std::ops::Range<usize>,
+    replacement: String,
+}
+
+/// An entry of the source to be edited.
+struct SourceEntry {
+    /// The span of this entry, including its comments
+    span: Span,
+    accesses: Vec<Access>,
+    /// The span of the value of this entry
+    value: Span,
+}
+
+fn parse_source(source: &str) -> Result<Vec<SourceEntry>, EditError> {
+    // Only valid sources can be edited, so that any error after the edit is caused by the edit
+    crate::parse(source)
+        .and_then(crate::evaluate)
+        .map_err(EditError::InvalidSource)?;
+    let cst = crate::parse_cst(source).map_err(EditError::InvalidSource)?;
+    cst.entries()
+        .map(|entry| {
+            Ok(SourceEntry {
+                span: entry.span().clone(),
+                accesses: entry.path().map_err(EditError::InvalidSource)?,
+                value: entry.value().span().clone(),
+            })
+        })
+        .collect()
+}
+
+fn parse_path(path: &str) -> Result<Vec<Access>, EditError> {
+    parser::parse_path(path)
+        .map(|accesses| accesses.into_iter().collect())
+        .map_err(|error| EditError::InvalidPath {
+            path: path.to_string(),
+            error,
+        })
+}
+
+/// Ensure the edited source is still valid,
+/// using the same checks as evaluation, such as duplicate assignment and type mismatch.
+fn validate(edited: String) -> Result<String, EditError> {
+    match crate::parse(&edited).and_then(crate::evaluate) {
+        Ok(_) => Ok(edited),
+        Err(error) => Err(EditError::Rejected { edited, error }),
+    }
+}
+
+fn apply(source: &str, edits: Vec<TextEdit>) -> String {
+    edits
+        .into_iter()
+        .sorted_by_key(|edit| std::cmp::Reverse(edit.range.start))
+        .fold(source.to_string(), |mut result, edit| {
+            result.replace_range(edit.range, &edit.replacement);
+            result
+        })
+}
+
+pub(crate) fn display_path(accesses: &[Access]) -> String {
+    accesses
+        .iter()
+        .map(|access| access.kind.display())
+        .collect()
+}
+
+fn starts_with(entry: &SourceEntry, prefix: &[Access]) -> bool {
+    common_prefix_length(entry, prefix) == prefix.len()
+}
+
+fn is_exactly(accesses: &[Access], path: &[Access]) -> bool {
+    accesses.len() == path.len()
+        && accesses
+            .iter()
+            .zip(path)
+            .all(|(a, b)| a.kind.refers_to_same_child(&b.kind))
+}
+
+fn common_prefix_length(entry: &SourceEntry, path: &[Access]) -> usize {
+    entry
+        .accesses
+        .iter()
+        .zip(path)
+        .take_while(|(a, b)| a.kind.refers_to_same_child(&b.kind))
+        .count()
+}
+
+/// The byte offset of the end of the last include of the source, if any.
+fn includes_end(source: &str) -> Option<usize> {
+    let cst = crate::parse_cst(source).ok()?;
+    cst.root()
+        .child_nodes()
+        .filter(|node| node.kind() == NodeKind::Include)
+        .last()
+        .map(|include| include.span().end())
+}
+
+/// The byte offset of the end of the line containing the given byte offset, excluding the newline.
+fn line_end(source: &str, offset: usize) -> usize {
+    source[offset..]
+        .find(['\r', '\n'])
+        .map(|index| offset + index)
+        .unwrap_or(source.len())
+}
+
+fn newline_of(source: &str) -> &'static str {
+    if source.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    }
+}
+
+/// The range to be removed in order to remove the entry at the given span.
+///
+/// If the entry occupies its own lines, the lines are removed entirely.
+fn removal_range(source: &str, span: &Span) -> std::ops::Range<usize> {
+    let line_start = source[..span.start()]
+        .rfind('\n')
+        .map(|index| index + 1)
+        .unwrap_or(0);
+    let starts_line = source[line_start..span.start()]
+        .chars()
+        .all(|char| char == ' ' || char == '\t');
+    let start = if starts_line {
+        line_start
+    } else {
+        span.start()
+    };
+
+    let after = &source[span.end()..];
+    let trailing_spaces = after.len() - after.trim_start_matches([' ', '\t']).len();
+    let after_spaces = &after[trailing_spaces..];
+    let newline = if after_spaces.starts_with("\r\n") {
+        2
+    } else if after_spaces.starts_with('\n') {
+        1
+    } else {
+        0
+    };
+    let end = span.end() + trailing_spaces + if starts_line { newline } else { 0 };
+    start..end
+}
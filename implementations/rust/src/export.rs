@@ -0,0 +1,262 @@
+//! Conversion of MERC into YAML and TOML, which keeps comments and key order,
+//! and into JSON, which keeps every digit of numbers.
+//!
+//! The comment of each entry is emitted above its key,
+//! and the comments that are not attached to any entry are emitted at the top.
+use crate::{data::Value, Error, Errors, MapKind, ValueScalarKind};
+use itertools::Itertools;
+
+/// Evaluate the given source, along with the comments that are not attached to any entry.
+pub(crate) fn evaluate(merc: &str) -> Result<(Value, Vec<String>), Errors> {
+    let value = crate::check(merc)?;
+    Ok((value, comments(merc)?))
+}
+
+/// The comments of the given source that are not attached to any entry.
+pub(crate) fn comments(merc: &str) -> Result<Vec<String>, Error> {
+    Ok(crate::parse(merc)?
+        .comments()
+        .flat_map(str::lines)
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect())
+}
+
+pub(crate) fn to_yaml(value: &Value, comments: &[String]) -> String {
+    let lines = yaml_lines(value);
+    if comments.is_empty() {
+        format!("{}\n", lines.join("\n"))
+    } else {
+        format!("{}\n\n{}\n", comments.join("\n"), lines.join("\n"))
+    }
+}
+
+/// TOML cannot represent `null`, so a value that contains `null` is rejected.
+pub(crate) fn to_toml(value: &Value, comments: &[String]) -> Result<String, String> {
+    let mut document = toml::to_string_pretty(&Ordered(value))
+        .map_err(|error| error.to_string())?
+        .parse::<toml_edit::DocumentMut>()
+        .map_err(|error| error.to_string())?;
+    decorate_table(document.as_table_mut(), value);
+    let document = document.to_string();
+    Ok(if comments.is_empty() {
+        document
+    } else {
+        format!("{}\n\n{document}", comments.join("\n"))
+    })
+}
+
+/// Serialize a value in the order of its entries, as `serde_json::Map` sorts its keys.
+///
+/// Numbers that TOML cannot represent exactly, which only has 64-bit integers and floats, are rejected.
+struct Ordered<'a>(&'a Value);
+
+impl serde::Serialize for Ordered<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::{Error, SerializeMap, SerializeSeq};
+        if let Some(ValueScalarKind::Datetime(datetime)) = self.0.as_scalar() {
+            return datetime.as_toml().serialize(serializer);
+        }
+        if let Some(number) = self.0.as_number() {
+            if let Some(integer) = number.as_i64() {
+                return serializer.serialize_i64(integer);
+            }
+            return match number.exact_json().filter(serde_json::Number::is_f64) {
+                Some(float) => serializer.serialize_f64(float.as_f64().unwrap_or_default()),
+                None => Err(S::Error::custom(format!(
+                    "The number {number} cannot be represented exactly in TOML"
+                ))),
+            };
+        }
+        let Some(map_like) = self.0.as_map_like() else {
+            return self.0.clone().into_json().serialize(serializer);
+        };
+        match map_like.kind() {
+            MapKind::Array => {
+                let mut seq = serializer.serialize_seq(Some(map_like.len()))?;
+                for value in map_like.values() {
+                    seq.serialize_element(&Ordered(value))?;
+                }
+                seq.end()
+            }
+            MapKind::Object | MapKind::Map => {
+                let mut map = serializer.serialize_map(Some(map_like.len()))?;
+                for (key, value) in map_like.iter() {
+                    map.serialize_entry(&key.string_value(), &Ordered(value))?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+/// Serialize a value into JSON, where the numbers that JSON readers cannot represent exactly
+/// keep every digit, which only works with the serializer of `serde_json`.
+///
+/// Keys are sorted, as they are by [`Value::into_json`].
+pub(crate) struct ExactJson<'a>(pub(crate) &'a Value);
+
+impl serde::Serialize for ExactJson<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::{Error, SerializeMap, SerializeSeq};
+        if let Some(number) = self.0.as_number() {
+            return match number.exact_json() {
+                Some(number) => number.serialize(serializer),
+                None => serde_json::value::RawValue::from_string(number.to_string())
+                    .map_err(S::Error::custom)?
+                    .serialize(serializer),
+            };
+        }
+        let Some(map_like) = self.0.as_map_like() else {
+            return self.0.clone().into_json().serialize(serializer);
+        };
+        match map_like.kind() {
+            MapKind::Array => {
+                let mut seq = serializer.serialize_seq(Some(map_like.len()))?;
+                for value in map_like.values() {
+                    seq.serialize_element(&ExactJson(value))?;
+                }
+                seq.end()
+            }
+            MapKind::Object | MapKind::Map => {
+                let mut map = serializer.serialize_map(Some(map_like.len()))?;
+                for (key, value) in map_like
+                    .iter()
+                    .map(|(key, value)| (key.string_value(), value))
+                    .sorted_by(|(a, _), (b, _)| a.cmp(b))
+                {
+                    map.serialize_entry(&key, &ExactJson(value))?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+/// The lines of YAML of the given value, without indentation.
+fn yaml_lines(value: &Value) -> Vec<String> {
+    let Some(map_like) = value.as_map_like() else {
+        return yaml_scalar(value);
+    };
+    if map_like.is_empty() {
+        return vec![match map_like.kind() {
+            MapKind::Array => "[]".to_string(),
+            MapKind::Object | MapKind::Map => "{}".to_string(),
+        }];
+    }
+    map_like
+        .iter()
+        .flat_map(|(key, child)| {
+            let mut lines = yaml_lines(child).into_iter();
+            let nested = child.as_map_like().filter(|map_like| !map_like.is_empty());
+            let entry = match (map_like.kind(), nested) {
+                (MapKind::Array, _) => {
+                    let first = lines.next().unwrap_or_default();
+                    std::iter::once(format!("- {first}"))
+                        .chain(lines.map(|line| format!("  {line}")))
+                        .collect_vec()
+                }
+                (MapKind::Object | MapKind::Map, Some(nested)) => {
+                    // Sequences are not indented under their keys, by convention
+                    let indentation = match nested.kind() {
+                        MapKind::Array => "",
+                        MapKind::Object | MapKind::Map => "  ",
+                    };
+                    std::iter::once(format!("{}:", yaml_string(&key.string_value())))
+                        .chain(lines.map(|line| format!("{indentation}{line}")))
+                        .collect_vec()
+                }
+                (MapKind::Object | MapKind::Map, None) => {
+                    let first = lines.next().unwrap_or_default();
+                    // The following lines of a block scalar are already indented
+                    std::iter::once(format!("{}: {first}", yaml_string(&key.string_value())))
+                        .chain(lines)
+                        .collect_vec()
+                }
+            };
+            comment_lines(child).into_iter().chain(entry)
+        })
+        .collect()
+}
+
+/// The lines of the comment of the given value, which only scalars have.
+fn comment_lines(value: &Value) -> Vec<String> {
+    value
+        .comment()
+        .into_iter()
+        .flat_map(str::lines)
+        .map(|line| line.trim().to_string())
+        .collect()
+}
+
+fn yaml_scalar(value: &Value) -> Vec<String> {
+    let scalar = match value.as_scalar() {
+        Some(ValueScalarKind::String(string)) => yaml_string(string.as_str()),
+        Some(ValueScalarKind::Enum(identifier)) => yaml_string(identifier.as_str()),
+        Some(ValueScalarKind::Number(number)) => number.to_string(),
+        // Quoted, because YAML 1.1 reads timestamps and sexagesimal numbers such as `07:32:00`
+        Some(ValueScalarKind::Datetime(datetime)) => format!("'{datetime}'"),
+        _ => value.clone().into_json().to_string(),
+    };
+    scalar.lines().map(str::to_string).collect()
+}
+
+/// Quote the given string if required, a multiline string becomes a block scalar.
+fn yaml_string(string: &str) -> String {
+    serde_yaml::to_string(string)
+        .map(|yaml| yaml.trim_end_matches('\n').to_string())
+        .unwrap_or_else(|_| serde_json::Value::String(string.to_string()).to_string())
+}
+
+/// Attach the comments of the entries under the given value to the keys of the table.
+fn decorate_table(table: &mut toml_edit::Table, value: &Value) {
+    let Some(map_like) = value.as_map_like() else {
+        return;
+    };
+    for (key, child) in map_like.iter() {
+        let key = key.string_value();
+        let comment = comment_lines(child);
+        if !comment.is_empty() {
+            if let Some(mut key) = table.key_mut(&key) {
+                key.leaf_decor_mut()
+                    .set_prefix(comment.iter().map(|line| format!("{line}\n")).join(""));
+            }
+        }
+        let items = child
+            .as_map_like()
+            .into_iter()
+            .flat_map(|map_like| map_like.values());
+        match table.get_mut(&key) {
+            Some(toml_edit::Item::Table(table)) => decorate_table(table, child),
+            Some(toml_edit::Item::ArrayOfTables(tables)) => {
+                for (table, item) in tables.iter_mut().zip(items) {
+                    decorate_table(table, item)
+                }
+            }
+            Some(toml_edit::Item::Value(toml_edit::Value::Array(array))) => {
+                for (element, item) in array.iter_mut().zip(items) {
+                    let comment = comment_lines(item);
+                    if comment.is_empty() {
+                        continue;
+                    }
+                    let prefix = element
+                        .decor()
+                        .prefix()
+                        .and_then(|prefix| prefix.as_str())
+                        .unwrap_or_default()
+                        .to_string();
+                    let indentation = prefix.strip_prefix('\n').unwrap_or("    ");
+                    // A comment in an array must be on its own line
+                    element.decor_mut().set_prefix(format!(
+                        "\n{}{indentation}",
+                        comment
+                            .iter()
+                            .map(|line| format!("{indentation}{line}\n"))
+                            .join("")
+                    ));
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,321 @@
+//! Conversion of YAML and TOML into MERC, which keeps comments and key order.
+//!
+//! Only scalars hold comments in MERC, so the comment of a key whose value is a table or a list
+//! is attached to the first scalar under it, which is printed right below the comment
+//! when the order of entries is preserved.
+use crate::{
+    data::Value, Datetime, Identifier, MapKey, MapKind, MercString, Number, ValueScalarKind,
+};
+use std::collections::{HashMap, HashSet};
+use yaml_rust2::{parser::Parser, scanner::TScalarStyle, Event, Yaml};
+
+/// An error raised while converting YAML or TOML into MERC.
+#[derive(Debug)]
+pub struct ImportError(String);
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+/// Convert the first document of the given YAML into a MERC value.
+pub fn yaml_to_merc(yaml: &str) -> Result<Value, ImportError> {
+    let mut parser = Parser::new_from_str(yaml);
+    let mut events = Vec::new();
+    loop {
+        let (event, marker) = parser
+            .next_token()
+            .map_err(|error| ImportError(error.to_string()))?;
+        if matches!(event, Event::StreamEnd | Event::DocumentEnd) {
+            break;
+        }
+        events.push((event, marker.line()));
+    }
+    let mut reader = YamlReader {
+        events: events.into_iter().peekable(),
+        anchors: HashMap::new(),
+        comments: LineComments::new(yaml),
+    };
+    // Skip the start of the stream and of the document
+    while let Some((Event::StreamStart | Event::DocumentStart, _)) = reader.events.peek() {
+        reader.events.next();
+    }
+    if reader.events.peek().is_none() {
+        return Err(ImportError("The YAML document is empty".to_string()));
+    }
+    reader.read()
+}
+
+struct YamlReader<'a, I: Iterator<Item = (Event, usize)>> {
+    events: std::iter::Peekable<I>,
+    anchors: HashMap<usize, Value>,
+    comments: LineComments<'a>,
+}
+
+impl<I: Iterator<Item = (Event, usize)>> YamlReader<'_, I> {
+    fn read(&mut self) -> Result<Value, ImportError> {
+        let Some((event, _)) = self.events.next() else {
+            return Err(ImportError(
+                "Unexpected end of the YAML document".to_string(),
+            ));
+        };
+        let (value, anchor) =
+            match event {
+                Event::Scalar(string, style, anchor, _) => (scalar(string, style)?, anchor),
+                Event::SequenceStart(anchor, _) => {
+                    let mut items = Vec::new();
+                    while let Some((event, line)) = self.events.peek() {
+                        if matches!(event, Event::SequenceEnd) {
+                            break;
+                        }
+                        let comment = self.comments.take(*line);
+                        items.push(self.read()?.with_comment(comment));
+                    }
+                    self.events.next();
+                    (array(items), anchor)
+                }
+                Event::MappingStart(anchor, _) => {
+                    let mut entries = Vec::new();
+                    while let Some((event, line)) = self.events.next() {
+                        let key = match event {
+                            Event::MappingEnd => break,
+                            Event::Scalar(key, ..) => key,
+                            _ => {
+                                return Err(ImportError(format!(
+                                "Only scalar keys are supported, found a complex key at line {line}"
+                            )))
+                            }
+                        };
+                        let comment = self.comments.take(line);
+                        let value = self.read()?.with_comment(comment);
+                        entries.push((MapKey::Explicit(identifier(&key)?), value));
+                    }
+                    (Value::map_like(MapKind::Object, entries), anchor)
+                }
+                Event::Alias(anchor) => {
+                    return self.anchors.get(&anchor).cloned().ok_or_else(|| {
+                        ImportError(format!("Unknown alias of the anchor {anchor}"))
+                    })
+                }
+                event => return Err(ImportError(format!("Unexpected YAML event {event:?}"))),
+            };
+        // An anchor ID of 0 means the value has no anchor
+        if anchor > 0 {
+            self.anchors.insert(anchor, value.clone());
+        }
+        Ok(value)
+    }
+}
+
+/// Resolve a YAML scalar with the core schema, quoted scalars are always strings.
+fn scalar(string: String, style: TScalarStyle) -> Result<Value, ImportError> {
+    let kind = if style != TScalarStyle::Plain {
+        ValueScalarKind::String(MercString::from(string.as_str()))
+    } else {
+        match Yaml::from_str(&string) {
+            Yaml::Null => ValueScalarKind::Null,
+            Yaml::Boolean(boolean) => ValueScalarKind::Boolean(boolean),
+            Yaml::Integer(integer) => ValueScalarKind::Number(integer.into()),
+            Yaml::Real(real) => number(&real)
+                .map(ValueScalarKind::Number)
+                .unwrap_or_else(|| ValueScalarKind::String(MercString::from(real.as_str()))),
+            _ => ValueScalarKind::String(MercString::from(string.as_str())),
+        }
+    };
+    Ok(Value::scalar(kind))
+}
+
+/// The comments of a YAML source, looked up by the line of the key or the item that they document.
+struct LineComments<'a> {
+    lines: Vec<&'a str>,
+    /// The lines whose comments are already attached, so that each comment is only attached once
+    taken: HashSet<usize>,
+}
+
+impl<'a> LineComments<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            lines: source.lines().collect(),
+            taken: HashSet::new(),
+        }
+    }
+
+    /// The comment lines right above the given 1-indexed line, followed by the comment at its end.
+    fn take(&mut self, line: usize) -> Option<String> {
+        let index = line.checked_sub(1)?;
+        if !self.taken.insert(index) {
+            return None;
+        }
+        let above = self.lines[..index.min(self.lines.len())]
+            .iter()
+            .rev()
+            .map(|line| line.trim())
+            .take_while(|line| line.starts_with('#'))
+            .collect::<Vec<_>>();
+        let trailing = self
+            .lines
+            .get(index)
+            .and_then(|line| trailing_comment(line));
+        comment(above.into_iter().rev().chain(trailing))
+    }
+}
+
+/// The comment at the end of the given YAML line, if any.
+fn trailing_comment(line: &str) -> Option<&str> {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (index, char) in line.char_indices() {
+        match (quote, char) {
+            (None, '#') if previous.is_whitespace() => return Some(line[index..].trim_end()),
+            // A quote only starts a string at the start of a scalar, such as the one in `key: 'value'`
+            (None, '\'' | '"') if previous.is_whitespace() || "[{,:".contains(previous) => {
+                quote = Some(char)
+            }
+            (Some('"'), '\\') => {
+                // Skip the escaped character
+                previous = ' ';
+                quote = Some('\\');
+                continue;
+            }
+            (Some('\\'), _) => quote = Some('"'),
+            (Some(opening), _) if opening == char => quote = None,
+            _ => {}
+        }
+        previous = char;
+    }
+    None
+}
+
+/// Convert the given TOML into a MERC value.
+pub fn toml_to_merc(toml: &str) -> Result<Value, ImportError> {
+    let document = toml
+        .parse::<toml_edit::DocumentMut>()
+        .map_err(|error| ImportError(error.to_string()))?;
+    table(document.as_table())
+}
+
+fn table(table: &toml_edit::Table) -> Result<Value, ImportError> {
+    let entries = table
+        .iter()
+        .map(|(key, item)| {
+            let decor = table.key(key).map(|key| key.leaf_decor());
+            let comment = comment(
+                decor
+                    .into_iter()
+                    .flat_map(|decor| decor_comments(decor.prefix()))
+                    .chain(item_comments(item)),
+            );
+            Ok((
+                MapKey::Explicit(identifier(key)?),
+                toml_item(item)?.with_comment(comment),
+            ))
+        })
+        .collect::<Result<Vec<_>, ImportError>>()?;
+    Ok(Value::map_like(MapKind::Object, entries))
+}
+
+/// The comments of the header of a table, or at the end of the line of a value.
+fn item_comments(item: &toml_edit::Item) -> Vec<&str> {
+    match item {
+        toml_edit::Item::Table(table) => decor_comments(table.decor().prefix()),
+        toml_edit::Item::Value(value) => decor_comments(value.decor().suffix()),
+        toml_edit::Item::ArrayOfTables(_) | toml_edit::Item::None => Vec::new(),
+    }
+}
+
+fn toml_item(item: &toml_edit::Item) -> Result<Value, ImportError> {
+    match item {
+        toml_edit::Item::Table(table) => self::table(table),
+        toml_edit::Item::Value(value) => toml_value(value),
+        toml_edit::Item::ArrayOfTables(tables) => {
+            let items = tables
+                .iter()
+                .map(|table| {
+                    let comment = comment(decor_comments(table.decor().prefix()));
+                    Ok(self::table(table)?.with_comment(comment))
+                })
+                .collect::<Result<Vec<_>, ImportError>>()?;
+            Ok(array(items))
+        }
+        toml_edit::Item::None => Err(ImportError("Unexpected empty TOML item".to_string())),
+    }
+}
+
+fn toml_value(value: &toml_edit::Value) -> Result<Value, ImportError> {
+    let kind = match value {
+        toml_edit::Value::String(string) => {
+            ValueScalarKind::String(MercString::from(string.value().as_str()))
+        }
+        toml_edit::Value::Integer(integer) => ValueScalarKind::Number((*integer.value()).into()),
+        toml_edit::Value::Float(float) => Number::from_f64(*float.value())
+            .map(ValueScalarKind::Number)
+            // Infinity and NaN are not numbers of MERC
+            .unwrap_or_else(|| {
+                ValueScalarKind::String(MercString::from(float.value().to_string().as_str()))
+            }),
+        toml_edit::Value::Boolean(boolean) => ValueScalarKind::Boolean(*boolean.value()),
+        toml_edit::Value::Datetime(datetime) => {
+            ValueScalarKind::Datetime(Datetime::from_toml(*datetime.value()))
+        }
+        toml_edit::Value::Array(values) => {
+            let items = values
+                .iter()
+                .map(|value| {
+                    let decor = value.decor();
+                    let comment = comment(
+                        decor_comments(decor.prefix())
+                            .into_iter()
+                            .chain(decor_comments(decor.suffix())),
+                    );
+                    Ok(toml_value(value)?.with_comment(comment))
+                })
+                .collect::<Result<Vec<_>, ImportError>>()?;
+            return Ok(array(items));
+        }
+        toml_edit::Value::InlineTable(inline_table) => {
+            let entries = inline_table
+                .iter()
+                .map(|(key, value)| Ok((MapKey::Explicit(identifier(key)?), toml_value(value)?)))
+                .collect::<Result<Vec<_>, ImportError>>()?;
+            return Ok(Value::map_like(MapKind::Object, entries));
+        }
+    };
+    Ok(Value::scalar(kind))
+}
+
+/// The comment lines of the given decoration of TOML.
+fn decor_comments(decor: Option<&toml_edit::RawString>) -> Vec<&str> {
+    decor
+        .and_then(|decor| decor.as_str())
+        .into_iter()
+        .flat_map(str::lines)
+        .map(str::trim)
+        .filter(|line| line.starts_with('#'))
+        .collect()
+}
+
+fn comment<'a>(lines: impl IntoIterator<Item = &'a str>) -> Option<String> {
+    let comment = lines.into_iter().collect::<Vec<_>>().join("\n");
+    (!comment.is_empty()).then_some(comment)
+}
+
+fn array(items: Vec<Value>) -> Value {
+    let entries = items
+        .into_iter()
+        .enumerate()
+        .map(|(index, item)| (MapKey::array_item(index, &item), item))
+        .collect::<Vec<_>>();
+    Value::map_like(MapKind::Array, entries)
+}
+
+/// Keep every digit of the given real, unless it is not written like a MERC number, such as `.5`.
+fn number(string: &str) -> Option<Number> {
+    Number::from_literal(string).or_else(|| Number::from_f64(string.parse().ok()?))
+}
+
+fn identifier(key: &str) -> Result<Identifier, ImportError> {
+    Identifier::from_str(key).map_err(|error| ImportError(error.to_string()))
+}
@@ -0,0 +1,408 @@
+//! Resolution of `@include`, which composes a file from the entries of other files.
+//!
+//! Every loaded file is parsed as if it were placed after the files loaded before it,
+//! so that a span tells which file it belongs to, and diagnostics can span multiple files.
+use crate::{
+    data::{self, EvaluateError, Value},
+    parser::{self, Access, Entry, Parsed},
+    Error, Errors, Schema, Span,
+};
+use annotate_snippets::{Level, Renderer, Snippet};
+use itertools::Itertools;
+use nonempty::NonEmpty;
+use std::collections::HashMap;
+use wasm_bindgen::prelude::wasm_bindgen;
+
+/// Loads the files included by `@include`.
+pub trait FileLoader {
+    /// Load the source of the file at the given path,
+    /// which is already resolved against the path of the including file, using `/` as the separator.
+    fn load(&self, path: &str) -> Result<String, String>;
+}
+
+/// Load files from the file system.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FileSystemLoader;
+
+impl FileLoader for FileSystemLoader {
+    fn load(&self, path: &str) -> Result<String, String> {
+        std::fs::read_to_string(path).map_err(|error| error.to_string())
+    }
+}
+
+/// Load files from memory, such as in the browser.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Default)]
+pub struct MemoryLoader {
+    files: HashMap<String, String>,
+}
+
+#[wasm_bindgen]
+impl MemoryLoader {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> MemoryLoader {
+        MemoryLoader::default()
+    }
+
+    /// Add a file, or replace the source of an existing one.
+    pub fn insert(&mut self, path: &str, source: &str) {
+        self.files.insert(normalize(path), source.to_string());
+    }
+}
+
+impl FileLoader for MemoryLoader {
+    fn load(&self, path: &str) -> Result<String, String> {
+        self.files
+            .get(path)
+            .cloned()
+            .ok_or_else(|| "No such file".to_string())
+    }
+}
+
+/// The sources of a file and of the files that it includes, to render diagnostics that span files.
+#[derive(Debug, Clone, Default)]
+pub struct Sources {
+    files: Vec<SourceFile>,
+}
+
+#[derive(Debug, Clone)]
+struct SourceFile {
+    path: String,
+    source: String,
+    /// The offset of the spans of this file
+    offset: usize,
+    /// The path of the `@include` of the first file through which this file is loaded,
+    /// which is `None` for the first file itself
+    included_at: Option<Span>,
+}
+
+impl Sources {
+    /// The path of the file that the given span belongs to, and the span within that file.
+    pub fn locate(&self, span: &Span) -> Option<(&str, Span)> {
+        let file = self.file(span)?;
+        Some((
+            &file.path,
+            Span::new(span.start() - file.offset, span.end() - file.offset),
+        ))
+    }
+
+    /// The path of the file that the given span belongs to, and the line and the column of its start,
+    /// both counted from 1.
+    pub fn position(&self, span: &Span) -> Option<(&str, usize, usize)> {
+        let file = self.file(span)?;
+        let before = file.source.get(..span.start() - file.offset)?;
+        let line = before.matches('\n').count() + 1;
+        let column = before
+            .chars()
+            .rev()
+            .take_while(|char| *char != '\n')
+            .count()
+            + 1;
+        Some((&file.path, line, column))
+    }
+
+    /// The span of the first file that the given span comes from,
+    /// which is the path of the `@include` that loads it if it belongs to an included file.
+    pub fn span_in_first_file(&self, span: &Span) -> Option<Span> {
+        let file = self.file(span)?;
+        Some(file.included_at.clone().unwrap_or_else(|| span.clone()))
+    }
+
+    /// Render every error as human-readable diagnostics, naming the file of each span.
+    pub fn display(&self, errors: &Errors) -> String {
+        errors
+            .iter()
+            .map(|error| self.display_error(error))
+            .join("\n\n")
+    }
+
+    fn display_error(&self, error: &Error) -> String {
+        let span = error.span();
+        let annotations = error
+            .related()
+            .map(|(span, message)| (Level::Info, span, message))
+            .into_iter()
+            .chain([(Level::Error, &span, error.message())])
+            .collect_vec();
+        // One snippet per file, in the order the files are loaded,
+        // except for blank files, which have no line to annotate
+        let (blank_files, files): (Vec<_>, Vec<_>) = self
+            .files
+            .iter()
+            .partition(|file| file.source.trim().is_empty());
+        // The first file is named by whoever renders the diagnostics, but not the included ones
+        let notes = annotations
+            .iter()
+            .filter_map(|(_, span, message)| {
+                let file = blank_files.iter().find(|file| {
+                    self.file(span)
+                        .is_some_and(|span_file| span_file.offset == file.offset)
+                })?;
+                Some(match file.included_at {
+                    Some(_) => format!("{}: {message}", file.path),
+                    None => message.to_string(),
+                })
+            })
+            .collect_vec();
+        let snippets = files.into_iter().filter_map(|file| {
+            let annotations = annotations
+                .iter()
+                .filter(|(_, span, _)| {
+                    self.file(span)
+                        .is_some_and(|span_file| span_file.offset == file.offset)
+                })
+                .map(|(level, span, message)| {
+                    level
+                        .span(span.start() - file.offset..span.end() - file.offset)
+                        .label(message)
+                })
+                .collect_vec();
+            (!annotations.is_empty()).then(|| {
+                Snippet::source(&file.source)
+                    .origin(&file.path)
+                    .fold(true)
+                    .annotations(annotations)
+            })
+        });
+        let message = Level::Error
+            .title(error.title())
+            .id(error.code())
+            .snippets(snippets)
+            .footers(notes.iter().map(|note| Level::Note.title(note)));
+        let help = match error {
+            Error::ParseError(error) => error.help().map(str::to_string),
+            Error::SchemaViolation(violation) => violation.help(),
+            _ => None,
+        };
+        let message = match &help {
+            Some(help) => message.footer(Level::Help.title(help)),
+            None => message,
+        };
+        // Unlike `to_string`, `format!` drops the rendering, which borrows the notes, before them
+        format!("{}", Renderer::plain().render(message))
+    }
+
+    fn file(&self, span: &Span) -> Option<&SourceFile> {
+        self.files
+            .iter()
+            .rev()
+            .find(|file| file.offset <= span.start())
+    }
+
+    /// The offset of the next file, which leaves a gap so that the end of each file is distinct.
+    fn next_offset(&self) -> usize {
+        self.files
+            .last()
+            .map_or(0, |file| file.offset + file.source.len() + 1)
+    }
+}
+
+/// The errors of a file and of the files that it includes.
+#[derive(Debug)]
+pub struct FileErrors {
+    pub errors: Errors,
+    pub sources: Sources,
+}
+
+impl FileErrors {
+    /// Render every error as human-readable diagnostics, naming the file of each span.
+    pub fn display(&self) -> String {
+        self.sources.display(&self.errors)
+    }
+}
+
+impl std::fmt::Display for FileErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.errors)
+    }
+}
+
+impl std::error::Error for FileErrors {}
+
+/// Like [`crate::check`], but the files included by `@include` are loaded with the given loader,
+/// and their entries are evaluated before the entries of the including file.
+///
+/// Relative paths are resolved against the directory of `path`.
+pub fn check_with_includes(
+    path: &str,
+    merc: &str,
+    loader: &dyn FileLoader,
+) -> Result<Value, FileErrors> {
+    check_with_sources(path, merc, loader).map(|(value, _)| value)
+}
+
+/// Like [`check_with_includes`], but also return the sources,
+/// to render the diagnostics of errors raised later on the value.
+pub fn check_with_sources(
+    path: &str,
+    merc: &str,
+    loader: &dyn FileLoader,
+) -> Result<(Value, Sources), FileErrors> {
+    let (parsed, errors, sources) = resolve_includes(path, merc, loader);
+    match evaluate_resolved(parsed, errors) {
+        Ok(value) => Ok((value, sources)),
+        Err(errors) => Err(FileErrors { errors, sources }),
+    }
+}
+
+/// Like [`crate::check_with_schema`], but the files included by `@include` are loaded with the given loader,
+/// see [`check_with_includes`].
+pub fn check_with_schema_and_includes(
+    path: &str,
+    merc: &str,
+    schema: &Schema,
+    loader: &dyn FileLoader,
+) -> Result<Value, FileErrors> {
+    let (parsed, errors, sources) = resolve_includes(path, merc, loader);
+    let access_violations = schema.validate_accesses(&parsed);
+    evaluate_resolved(parsed, errors)
+        .and_then(|value| crate::check_schema(schema, access_violations, value))
+        .map_err(|errors| FileErrors { errors, sources })
+}
+
+/// Evaluate the entries of a file and of the files that it includes,
+/// failing with the given errors of resolving them, if any, along with the errors of evaluation.
+fn evaluate_resolved(parsed: Parsed, mut errors: Vec<Error>) -> Result<Value, Errors> {
+    let (value, evaluate_errors) = data::evaluate_recovering(parsed);
+    errors.extend(
+        evaluate_errors
+            .into_iter()
+            .map(|error| Error::EvaluationError(Box::new(error))),
+    );
+    if errors.is_empty() {
+        Ok(value)
+    } else {
+        errors.sort_by_key(|error| error.span().start());
+        Err(Errors(errors))
+    }
+}
+
+/// The entries of the given file, preceded by the entries of the files that it includes,
+/// along with the errors of loading and parsing them.
+pub(crate) fn resolve_includes(
+    path: &str,
+    merc: &str,
+    loader: &dyn FileLoader,
+) -> (Parsed, Vec<Error>, Sources) {
+    let mut resolver = Resolver {
+        loader,
+        sources: Sources::default(),
+        errors: Vec::new(),
+        including: Vec::new(),
+    };
+    let entries = resolver.entries(normalize(path), merc.to_string(), &[], None);
+    (
+        Parsed::from_entries(entries),
+        resolver.errors,
+        resolver.sources,
+    )
+}
+
+struct Resolver<'a> {
+    loader: &'a dyn FileLoader,
+    sources: Sources,
+    errors: Vec<Error>,
+    /// The paths of the files that are being included, to detect cycles
+    including: Vec<String>,
+}
+
+impl Resolver<'_> {
+    /// The entries of the given file, preceded by the entries of the files that it includes,
+    /// placed under the given prefix.
+    fn entries(
+        &mut self,
+        path: String,
+        source: String,
+        prefix: &[Access],
+        included_at: Option<Span>,
+    ) -> Vec<Entry> {
+        let offset = self.sources.next_offset();
+        // Spaces are skipped by the parser, so the spans of the source start at the offset
+        let (parsed, errors) = parser::parse_recovering(&format!("{}{source}", " ".repeat(offset)));
+        self.errors.extend(errors);
+        self.sources.files.push(SourceFile {
+            path: path.clone(),
+            source,
+            offset,
+            included_at: included_at.clone(),
+        });
+        self.including.push(path.clone());
+        let mut entries = Vec::new();
+        for include in parsed.includes() {
+            let included = resolve(&path, include.path.as_str());
+            let prefix = prefix
+                .iter()
+                .chain(include.at.iter().flatten())
+                .cloned()
+                .collect_vec();
+            if let Some(index) = self.including.iter().position(|path| *path == included) {
+                let cycle = self.including[index..]
+                    .iter()
+                    .chain([&included])
+                    .map(|path| format!("`{path}`"))
+                    .join(" -> ");
+                self.push_error(EvaluateError::IncludeCycle {
+                    span: include.path_span.clone(),
+                    error: format!("This include forms a cycle: {cycle}"),
+                });
+                continue;
+            }
+            match self.loader.load(&included) {
+                Ok(source) => {
+                    let included_at = included_at
+                        .clone()
+                        .unwrap_or_else(|| include.path_span.clone());
+                    entries.extend(self.entries(included, source, &prefix, Some(included_at)))
+                }
+                Err(error) => self.push_error(EvaluateError::UnresolvedInclude {
+                    span: include.path_span.clone(),
+                    error: format!("Unable to load `{included}`: {error}"),
+                }),
+            }
+        }
+        entries.extend(parsed.into_entries().into_iter().map(|entry| {
+            let accesses = match NonEmpty::from_slice(prefix) {
+                Some(mut accesses) => {
+                    accesses.extend(entry.accesses);
+                    accesses
+                }
+                None => entry.accesses,
+            };
+            Entry { accesses, ..entry }
+        }));
+        self.including.pop();
+        entries
+    }
+
+    fn push_error(&mut self, error: EvaluateError) {
+        self.errors.push(Error::EvaluationError(Box::new(error)))
+    }
+}
+
+/// Resolve the path of an `@include` against the path of the including file.
+fn resolve(including: &str, included: &str) -> String {
+    match including.rsplit_once('/') {
+        Some((directory, _)) if !included.starts_with('/') => {
+            normalize(&format!("{directory}/{included}"))
+        }
+        _ => normalize(included),
+    }
+}
+
+/// Remove the `.` segments of the given path, and the `..` segments that follow a directory.
+fn normalize(path: &str) -> String {
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in path.split('/') {
+        match segment {
+            "." => {}
+            ".." if segments
+                .last()
+                .is_some_and(|last| !last.is_empty() && *last != "..") =>
+            {
+                segments.pop();
+            }
+            _ => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
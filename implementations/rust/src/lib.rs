@@ -1,30 +1,334 @@
-use data::evaluate;
-use parser::Rule;
+//! MERC, the MErcilessly Redundant Config language.
+//!
+//! ```
+//! let parsed = merc::parse(".materials{metal}.reflectivity = 1.0").unwrap();
+//! let value = merc::evaluate(parsed).unwrap();
+//! let metal = value.get("materials").unwrap().get("metal").unwrap();
+//! assert_eq!(metal.get("reflectivity").unwrap().as_f64(), Some(1.0));
+//! ```
+use itertools::Itertools;
 use wasm_bindgen::prelude::*;
 
+mod completion;
+mod cst;
 mod data;
+mod de;
+mod diff;
+mod edit;
+mod export;
+mod import;
+mod include;
+mod merge;
+mod overlay;
+mod parse_error;
 mod parser;
+mod query;
+mod references;
+mod schema;
+mod ser;
+
+pub use completion::{complete, Completion, CompletionKind};
+pub use cst::{parse_cst, Cst, CstElement, CstEntry, CstNode, CstToken, NodeKind, TokenKind};
+pub use data::{
+    Datetime, EntryOrder, EvaluateError, FormatOptions, Identifier, JsonNumbers, MapKey,
+    MapKeyImplicit, MapKind, MapLike, Number, Type, TypeMismatch, Value, ValueScalarKind,
+};
+pub use de::{from_str, from_value, DeserializeError};
+pub use diff::{diff, diff_across_formats, Change};
+pub use edit::{fix_accesses, mv, rename, set, unset, EditError};
+pub use import::{toml_to_merc, yaml_to_merc, ImportError};
+pub use include::{
+    check_with_includes, check_with_schema_and_includes, check_with_sources, FileErrors,
+    FileLoader, FileSystemLoader, MemoryLoader, Sources,
+};
+pub use merge::{merge, ApplyError, Conflict, Merge};
+pub use overlay::{Overlay, Provenance};
+pub use parse_error::{ParseError, ParseErrorKind};
+pub use parser::{Access, AccessKind, MercString, Parsed, Span};
+pub use query::{print_matches, query, QueryMatch};
+pub use references::{find_references, PathReferences, Reference};
+pub use schema::{Schema, SchemaError, SchemaViolation};
+pub use ser::{to_string, to_value, SerializeError};
 
 #[cfg(test)]
 mod test_cases;
 
 #[derive(Debug)]
-pub(crate) enum Error {
-    ParseError(Box<pest::error::Error<Rule>>),
+pub enum Error {
+    ParseError(Box<ParseError>),
     EvaluationError(Box<data::EvaluateError>),
+    DeserializationError(Box<DeserializeError>),
+    SchemaViolation(Box<SchemaViolation>),
+}
+
+/// Parse the given MERC source without evaluating it.
+pub fn parse(merc: &str) -> Result<Parsed, Error> {
+    parser::parse(merc)
+}
+
+/// Parse a path, such as `.machines{api}.size`, into its accesses.
+pub fn parse_path(path: &str) -> Result<Vec<Access>, Error> {
+    parser::parse_path(path).map(|accesses| accesses.into_iter().collect())
+}
+
+/// Evaluate the parsed entries into a single value.
+pub fn evaluate(parsed: Parsed) -> Result<Value, Error> {
+    data::evaluate(parsed).map_err(|error| Error::EvaluationError(Box::new(error)))
+}
+
+/// Parse and evaluate the given MERC source.
+///
+/// Unlike [`parse`] and [`evaluate`], which stop at the first error,
+/// this recovers from errors so that every error of the source is reported.
+///
+/// The files included by `@include` are not loaded, see [`check_with_includes`].
+pub fn check(merc: &str) -> Result<Value, Errors> {
+    let (parsed, mut errors) = parser::parse_recovering(merc);
+    let (value, evaluate_errors) = data::evaluate_recovering(parsed);
+    errors.extend(
+        evaluate_errors
+            .into_iter()
+            .map(|error| Error::EvaluationError(Box::new(error))),
+    );
+    if errors.is_empty() {
+        Ok(value)
+    } else {
+        errors.sort_by_key(|error| error.span().start());
+        Err(Errors(errors))
+    }
+}
+
+/// Like [`check`], but also validate the value and the accesses against the given schema.
+///
+/// Schema violations are only reported if the source has no other errors.
+/// The files included by `@include` are not loaded, see [`check_with_schema_and_includes`].
+pub fn check_with_schema(merc: &str, schema: &Schema) -> Result<Value, Errors> {
+    let value = check(merc)?;
+    let access_violations = schema.validate_accesses(&parser::parse(merc)?);
+    check_schema(schema, access_violations, value)
+}
+
+/// Fail with the given violations of the accesses, along with the violations of the value, if any.
+fn check_schema(
+    schema: &Schema,
+    access_violations: Vec<SchemaViolation>,
+    value: Value,
+) -> Result<Value, Errors> {
+    let violations = access_violations
+        .into_iter()
+        .chain(schema.validate(&value))
+        .collect_vec();
+    if violations.is_empty() {
+        Ok(value)
+    } else {
+        Err(Errors(
+            violations
+                .into_iter()
+                .map(|violation| Error::SchemaViolation(Box::new(violation)))
+                .sorted_by_key(|error| error.span().start())
+                .collect(),
+        ))
+    }
+}
+
+/// Every error of a MERC source, sorted by their position.
+#[derive(Debug)]
+pub struct Errors(Vec<Error>);
+
+impl Errors {
+    pub fn iter(&self) -> impl Iterator<Item = &Error> {
+        self.0.iter()
+    }
+
+    pub fn into_vec(self) -> Vec<Error> {
+        self.0
+    }
+
+    /// Render every error as human-readable diagnostics of the given source.
+    pub fn display(&self, source: &str) -> String {
+        self.0
+            .iter()
+            .map(|error| error.display(source))
+            .join("\n\n")
+    }
+}
+
+impl std::fmt::Display for Errors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.iter().join("\n"))
+    }
+}
+
+impl std::error::Error for Errors {}
+
+impl From<Error> for Errors {
+    fn from(error: Error) -> Self {
+        Errors(vec![error])
+    }
+}
+
+/// A diagnostic of a MERC source, for editors to underline.
+#[wasm_bindgen(getter_with_clone)]
+pub struct Diagnostic {
+    /// The start of the span, in UTF-16 code units, as JavaScript strings are indexed
+    pub start: usize,
+    /// The end of the span, in UTF-16 code units, as JavaScript strings are indexed
+    pub end: usize,
+    pub code: String,
+    pub title: String,
+    /// The rendered diagnostic, as shown by the CLI
+    pub message: String,
+}
+
+/// Every diagnostic of the given MERC source, empty if the source is valid.
+#[wasm_bindgen]
+pub fn merc_diagnostics(merc: &str) -> Vec<Diagnostic> {
+    let utf16_offset = |offset: usize| merc[..offset].encode_utf16().count();
+    match check(merc) {
+        Ok(_) => Vec::new(),
+        Err(errors) => errors
+            .iter()
+            .map(|error| {
+                let span = error.span();
+                Diagnostic {
+                    start: utf16_offset(span.start()),
+                    end: utf16_offset(span.end()),
+                    code: error.code().to_string(),
+                    title: error.title().to_string(),
+                    message: error.display(merc),
+                }
+            })
+            .collect(),
+    }
+}
+
+/// A completion for editors in JavaScript, see [`Completion`].
+#[wasm_bindgen(getter_with_clone)]
+pub struct CompletionItem {
+    pub label: String,
+    /// `property`, `enumMember` or `value`
+    pub kind: String,
+    pub detail: Option<String>,
+    pub documentation: Option<String>,
+    pub insert_text: String,
+    /// The start of the text to be replaced, in UTF-16 code units
+    pub start: usize,
+    /// The end of the text to be replaced, in UTF-16 code units
+    pub end: usize,
+}
+
+/// The completions at the given UTF-16 offset of the MERC source, using the given JSON Schema.
+#[wasm_bindgen]
+pub fn merc_completions(
+    merc: &str,
+    offset: usize,
+    schema: &str,
+) -> Result<Vec<CompletionItem>, String> {
+    let schema: Schema = schema.parse().map_err(|err: SchemaError| err.to_string())?;
+    let utf16_offset = |offset: usize| merc[..offset].encode_utf16().count();
+    // Convert the UTF-16 offset into a byte offset
+    let offset = merc
+        .char_indices()
+        .scan(0, |utf16_count, (index, char)| {
+            let current = *utf16_count;
+            *utf16_count += char.len_utf16();
+            Some((index, current))
+        })
+        .find(|(_, utf16_count)| *utf16_count >= offset)
+        .map_or(merc.len(), |(index, _)| index);
+    Ok(complete(merc, offset, &schema)
+        .into_iter()
+        .map(|completion| CompletionItem {
+            label: completion.label,
+            kind: match completion.kind {
+                CompletionKind::Property => "property",
+                CompletionKind::EnumMember => "enumMember",
+                CompletionKind::Value => "value",
+            }
+            .to_string(),
+            detail: completion.detail,
+            documentation: completion.documentation,
+            insert_text: completion.insert_text,
+            start: utf16_offset(completion.replace.start()),
+            end: utf16_offset(completion.replace.end()),
+        })
+        .collect())
+}
+
+/// The changes from the `old` MERC source to the `new` one, one line per removed or added leaf,
+/// such as `- .machines{api}.size = 't3-medium'`, see [`diff()`].
+#[wasm_bindgen]
+pub fn merc_diff(old: &str, new: &str) -> Result<String, String> {
+    let old_value = check(old).map_err(|err| err.display(old))?;
+    let new_value = check(new).map_err(|err| err.display(new))?;
+    Ok(diff(&old_value, &new_value).iter().join("\n"))
+}
+
+/// The values of the given MERC source that the query matches, such as `.machines{*}.size`,
+/// printed as lines of MERC, see [`query()`].
+#[wasm_bindgen]
+pub fn merc_query(merc: &str, query: &str) -> Result<String, String> {
+    let value = check(merc).map_err(|err| err.display(merc))?;
+    let matches = self::query(&value, query).map_err(|err| err.display(query))?;
+    Ok(print_matches(&matches))
+}
+
+/// Like [`merc_to_json_string`], but numbers that JSON readers cannot represent exactly,
+/// such as `123456789012345678901234567890`, are converted as the given option says.
+#[wasm_bindgen]
+pub fn merc_to_json_string_with(merc: &str, numbers: JsonNumbers) -> Result<String, String> {
+    let value = check(merc).map_err(|err| err.display(merc))?;
+    value_to_json_string(value, numbers, |err| err.display(merc))
+}
+
+fn value_to_json_string(
+    value: Value,
+    numbers: JsonNumbers,
+    display: impl Fn(EvaluateError) -> String,
+) -> Result<String, String> {
+    let json = match numbers {
+        JsonNumbers::Number => serde_json::to_string_pretty(&export::ExactJson(&value)),
+        JsonNumbers::String | JsonNumbers::Error => {
+            serde_json::to_string_pretty(&value.into_json_with(numbers).map_err(display)?)
+        }
+    };
+    json.map_err(|err| err.to_string())
 }
 
 #[wasm_bindgen]
 pub fn merc_to_json_string(merc: &str) -> Result<String, String> {
-    merc_to_json(merc)
-        .map_err(|err| err.display(merc))
-        .and_then(|json| serde_json::to_string_pretty(&json).map_err(|err| err.to_string()))
+    merc_to_json_string_with(merc, JsonNumbers::default())
 }
 
-fn merc_to_json(merc: &str) -> Result<serde_json::Value, Error> {
-    let parsed = parser::parse(merc)?;
-    let merc_value = evaluate(parsed).map_err(|error| Error::EvaluationError(Box::new(error)))?;
-    Ok(merc_value.into_json())
+/// Like [`merc_to_json_string`], but the files included by `@include` are loaded from `files`,
+/// resolving relative paths against `path`, the path of the given source.
+#[wasm_bindgen]
+pub fn merc_to_json_string_with_includes(
+    path: &str,
+    merc: &str,
+    files: &MemoryLoader,
+) -> Result<String, String> {
+    merc_to_json_string_with_loader(path, merc, files, JsonNumbers::default())
+}
+
+/// Like [`merc_to_json_string_with`], but the files included by `@include` are loaded with the given loader,
+/// resolving relative paths against `path`, the path of the given source.
+pub fn merc_to_json_string_with_loader(
+    path: &str,
+    merc: &str,
+    loader: &dyn FileLoader,
+    numbers: JsonNumbers,
+) -> Result<String, String> {
+    let (value, sources) =
+        include::check_with_sources(path, merc, loader).map_err(|errors| errors.display())?;
+    value_to_json_string(value, numbers, |err| {
+        sources.display(&Error::EvaluationError(Box::new(err)).into())
+    })
+}
+
+#[cfg(test)]
+fn merc_to_json(merc: &str) -> Result<serde_json::Value, Errors> {
+    Ok(check(merc)?.into_json())
 }
 
 #[wasm_bindgen]
@@ -34,9 +338,73 @@ pub fn json_to_merc_string(json: &str) -> Result<String, String> {
         .map_err(|err| err.to_string())
 }
 
-fn json_to_merc(json: &str) -> anyhow::Result<data::Value> {
+/// Like [`json_to_merc_string`], but JSON objects that the schema describes as maps become maps.
+pub fn json_to_merc_string_with_schema(json: &str, schema: &Schema) -> Result<String, String> {
+    serde_json::from_str(json)
+        .map_err(|err| err.to_string())
+        .and_then(|parsed| {
+            Value::from_json_with_schema(parsed, schema).map_err(|err| err.display(json))
+        })
+        .map(|merc| merc.print())
+}
+
+fn json_to_merc(json: &str) -> anyhow::Result<Value> {
     let parsed = serde_json::from_str(json)?;
-    data::Value::from_json(parsed).map_err(|err| anyhow::anyhow!("{}", err.display(json)))
+    Value::from_json(parsed).map_err(|err| anyhow::anyhow!("{}", err.display(json)))
+}
+
+/// Convert MERC into YAML, keeping its comments and key order.
+#[wasm_bindgen]
+pub fn merc_to_yaml_string(merc: &str) -> Result<String, String> {
+    let (value, comments) = export::evaluate(merc).map_err(|err| err.display(merc))?;
+    Ok(export::to_yaml(&value, &comments))
+}
+
+/// Convert MERC into TOML, keeping its comments and key order.
+#[wasm_bindgen]
+pub fn merc_to_toml_string(merc: &str) -> Result<String, String> {
+    let (value, comments) = export::evaluate(merc).map_err(|err| err.display(merc))?;
+    export::to_toml(&value, &comments)
+}
+
+/// Like [`merc_to_yaml_string`], but the files included by `@include` are loaded with the given loader,
+/// resolving relative paths against `path`, the path of the given source.
+pub fn merc_to_yaml_string_with_loader(
+    path: &str,
+    merc: &str,
+    loader: &dyn FileLoader,
+) -> Result<String, String> {
+    let value = check_with_includes(path, merc, loader).map_err(|errors| errors.display())?;
+    let comments = export::comments(merc).map_err(|err| err.display(merc))?;
+    Ok(export::to_yaml(&value, &comments))
+}
+
+/// Like [`merc_to_toml_string`], but the files included by `@include` are loaded with the given loader,
+/// resolving relative paths against `path`, the path of the given source.
+pub fn merc_to_toml_string_with_loader(
+    path: &str,
+    merc: &str,
+    loader: &dyn FileLoader,
+) -> Result<String, String> {
+    let value = check_with_includes(path, merc, loader).map_err(|errors| errors.display())?;
+    let comments = export::comments(merc).map_err(|err| err.display(merc))?;
+    export::to_toml(&value, &comments)
+}
+
+/// Convert YAML into MERC, keeping its comments and key order.
+#[wasm_bindgen]
+pub fn yaml_to_merc_string(yaml: &str) -> Result<String, String> {
+    yaml_to_merc(yaml)
+        .map(|merc| merc.print_with(&FormatOptions::new(EntryOrder::Preserve)))
+        .map_err(|err| err.to_string())
+}
+
+/// Convert TOML into MERC, keeping its comments and key order.
+#[wasm_bindgen]
+pub fn toml_to_merc_string(toml: &str) -> Result<String, String> {
+    toml_to_merc(toml)
+        .map(|merc| merc.print_with(&FormatOptions::new(EntryOrder::Preserve)))
+        .map_err(|err| err.to_string())
 }
 
 #[wasm_bindgen]
@@ -88,14 +456,80 @@ pub fn yaml_to_json_string(yaml: &str) -> Result<String, String> {
 }
 
 impl Error {
-    fn display(&self, source: &str) -> String {
+    /// Render this error as a human-readable diagnostic of the given source.
+    pub fn display(&self, source: &str) -> String {
         match self {
-            Error::ParseError(error) => error.to_string(),
+            Error::ParseError(error) => error.display(source),
             Error::EvaluationError(error) => error.display(source),
+            Error::DeserializationError(error) => error.display(source),
+            Error::SchemaViolation(error) => error.display(source),
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::ParseError(error) => error.kind().code(),
+            Error::EvaluationError(error) => error.code(),
+            Error::DeserializationError(_) => DeserializeError::CODE,
+            Error::SchemaViolation(error) => error.code(),
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Error::ParseError(error) => error.kind().title(),
+            Error::EvaluationError(error) => error.title(),
+            Error::DeserializationError(_) => DeserializeError::TITLE,
+            Error::SchemaViolation(error) => error.title(),
+        }
+    }
+
+    /// The message of the primary span of this error, see [`Error::span`].
+    pub fn message(&self) -> &str {
+        match self {
+            Error::ParseError(error) => error.message(),
+            Error::EvaluationError(error) => error.message(),
+            Error::DeserializationError(error) => error.message(),
+            Error::SchemaViolation(error) => error.message(),
+        }
+    }
+
+    /// The secondary span of this error and its message, if any.
+    pub fn related(&self) -> Option<(&Span, &str)> {
+        match self {
+            Error::EvaluationError(error) => error.related(),
+            Error::ParseError(_) | Error::DeserializationError(_) | Error::SchemaViolation(_) => {
+                None
+            }
+        }
+    }
+
+    /// The span where the error occurred.
+    pub fn span(&self) -> Span {
+        match self {
+            Error::ParseError(error) => error.span().clone(),
+            Error::EvaluationError(error) => error.span().clone(),
+            Error::DeserializationError(error) => {
+                error.span().cloned().unwrap_or_else(Span::default)
+            }
+            Error::SchemaViolation(error) => error.span().clone(),
         }
     }
 }
 
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::ParseError(error) => write!(f, "{error}"),
+            Error::EvaluationError(error) => write!(f, "{error}"),
+            Error::DeserializationError(error) => write!(f, "{error}"),
+            Error::SchemaViolation(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
 #[wasm_bindgen]
 extern "C" {
     pub fn alert(s: &str);
@@ -125,11 +559,43 @@ mod test_lib {
     }
 }
 
+/// Format the given MERC source, the default options are used if `options` is not given.
 #[wasm_bindgen]
-pub fn format_merc(merc: &str) -> Result<String, String> {
-    fn format_merc(merc: &str) -> Result<String, Error> {
-        let parsed = parser::parse(merc)?;
-        parsed.into_string()
-    }
-    format_merc(merc).map_err(|err| err.display(merc))
+pub fn format_merc(merc: &str, options: Option<FormatOptions>) -> Result<String, String> {
+    format(merc, &options.unwrap_or_default()).map_err(|err| err.display(merc))
+}
+
+/// Format the given MERC source.
+///
+/// Every `@include` is placed above the entries, as the entries of included files are evaluated first.
+/// Unless entries are sorted, the blank lines between entries are kept,
+/// and the comments at the end of the file are always kept.
+pub fn format(merc: &str, options: &FormatOptions) -> Result<String, Errors> {
+    let value = check(merc)?;
+    let cst = parse_cst(merc)?;
+    // The values of the entries that are separated from the previous entry by a blank line
+    let breaks = cst
+        .entries()
+        .tuple_windows()
+        .filter(|(previous, entry)| {
+            merc[previous.span().end()..entry.span().start()]
+                .matches('\n')
+                .count()
+                > 1
+        })
+        .map(|(_, entry)| entry.value().span().start())
+        .collect();
+    let entries = value.print_with_breaks(options, &breaks);
+    let includes = parser::parse(merc)?
+        .includes()
+        .map(|include| include.display())
+        .join("\n");
+    let trailing_comments = cst
+        .trailing_comments()
+        .map(|comment| comment.text().trim_end())
+        .join("\n");
+    Ok([includes, entries, trailing_comments]
+        .into_iter()
+        .filter(|section| !section.is_empty())
+        .join("\n\n"))
 }
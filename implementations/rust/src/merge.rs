@@ -0,0 +1,326 @@
+//! Three-way merge of values, such as for a merge driver of Git.
+//!
+//! As every line of MERC is a full path to a leaf, two sides only conflict
+//! when they change the same leaf differently.
+use crate::{
+    data::Value, diff, edit, edit::EditError, parser, AccessKind, Change, MapKey, MapKind, MapLike,
+};
+use itertools::Itertools;
+
+/// The result of [`merge()`].
+#[derive(Debug, Clone)]
+pub struct Merge {
+    /// The merged entries, without the conflicting ones, or `None` if no entry is left
+    pub value: Option<Value>,
+    pub conflicts: Vec<Conflict>,
+}
+
+impl Merge {
+    /// Print the merged entries, followed by the conflicts with their markers.
+    pub fn print(&self) -> String {
+        self.value
+            .iter()
+            .map(|value| value.print())
+            .chain(self.conflicts.iter().map(|conflict| conflict.to_string()))
+            .join("\n\n")
+    }
+
+    /// Apply the merged entries to the source of our side with [`set`](crate::set) and [`unset`](crate::unset),
+    /// keeping its comments, includes and order of entries.
+    /// The conflicts with their markers replace the conflicting entries of our side,
+    /// or follow the entries if our side has none of them.
+    ///
+    /// Only leaves outside of arrays and items appended to arrays can be applied,
+    /// as the other items of arrays cannot be located in the source by their positions.
+    pub fn apply(&self, source: &str) -> Result<String, ApplyError> {
+        let ours = crate::parse(source)
+            .and_then(crate::evaluate)
+            .map_err(|error| ApplyError::Edit(EditError::InvalidSource(error)))?;
+        let merged = self.value.as_ref().ok_or(ApplyError::NoEntryLeft)?;
+        // The conflicting entries of our side are kept until they are replaced with the markers
+        let mut in_place = Vec::new();
+        let mut at_end = Vec::new();
+        for conflict in &self.conflicts {
+            if edit::has_entries(source, &conflict.path).map_err(ApplyError::Edit)? {
+                in_place.push(conflict);
+            } else {
+                at_end.push(conflict);
+            }
+        }
+        let is_applied = |change: &Change| {
+            !in_place
+                .iter()
+                .any(|conflict| is_under(change.path(), &conflict.path))
+        };
+        let mut edited = source.to_string();
+        for change in diff(&ours, merged).into_iter().filter(is_applied) {
+            edited = apply_change(&edited, &ours, &change)?;
+        }
+        if let Some(change) = crate::parse(&edited)
+            .and_then(crate::evaluate)
+            .ok()
+            .and_then(|value| diff(&value, merged).into_iter().find(is_applied))
+        {
+            return Err(ApplyError::Unsupported(change));
+        }
+        let replacements = in_place
+            .iter()
+            .map(|conflict| (conflict.path.as_str(), conflict.to_string()))
+            .collect_vec();
+        let edited = edit::replace_entries(&edited, &replacements).map_err(ApplyError::Edit)?;
+        if at_end.is_empty() {
+            return Ok(edited);
+        }
+        Ok(std::iter::once(edited.trim_end().to_string())
+            .chain(at_end.iter().map(|conflict| conflict.to_string()))
+            .join("\n\n"))
+    }
+}
+
+/// An error raised by [`Merge::apply`].
+#[derive(Debug)]
+pub enum ApplyError {
+    /// A change that cannot be located in the source, such as a change of an array item
+    Unsupported(Change),
+    /// Every entry is removed by the merge or conflicts, but a MERC file needs at least one entry
+    NoEntryLeft,
+    Edit(EditError),
+}
+
+impl std::fmt::Display for ApplyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApplyError::Unsupported(change) => {
+                write!(f, "The change cannot be applied to the source:\n{change}")
+            }
+            ApplyError::NoEntryLeft => write!(
+                f,
+                "The merge leaves no entry, but a MERC file needs at least one"
+            ),
+            ApplyError::Edit(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for ApplyError {}
+
+fn apply_change(source: &str, ours: &Value, change: &Change) -> Result<String, ApplyError> {
+    let unsupported = || ApplyError::Unsupported(change.clone());
+    let accesses = parser::parse_path(change.path()).map_err(|_| unsupported())?;
+    let array_accesses = accesses
+        .iter()
+        .positions(|access| matches!(access.kind, AccessKind::ArrayAccessExplicit { .. }))
+        .collect_vec();
+    let result = match (change, array_accesses.as_slice()) {
+        (
+            Change::Added { path, value }
+            | Change::Changed {
+                path, new: value, ..
+            },
+            [],
+        ) => edit::set(source, path, value),
+        (Change::Removed { path, .. }, []) => edit::unset(source, path),
+        // An item appended to an array of implicit keys, such as `.excludes[2]`, is set at `.excludes[+]`
+        (Change::Added { value, .. }, [index]) if *index == accesses.len() - 1 => {
+            let parent = edit::display_path(&accesses.iter().take(*index).cloned().collect_vec());
+            let items = crate::query(ours, &parent)
+                .map_err(|_| unsupported())?
+                .into_iter()
+                .filter_map(|parent| parent.value.as_map_like())
+                .flat_map(|array| array.iter().map(|(key, _)| key.clone()).collect_vec())
+                .collect_vec();
+            if !items.iter().all(|key| matches!(key, MapKey::Implicit(_))) {
+                return Err(unsupported());
+            }
+            edit::set(source, &format!("{parent}[+]"), value)
+        }
+        _ => return Err(unsupported()),
+    };
+    result.map_err(ApplyError::Edit)
+}
+
+/// Whether the given path is the given prefix, or a path under it.
+fn is_under(path: &str, prefix: &str) -> bool {
+    path.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(['.', '[', '{']))
+}
+
+/// The entries under a path that both sides change differently.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub path: String,
+    /// The lines of MERC of our side, empty if our side removes the entries
+    pub ours: String,
+    /// The lines of MERC of their side, empty if their side removes the entries
+    pub theirs: String,
+}
+
+impl std::fmt::Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "<<<<<<< ours")?;
+        if !self.ours.is_empty() {
+            writeln!(f, "{}", self.ours)?;
+        }
+        writeln!(f, "=======")?;
+        if !self.theirs.is_empty() {
+            writeln!(f, "{}", self.theirs)?;
+        }
+        write!(f, ">>>>>>> theirs")
+    }
+}
+
+/// Merge the changes from `base` to `ours` and from `base` to `theirs`.
+///
+/// Objects and maps are merged key by key, and arrays position by position,
+/// except that the items appended by both sides are all kept, ours first.
+/// A leaf that both sides change differently, or remove on one side and change on the other,
+/// is a conflict.
+pub fn merge(base: &Value, ours: &Value, theirs: &Value) -> Merge {
+    let mut conflicts = Vec::new();
+    let value = merge_values("", Some(base), Some(ours), Some(theirs), &mut conflicts);
+    Merge { value, conflicts }
+}
+
+fn merge_values(
+    path: &str,
+    base: Option<&Value>,
+    ours: Option<&Value>,
+    theirs: Option<&Value>,
+    conflicts: &mut Vec<Conflict>,
+) -> Option<Value> {
+    if is_same(ours, theirs) || is_same(base, theirs) {
+        return ours.cloned();
+    }
+    if is_same(base, ours) {
+        return theirs.cloned();
+    }
+    match (ours, theirs) {
+        (Some(ours), Some(theirs)) => match (ours.as_map_like(), theirs.as_map_like()) {
+            (Some(ours_map), Some(theirs_map))
+                if (ours_map.kind() == &MapKind::Array)
+                    == (theirs_map.kind() == &MapKind::Array) =>
+            {
+                // The base is ignored if it is not of the same kind, as if both sides added the value
+                let base = base.and_then(Value::as_map_like).filter(|base| {
+                    (base.kind() == &MapKind::Array) == (ours_map.kind() == &MapKind::Array)
+                });
+                // A side that keeps the kind of the base does not change it
+                let kind = match base {
+                    Some(base) if base.kind() == ours_map.kind() => theirs_map.kind(),
+                    _ => ours_map.kind(),
+                };
+                let entries = if kind == &MapKind::Array {
+                    merge_arrays(path, base, ours_map, theirs_map, conflicts)
+                } else {
+                    merge_objects(path, kind, base, ours_map, theirs_map, conflicts)
+                };
+                (!entries.is_empty()).then(|| Value::map_like(kind.clone(), entries))
+            }
+            _ => conflict(path, Some(ours), Some(theirs), conflicts),
+        },
+        (ours, theirs) => conflict(path, ours, theirs, conflicts),
+    }
+}
+
+fn merge_objects(
+    path: &str,
+    kind: &MapKind,
+    base: Option<&MapLike>,
+    ours: &MapLike,
+    theirs: &MapLike,
+    conflicts: &mut Vec<Conflict>,
+) -> Vec<(MapKey, Value)> {
+    // Keys are kept in the order of our side, followed by the keys that only their side has
+    let keys = ours
+        .iter()
+        .chain(theirs.iter())
+        .chain(base.into_iter().flat_map(|base| base.iter()))
+        .map(|(key, _)| key)
+        .unique_by(|key| key.string_value())
+        .cloned()
+        .collect_vec();
+    keys.into_iter()
+        .filter_map(|key| {
+            let name = key.string_value();
+            let path = match kind {
+                MapKind::Map => format!("{path}{{{}}}", key.display()),
+                MapKind::Object | MapKind::Array => format!("{path}.{}", key.display()),
+            };
+            merge_values(
+                &path,
+                base.and_then(|base| base.get(&name)),
+                ours.get(&name),
+                theirs.get(&name),
+                conflicts,
+            )
+            .map(|value| (key, value))
+        })
+        .collect()
+}
+
+/// Items are merged by their positions in the base,
+/// and the items after the end of the base are appended by each side.
+fn merge_arrays(
+    path: &str,
+    base: Option<&MapLike>,
+    ours: &MapLike,
+    theirs: &MapLike,
+    conflicts: &mut Vec<Conflict>,
+) -> Vec<(MapKey, Value)> {
+    let base = base
+        .map(|base| base.values().collect_vec())
+        .unwrap_or_default();
+    let ours = ours.values().collect_vec();
+    let theirs = theirs.values().collect_vec();
+    let mut items = (0..base.len())
+        .filter_map(|index| {
+            let path = format!("{path}[{index}]");
+            merge_values(
+                &path,
+                base.get(index).copied(),
+                ours.get(index).copied(),
+                theirs.get(index).copied(),
+                conflicts,
+            )
+        })
+        .collect_vec();
+    let ours_appended = ours.get(base.len()..).unwrap_or_default();
+    let theirs_appended = theirs.get(base.len()..).unwrap_or_default();
+    let appended_by_both = ours_appended.len() == theirs_appended.len()
+        && ours_appended
+            .iter()
+            .zip(theirs_appended)
+            .all(|(ours, theirs)| is_same(Some(ours), Some(theirs)));
+    items.extend(ours_appended.iter().map(|item| (*item).clone()));
+    if !appended_by_both {
+        items.extend(theirs_appended.iter().map(|item| (*item).clone()));
+    }
+    items
+        .into_iter()
+        .enumerate()
+        .map(|(index, item)| (MapKey::array_item(index, &item), item))
+        .collect()
+}
+
+fn conflict(
+    path: &str,
+    ours: Option<&Value>,
+    theirs: Option<&Value>,
+    conflicts: &mut Vec<Conflict>,
+) -> Option<Value> {
+    let print = |value: Option<&Value>| value.map(|value| value.print_at(path)).unwrap_or_default();
+    conflicts.push(Conflict {
+        path: path.to_string(),
+        ours: print(ours),
+        theirs: print(theirs),
+    });
+    None
+}
+
+fn is_same(a: Option<&Value>, b: Option<&Value>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => diff(a, b).is_empty(),
+        (None, None) => true,
+        _ => false,
+    }
+}
@@ -0,0 +1,199 @@
+//! Overlay of multiple sources, such as `base.merc` and `prod.merc`,
+//! where later layers override the values set by earlier layers.
+use crate::{
+    data::{self, Value},
+    include,
+    parser::{self, Parsed},
+    Error, Errors, FileErrors, FileLoader, MapKey, MapKind, Sources, Span,
+};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use std::collections::HashMap;
+
+/// Layers of MERC sources, evaluated into a single value.
+///
+/// A layer overrides whatever earlier layers set at the same path,
+/// which is a scalar, or a map-like of a different kind,
+/// while map-likes of the same kind are overlaid entry by entry, and array items are appended.
+#[derive(Debug, Clone, Default)]
+pub struct Overlay {
+    strict: bool,
+    value: Option<Value>,
+    /// The layer and the span that set each leaf, by the keys of its path
+    provenance: HashMap<Vec<MapKey>, (usize, Span)>,
+    layers: usize,
+}
+
+/// The layer and the span that provided the final value of a leaf.
+#[derive(Debug, Clone)]
+pub struct Provenance {
+    /// The path of the leaf, such as `.machines{api}.size`
+    pub path: String,
+    /// The value of the leaf in MERC, such as `'t3-large'`
+    pub value: String,
+    /// The index of the layer, in the order they are added
+    pub layer: usize,
+    /// The span of the value in the source of the layer
+    pub span: Span,
+}
+
+impl Overlay {
+    /// In strict mode, assigning the same path twice within a single layer is still an error,
+    /// otherwise the later entry overrides the earlier one, as if it were in a later layer.
+    pub fn new(strict: bool) -> Overlay {
+        Overlay {
+            strict,
+            ..Default::default()
+        }
+    }
+
+    /// Evaluate the given source, and override the values of the earlier layers with it.
+    ///
+    /// The overlay is left unchanged if the source has any error.
+    /// The files included by `@include` are not loaded, see [`Overlay::add_layer_with_includes`].
+    pub fn add_layer(&mut self, merc: &str) -> Result<(), Errors> {
+        let (parsed, errors) = parser::parse_recovering(merc);
+        self.add_parsed(parsed, errors).map_err(Errors)
+    }
+
+    /// Like [`Overlay::add_layer`], but the files included by `@include` are loaded with the given loader,
+    /// see [`crate::check_with_includes`].
+    ///
+    /// Returns the sources of the layer, which the spans of its [`Provenance`] belong to.
+    pub fn add_layer_with_includes(
+        &mut self,
+        path: &str,
+        merc: &str,
+        loader: &dyn FileLoader,
+    ) -> Result<Sources, FileErrors> {
+        let (parsed, errors, sources) = include::resolve_includes(path, merc, loader);
+        match self.add_parsed(parsed, errors) {
+            Ok(()) => Ok(sources),
+            Err(errors) => Err(FileErrors {
+                errors: Errors(errors),
+                sources,
+            }),
+        }
+    }
+
+    fn add_parsed(&mut self, parsed: Parsed, mut errors: Vec<Error>) -> Result<(), Vec<Error>> {
+        let (values, evaluate_errors) = if self.strict {
+            let (value, evaluate_errors) = data::evaluate_recovering(parsed);
+            (vec![value], evaluate_errors)
+        } else {
+            data::evaluate_entries(parsed)
+        };
+        errors.extend(
+            evaluate_errors
+                .into_iter()
+                .map(|error| Error::EvaluationError(Box::new(error))),
+        );
+        if !errors.is_empty() {
+            errors.sort_by_key(|error| error.span().start());
+            return Err(errors);
+        }
+        for value in values {
+            let below = self.value.take();
+            self.value = Some(self.overlay(below, value, &mut Vec::new()));
+        }
+        self.layers += 1;
+        Ok(())
+    }
+
+    /// The value of all layers, or `None` if no layer is added.
+    pub fn value(&self) -> Option<&Value> {
+        self.value.as_ref()
+    }
+
+    pub fn into_value(self) -> Option<Value> {
+        self.value
+    }
+
+    /// The provenance of every leaf of the value, sorted by the keys of their paths,
+    /// or by the positions of array items.
+    pub fn provenance(&self) -> Vec<Provenance> {
+        let mut provenance = Vec::new();
+        if let Some(value) = &self.value {
+            self.collect_provenance(value, "", &mut Vec::new(), &mut provenance)
+        }
+        provenance
+    }
+
+    fn overlay(&mut self, below: Option<Value>, above: Value, keys: &mut Vec<MapKey>) -> Value {
+        let (Some(below_map), Some(above_map)) = (
+            below.as_ref().and_then(Value::as_map_like),
+            above.as_map_like(),
+        ) else {
+            self.record(&above, keys);
+            return above;
+        };
+        if below_map.kind() != above_map.kind() {
+            self.record(&above, keys);
+            return above;
+        }
+        let mut entries = below_map
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect::<IndexMap<_, _>>();
+        for (key, value) in above_map.iter() {
+            keys.push(key.clone());
+            let overlaid = self.overlay(entries.get(key).cloned(), value.clone(), keys);
+            keys.pop();
+            entries.insert(key.clone(), overlaid);
+        }
+        Value::map_like(above_map.kind().clone(), entries)
+    }
+
+    /// Record the current layer as the provenance of every leaf of the given value.
+    fn record(&mut self, value: &Value, keys: &mut Vec<MapKey>) {
+        match value.as_map_like() {
+            Some(map_like) => {
+                for (key, value) in map_like.iter() {
+                    keys.push(key.clone());
+                    self.record(value, keys);
+                    keys.pop();
+                }
+            }
+            None => {
+                self.provenance
+                    .insert(keys.clone(), (self.layers, value.inferred_at().clone()));
+            }
+        }
+    }
+
+    fn collect_provenance(
+        &self,
+        value: &Value,
+        path: &str,
+        keys: &mut Vec<MapKey>,
+        provenance: &mut Vec<Provenance>,
+    ) {
+        let Some(map_like) = value.as_map_like() else {
+            if let (Some(scalar), Some((layer, span))) =
+                (value.as_scalar(), self.provenance.get(keys))
+            {
+                provenance.push(Provenance {
+                    path: path.to_string(),
+                    value: scalar.display(),
+                    layer: *layer,
+                    span: span.clone(),
+                })
+            }
+            return;
+        };
+        let mut entries = map_like.iter().collect_vec();
+        if map_like.kind() != &MapKind::Array {
+            entries.sort_by_key(|(key, _)| key.string_value())
+        }
+        for (key, value) in entries {
+            let path = match map_like.kind() {
+                MapKind::Object => format!("{path}.{}", key.display()),
+                MapKind::Map => format!("{path}{{{}}}", key.display()),
+                MapKind::Array => format!("{path}[{}]", key.display()),
+            };
+            keys.push(key.clone());
+            self.collect_provenance(value, &path, keys, provenance);
+            keys.pop();
+        }
+    }
+}
@@ -0,0 +1,345 @@
+//! Translation of pest errors into MERC-specific syntax errors.
+use crate::{parser::Rule, Span};
+use annotate_snippets::{Level, Renderer};
+use itertools::Itertools;
+use pest::error::{ErrorVariant, InputLocation};
+
+/// A syntax error of MERC source.
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    kind: ParseErrorKind,
+    span: Span,
+    label: String,
+    help: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// Any syntax error that is not covered by the other kinds
+    UnexpectedToken,
+    /// For example, `.x.y 1`
+    MissingEqualsSign,
+    /// For example, `.x = ` or `.x = hello`
+    MissingValue,
+    /// For example, `.x. = 1` or `.x = #`
+    MissingKey,
+    /// For example, `.x{y = 1`
+    UnclosedAccess,
+    /// For example, `.x = '''hello`
+    UnterminatedString,
+    /// For example, a file with only comments
+    MissingEntry,
+}
+
+impl ParseErrorKind {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseErrorKind::UnexpectedToken => "E0001",
+            ParseErrorKind::MissingEqualsSign => "E0002",
+            ParseErrorKind::MissingValue => "E0003",
+            ParseErrorKind::MissingKey => "E0004",
+            ParseErrorKind::UnclosedAccess => "E0005",
+            ParseErrorKind::UnterminatedString => "E0006",
+            ParseErrorKind::MissingEntry => "E0007",
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            ParseErrorKind::UnexpectedToken => "Unexpected Token",
+            ParseErrorKind::MissingEqualsSign => "Missing Equals Sign",
+            ParseErrorKind::MissingValue => "Missing Value",
+            ParseErrorKind::MissingKey => "Missing Key",
+            ParseErrorKind::UnclosedAccess => "Unclosed Access",
+            ParseErrorKind::UnterminatedString => "Unterminated String",
+            ParseErrorKind::MissingEntry => "Missing Entry",
+        }
+    }
+}
+
+impl ParseError {
+    pub fn kind(&self) -> ParseErrorKind {
+        self.kind
+    }
+
+    /// The span of the offending token, which is empty at the end of the input,
+    /// unless the input ends with a newline, in which case it is the last character before it.
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    /// A one-line description of this error, such as ``expected `=` after the path `.x.y` ``.
+    pub fn message(&self) -> &str {
+        &self.label
+    }
+
+    /// A suggestion to fix this error, if any.
+    pub fn help(&self) -> Option<&str> {
+        self.help.as_deref()
+    }
+
+    pub fn display(&self, source: &str) -> String {
+        if source.trim().is_empty() {
+            // There is no line to annotate, so the label is shown on its own
+            let message = Level::Error
+                .title(self.kind.title())
+                .id(self.kind.code())
+                .footer(Level::Note.title(&self.label));
+            let message = match &self.help {
+                Some(help) => message.footer(Level::Help.title(help)),
+                None => message,
+            };
+            return Renderer::plain().render(message).to_string();
+        }
+        crate::data::render(
+            self.kind.code(),
+            self.kind.title(),
+            vec![Level::Error.span(self.span.byte_range()).label(&self.label)],
+            self.help.as_deref(),
+            source,
+        )
+    }
+
+    pub(crate) fn new(error: pest::error::Error<Rule>, input: &str) -> ParseError {
+        let position = match error.location {
+            InputLocation::Pos(position) => position,
+            InputLocation::Span((start, _)) => start,
+        };
+        let positives = match error.variant {
+            ErrorVariant::ParsingError { positives, .. } => positives,
+            ErrorVariant::CustomError { message } => {
+                return ParseError {
+                    kind: ParseErrorKind::UnexpectedToken,
+                    span: Span::new(position, position),
+                    label: message,
+                    help: None,
+                }
+            }
+        };
+        if let Some(error) = unterminated_string(input, position, &positives) {
+            return error;
+        }
+
+        let span = found_span(input, position);
+        let found = describe(&input[span.byte_range()]);
+        let expects = |rules: &[Rule]| rules.iter().any(|rule| positives.contains(rule));
+        let before = input[..position].trim_end();
+        // Strings are not checked here, because keys can be strings too
+        let (kind, label, help) = if expects(&[
+            Rule::null,
+            Rule::boolean,
+            Rule::datetime,
+            Rule::number,
+            Rule::r#enum,
+        ]) {
+            let text = &input[span.byte_range()];
+            let help = text
+                .chars()
+                .next()
+                .filter(|char| char.is_alphabetic())
+                .map(|_| format!("strings must be quoted, for example `'{text}'`"));
+            (
+                ParseErrorKind::MissingValue,
+                format!("expected a value after `=`, found {found}"),
+                help,
+            )
+        } else if expects(&[Rule::unquoted_identifier]) {
+            match before.chars().last() {
+                Some('#') => (
+                    ParseErrorKind::MissingKey,
+                    format!("expected a name after `#`, found {found}"),
+                    Some("enums are written as `#name`, for example `#fast`".to_string()),
+                ),
+                Some(char) => (
+                    ParseErrorKind::MissingKey,
+                    format!("expected a key after `{char}`, found {found}"),
+                    None,
+                ),
+                None => (
+                    ParseErrorKind::MissingKey,
+                    format!("expected a key, found {found}"),
+                    None,
+                ),
+            }
+        } else if expects(&[Rule::object_access, Rule::map_access]) {
+            let rest = &input[position..];
+            if let Some(closing) = [('{', '}'), ('[', ']')]
+                .into_iter()
+                .find(|(opening, _)| rest.starts_with(*opening))
+                .map(|(_, closing)| closing)
+            {
+                (
+                    ParseErrorKind::UnclosedAccess,
+                    format!("expected `{closing}` to close this access"),
+                    None,
+                )
+            } else if expects(&[Rule::EOI]) {
+                // A query, which ends after any access
+                (
+                    ParseErrorKind::UnexpectedToken,
+                    format!("expected an access, such as `.key` or `.*`, found {found}"),
+                    None,
+                )
+            } else {
+                let path = before.lines().last().unwrap_or_default().trim();
+                (
+                    ParseErrorKind::MissingEqualsSign,
+                    format!("expected `=` after the path `{path}`, found {found}"),
+                    None,
+                )
+            }
+        } else if expects(&[Rule::file, Rule::entry, Rule::comment]) {
+            let help = input[position..]
+                .starts_with("@include")
+                .then(|| "`@include` must be placed above every entry".to_string());
+            (
+                ParseErrorKind::MissingEntry,
+                format!("expected an entry, such as `.key = value`, found {found}"),
+                help,
+            )
+        } else {
+            let expected = positives
+                .iter()
+                .map(|rule| human_term(*rule))
+                .unique()
+                .collect_vec();
+            let expected = match expected.split_last() {
+                Some((last, [])) => last.to_string(),
+                Some((last, init)) => format!("{} or {last}", init.join(", ")),
+                None => "nothing".to_string(),
+            };
+            (
+                ParseErrorKind::UnexpectedToken,
+                format!("expected {expected}, found {found}"),
+                None,
+            )
+        };
+        ParseError {
+            kind,
+            span: visible_span(input, span),
+            label,
+            help,
+        }
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.label)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Detect a string that starts at the error position but is never closed.
+///
+/// An unclosed `'''` is parsed as an empty `''` followed by a stray `'`,
+/// so the error position might be right after the first two quotes.
+fn unterminated_string(input: &str, position: usize, positives: &[Rule]) -> Option<ParseError> {
+    let start = if positives.contains(&Rule::string) {
+        position
+    } else if ["'''", "\"\"\""].iter().any(|quotes| {
+        input
+            .get(position.saturating_sub(2)..)
+            .is_some_and(|rest| rest.starts_with(quotes))
+    }) {
+        position.saturating_sub(2)
+    } else {
+        return None;
+    };
+    let rest = &input[start..];
+    let quotes = ["'''", "\"\"\"", "'", "\""]
+        .into_iter()
+        .find(|quotes| rest.starts_with(quotes))?;
+    let content = &rest[quotes.len()..];
+    let is_closed = match quotes {
+        "'''" | "\"\"\"" => content.contains(quotes),
+        "'" => content.lines().next().unwrap_or_default().contains('\''),
+        _ => {
+            let mut chars = content.chars();
+            let mut is_closed = false;
+            while let Some(char) = chars.next() {
+                match char {
+                    '\\' => {
+                        chars.next();
+                    }
+                    '"' => {
+                        is_closed = true;
+                        break;
+                    }
+                    '\n' => break,
+                    _ => {}
+                }
+            }
+            is_closed
+        }
+    };
+    if is_closed {
+        return None;
+    }
+    let help = if quotes.len() == 1 {
+        Some("use `'''` or `\"\"\"` for strings that span multiple lines".to_string())
+    } else {
+        None
+    };
+    Some(ParseError {
+        kind: ParseErrorKind::UnterminatedString,
+        span: Span::new(start, start + quotes.len()),
+        label: format!("unterminated `{quotes}` string"),
+        help,
+    })
+}
+
+/// The span of the token at the given position, which is empty at the end of the input.
+fn found_span(input: &str, position: usize) -> Span {
+    let length = input[position..]
+        .find(char::is_whitespace)
+        .unwrap_or(input.len() - position);
+    Span::new(position, position + length)
+}
+
+/// An empty span at the start of a line cannot be shown,
+/// so it is widened to the last character before it, if any.
+fn visible_span(input: &str, span: Span) -> Span {
+    let before = &input[..span.start()];
+    if !span.byte_range().is_empty() || !(before.is_empty() || before.ends_with('\n')) {
+        return span;
+    }
+    match before.trim_end().char_indices().last() {
+        Some((index, char)) => Span::new(index, index + char.len_utf8()),
+        None => span,
+    }
+}
+
+fn describe(found: &str) -> String {
+    if found.is_empty() {
+        "the end of the input".to_string()
+    } else {
+        format!("`{found}`")
+    }
+}
+
+fn human_term(rule: Rule) -> &'static str {
+    match rule {
+        Rule::EOI => "the end of the input",
+        Rule::entry => "an entry",
+        Rule::include => "an `@include`",
+        Rule::comment | Rule::comments => "a comment",
+        Rule::accesses | Rule::include_accesses => "a path, such as `.key`",
+        Rule::object_access => "`.key`",
+        Rule::map_access => "`{key}`",
+        Rule::array_access_implicit => "`[+]`",
+        Rule::array_access_explicit => "`[key]`",
+        Rule::recursive_descent => "`.**`",
+        Rule::object_wildcard => "`.*`",
+        Rule::map_wildcard => "`{*}`",
+        Rule::array_wildcard => "`[*]`",
+        Rule::unquoted_identifier => "a key",
+        Rule::string => "a string",
+        Rule::number | Rule::integer => "a number",
+        Rule::boolean => "`true` or `false`",
+        Rule::null => "`null`",
+        Rule::r#enum => "an enum, such as `#fast`",
+        _ => "a valid token",
+    }
+}
@@ -1,4 +1,5 @@
-use crate::data::{EvaluateError, Identifier};
+use crate::data::{Datetime, EvaluateError, Identifier, MapKind, Number};
+use crate::parse_error::{ParseError, ParseErrorKind};
 use itertools::Itertools;
 use nonempty::NonEmpty;
 use pest::{iterators::Pair, Parser};
@@ -9,63 +10,192 @@ use pest_derive::Parser;
 pub(crate) struct MercParser;
 
 pub(crate) fn parse(input: &str) -> Result<Parsed, crate::Error> {
-    let file = MercParser::parse(Rule::file, input)
-        .map_err(|error| crate::Error::ParseError(Box::new(error)))?
-        .next()
-        .unwrap();
-    let statements = file
-        .into_inner()
-        .map(|pair| -> Result<_, EvaluateError> {
-            match pair.as_rule() {
-                Rule::entry => {
-                    let mut inner_rules = pair.into_inner();
-                    let comment = inner_rules.next().unwrap().as_str().trim();
-                    let comment = if !comment.is_empty() {
-                        Some(
-                            comment
-                                .lines()
-                                .filter(|line| !line.trim().is_empty())
-                                .join("\n"),
-                        )
-                    } else {
-                        None
-                    };
-                    let accesses = {
-                        let mut inner = inner_rules.next().unwrap().into_inner();
-
-                        let next = inner.next().unwrap();
-                        let access_head = parse_access(next)?;
-
-                        let access_tail = inner.map(|pair| parse_access(pair)).try_collect()?;
-
-                        NonEmpty {
-                            head: access_head,
-                            tail: access_tail,
-                        }
-                    };
-                    let value = parse_value(inner_rules.next().unwrap())?;
-                    Ok(Some(Statement::Entry(Entry {
-                        comment,
-                        accesses,
-                        value,
-                    })))
+    let (parsed, errors) = parse_recovering(input);
+    match errors.into_iter().next() {
+        Some(error) => Err(error),
+        None => Ok(parsed),
+    }
+}
+
+/// Parse the given input, recovering from errors so that every error can be reported at once.
+///
+/// When the parser fails, the failing entry is blanked out until the next line that
+/// starts with `.`, `{`, `[` or `@`, and the input is parsed again.
+/// The returned errors are sorted by their position,
+/// and the returned entries are those that are parsed successfully.
+pub(crate) fn parse_recovering(input: &str) -> (Parsed, Vec<crate::Error>) {
+    let mut errors = Vec::new();
+    let mut source = input.to_string();
+    let is_recovered = loop {
+        let Err(error) = MercParser::parse(Rule::file, &source) else {
+            break true;
+        };
+        let error = ParseError::new(error, &source);
+        let position = error.span().start();
+        // A file that is entirely blanked out has no entry, which is not a genuine error
+        if !errors.is_empty()
+            && error.kind() == ParseErrorKind::MissingEntry
+            && source[position..].trim().is_empty()
+        {
+            break false;
+        }
+        let range = recovery_range(&source, position);
+        errors.push(crate::Error::ParseError(Box::new(error)));
+        if range.is_empty() {
+            break false;
+        }
+        blank(&mut source, range);
+    };
+    let statements = if is_recovered {
+        let file = MercParser::parse(Rule::file, &source)
+            .expect("the source is recovered")
+            .next()
+            .unwrap();
+        file.into_inner()
+            .map(parse_statement)
+            .filter_map(|result| match result {
+                Ok(statements) => Some(statements),
+                Err(error) => {
+                    errors.push(crate::Error::EvaluationError(Box::new(error)));
+                    None
                 }
-                Rule::comment => Ok(Some(Statement::Comment(Comment(pair.as_str().to_string())))),
-                Rule::EOI => Ok(None),
-                _ => unreachable!(),
-            }
+            })
+            .flatten()
+            .collect()
+    } else {
+        Vec::new()
+    };
+    errors.sort_by_key(|error| error.span().start());
+    (Parsed(statements), errors)
+}
+
+fn parse_statement(pair: Pair<Rule>) -> Result<Vec<Statement>, EvaluateError> {
+    match pair.as_rule() {
+        Rule::entry => {
+            let mut inner_rules = pair.into_inner();
+            let comment = parse_comment(inner_rules.next().unwrap());
+            let accesses = parse_accesses(inner_rules.next().unwrap())?;
+            let value = parse_value(inner_rules.next().unwrap())?;
+            Ok(vec![Statement::Entry(Entry {
+                comment,
+                accesses,
+                value,
+            })])
+        }
+        Rule::include => {
+            let mut inner_rules = pair.into_inner();
+            let comment = parse_comment(inner_rules.next().unwrap());
+            let path = inner_rules.nth(1).unwrap();
+            let path_span = path.as_span().into();
+            let path = parse_string(path)?;
+            // Skip the `at` keyword
+            let at = inner_rules
+                .nth(1)
+                .map(|accesses| parse_accesses(accesses))
+                .transpose()?;
+            Ok(vec![Statement::Include(Include {
+                comment,
+                path,
+                path_span,
+                at,
+            })])
+        }
+        // Comments at the end of the file, which are not attached to any entry
+        Rule::comments => Ok(pair
+            .into_inner()
+            .map(|comment| Statement::Comment(Comment(comment.as_str().to_string())))
+            .collect()),
+        Rule::EOI => Ok(Vec::new()),
+        _ => unreachable!(),
+    }
+}
+
+fn parse_comment(pair: Pair<Rule>) -> Option<String> {
+    let comment = pair.as_str().trim();
+    if !comment.is_empty() {
+        Some(
+            comment
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .join("\n"),
+        )
+    } else {
+        None
+    }
+}
+
+/// The range to be blanked out in order to recover from an error at the given position,
+/// which spans from the start of the failing entry until the next entry.
+fn recovery_range(source: &str, position: usize) -> std::ops::Range<usize> {
+    let starts_entry = |line_start: &usize| {
+        source[*line_start..]
+            .split('\n')
+            .next()
+            .is_some_and(|line| line.trim_start().starts_with(['.', '{', '[', '@']))
+    };
+    let line_starts = std::iter::once(0)
+        .chain(source.match_indices('\n').map(|(index, _)| index + 1))
+        .collect_vec();
+    let current_line = line_starts
+        .iter()
+        .rposition(|line_start| *line_start <= position)
+        .unwrap_or(0);
+    let start = line_starts[..=current_line]
+        .iter()
+        .rev()
+        .find(|line_start| starts_entry(line_start))
+        .copied()
+        .unwrap_or(line_starts[current_line]);
+    let end = line_starts[current_line + 1..]
+        .iter()
+        .find(|line_start| starts_entry(line_start))
+        .copied()
+        .unwrap_or(source.len());
+    start..end
+}
+
+/// Replace the given range with spaces, keeping newlines, so that byte offsets are unchanged.
+fn blank(source: &mut String, range: std::ops::Range<usize>) {
+    let blanked = source[range.clone()]
+        .chars()
+        .map(|char| match char {
+            '\n' | '\r' => char.to_string(),
+            _ => " ".repeat(char.len_utf8()),
         })
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|evaluate_error| crate::Error::EvaluationError(Box::new(evaluate_error)))?
-        .into_iter()
-        .flatten()
-        .collect();
-    Ok(Parsed(statements))
+        .collect::<String>();
+    source.replace_range(range, &blanked);
 }
 
+/// The statements of a MERC file, not yet evaluated.
 #[derive(Debug)]
-pub(crate) struct Parsed(Vec<Statement>);
+pub struct Parsed(Vec<Statement>);
 impl Parsed {
+    /// The comments that are not attached to any entry.
+    pub fn comments(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().filter_map(|statement| match statement {
+            Statement::Comment(Comment(comment)) => Some(comment.as_str()),
+            _ => None,
+        })
+    }
+
+    pub(crate) fn entries(&self) -> impl Iterator<Item = &Entry> {
+        self.0.iter().filter_map(|statement| match statement {
+            Statement::Entry(entry) => Some(entry),
+            _ => None,
+        })
+    }
+
+    pub(crate) fn includes(&self) -> impl Iterator<Item = &Include> {
+        self.0.iter().filter_map(|statement| match statement {
+            Statement::Include(include) => Some(include),
+            _ => None,
+        })
+    }
+
+    pub(crate) fn from_entries(entries: Vec<Entry>) -> Parsed {
+        Parsed(entries.into_iter().map(Statement::Entry).collect())
+    }
+
     pub(crate) fn into_entries(self) -> Vec<Entry> {
         self.0
             .into_iter()
@@ -75,17 +205,12 @@ impl Parsed {
             })
             .collect_vec()
     }
-
-    pub(crate) fn into_string(self) -> Result<String, crate::Error> {
-        let merc_value = crate::data::evaluate(self)
-            .map_err(|error| crate::Error::EvaluationError(Box::new(error)))?;
-        Ok(merc_value.print())
-    }
 }
 
 #[derive(Debug)]
 enum Statement {
     Entry(Entry),
+    Include(Include),
     Comment(Comment),
 }
 
@@ -99,17 +224,117 @@ pub(crate) struct Entry {
     pub(crate) value: EntryValue,
 }
 
+/// An `@include` of another file, such as `@include 'db.merc' at .services{db}`.
+#[derive(Debug, Clone)]
+pub(crate) struct Include {
+    pub(crate) comment: Option<String>,
+    pub(crate) path: MercString,
+    pub(crate) path_span: Span,
+    /// The path under which the entries of the included file are placed
+    pub(crate) at: Option<NonEmpty<Access>>,
+}
+
+impl Include {
+    /// Render this include as MERC.
+    pub(crate) fn display(&self) -> String {
+        let comment = self
+            .comment
+            .as_ref()
+            .map(|comment| format!("{comment}\n"))
+            .unwrap_or_default();
+        let at = self
+            .at
+            .iter()
+            .map(|accesses| {
+                format!(
+                    " at {}",
+                    accesses.iter().map(|access| access.kind.display()).join("")
+                )
+            })
+            .join("");
+        format!("{comment}@include {}{at}", self.path.display())
+    }
+}
+
+/// Parse a standalone path, such as `.machines{api}.size`.
+pub(crate) fn parse_path(input: &str) -> Result<NonEmpty<Access>, crate::Error> {
+    let path = MercParser::parse(Rule::path, input)
+        .map_err(|error| crate::Error::ParseError(Box::new(ParseError::new(error, input))))?
+        .next()
+        .unwrap();
+    parse_accesses(path.into_inner().next().unwrap())
+        .map_err(|error| crate::Error::EvaluationError(Box::new(error)))
+}
+
+/// An access of a query, see [`parse_query`].
+#[derive(Debug, Clone)]
+pub(crate) enum QueryAccess {
+    Access(AccessKind),
+    /// `.*`, `{*}` or `[*]`, which matches every child of an object, a map or an array respectively
+    Wildcard(MapKind),
+    /// `.**`, which matches the value itself and every value under it
+    RecursiveDescent,
+}
+
+/// Parse a query, such as `.machines{*}.size`.
+pub(crate) fn parse_query(input: &str) -> Result<Vec<QueryAccess>, crate::Error> {
+    let query = MercParser::parse(Rule::query, input)
+        .map_err(|error| crate::Error::ParseError(Box::new(ParseError::new(error, input))))?
+        .next()
+        .unwrap();
+    query
+        .into_inner()
+        .filter(|pair| pair.as_rule() != Rule::EOI)
+        .map(|pair| {
+            Ok(match pair.as_rule() {
+                Rule::recursive_descent => QueryAccess::RecursiveDescent,
+                Rule::object_wildcard => QueryAccess::Wildcard(MapKind::Object),
+                Rule::map_wildcard => QueryAccess::Wildcard(MapKind::Map),
+                Rule::array_wildcard => QueryAccess::Wildcard(MapKind::Array),
+                _ => QueryAccess::Access(parse_access(pair)?.kind),
+            })
+        })
+        .collect::<Result<_, EvaluateError>>()
+        .map_err(|error| crate::Error::EvaluationError(Box::new(error)))
+}
+
+/// Parse a standalone value, such as `'t3-large'`.
+pub(crate) fn parse_standalone_value(input: &str) -> Result<EntryValue, crate::Error> {
+    let value = MercParser::parse(Rule::standalone_value, input)
+        .map_err(|error| crate::Error::ParseError(Box::new(ParseError::new(error, input))))?
+        .next()
+        .unwrap();
+    parse_value(value.into_inner().next().unwrap())
+        .map_err(|error| crate::Error::EvaluationError(Box::new(error)))
+}
+
+fn parse_accesses(pair: Pair<Rule>) -> Result<NonEmpty<Access>, EvaluateError> {
+    let mut inner = pair.into_inner();
+
+    let next = inner.next().unwrap();
+    let access_head = parse_access(next)?;
+
+    let access_tail = inner.map(|pair| parse_access(pair)).try_collect()?;
+
+    Ok(NonEmpty {
+        head: access_head,
+        tail: access_tail,
+    })
+}
+
 fn parse_value(pair: Pair<Rule>) -> Result<EntryValue, EvaluateError> {
-    let span = pair.as_span().into();
+    let span: Span = pair.as_span().into();
     let kind = match pair.as_rule() {
         Rule::string => ValueKind::String(parse_string(pair)?),
 
-        Rule::number => {
-            ValueKind::Decimal(str::parse::<serde_json::Number>(pair.as_str()).unwrap())
-        }
-        Rule::integer => ValueKind::Integer(str::parse::<isize>(pair.as_str()).unwrap()),
+        Rule::number => ValueKind::Number(
+            Number::from_literal(pair.as_str())
+                .ok_or_else(|| EvaluateError::NumberOutOfRange { span: span.clone() })?,
+        ),
         Rule::boolean => ValueKind::Boolean(str::parse::<bool>(pair.as_str()).unwrap()),
+        Rule::datetime => ValueKind::Datetime(parse_datetime(pair.as_str(), &span)?),
         Rule::null => ValueKind::Null,
+        Rule::r#enum => ValueKind::Enum(parse_identifier(pair.into_inner().next().unwrap())?),
         rule => unreachable!("rule = {rule:?}"),
     };
     Ok(EntryValue { span, kind })
@@ -118,14 +343,22 @@ fn parse_value(pair: Pair<Rule>) -> Result<EntryValue, EvaluateError> {
 #[derive(Debug)]
 pub(crate) enum ValueKind {
     String(MercString),
-    Integer(isize),
-    Decimal(serde_json::Number),
+    Number(Number),
     Boolean(bool),
     Null,
+    Enum(Identifier),
+    Datetime(Datetime),
+}
+
+/// Parse a datetime whose syntax is already checked by the grammar,
+/// so that only the ranges of its fields, such as the day of `2024-02-30`, are checked here.
+fn parse_datetime(string: &str, span: &Span) -> Result<Datetime, EvaluateError> {
+    Datetime::from_literal(string)
+        .ok_or_else(|| EvaluateError::InvalidDatetime { span: span.clone() })
 }
 
 #[derive(Debug, Clone)]
-pub(crate) struct MercString {
+pub struct MercString {
     value: String,
 }
 impl MercString {
@@ -158,11 +391,17 @@ impl MercString {
         Ok(Self { value })
     }
 
-    pub(crate) fn string_value(&self) -> String {
+    pub fn string_value(&self) -> String {
         self.value.clone()
     }
 
-    pub(crate) fn display(&self) -> String {
+    pub fn as_str(&self) -> &str {
+        &self.value
+    }
+
+    /// Render this string as a MERC string literal,
+    /// using the simplest quoting that can represent it.
+    pub fn display(&self) -> String {
         let s = self.string_value();
         fn serialize_string(s: &str) -> String {
             trim_by_count(
@@ -185,6 +424,14 @@ impl MercString {
     }
 }
 
+impl From<&str> for MercString {
+    fn from(value: &str) -> Self {
+        Self {
+            value: value.to_string(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub(crate) enum StringKind {
     SinglelineRaw,
@@ -192,16 +439,29 @@ pub(crate) enum StringKind {
     SinglineEscaped,
     MultilineAbleEscaped,
 }
-#[derive(Debug, Clone)]
-pub(crate) struct Span {
+/// A byte range of the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
     start: usize,
     end: usize,
 }
 impl Span {
-    pub(crate) fn byte_range(&self) -> std::ops::Range<usize> {
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    pub fn byte_range(&self) -> std::ops::Range<usize> {
         self.start..self.end
     }
 
+    pub(crate) fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+
     pub(crate) fn default() -> Span {
         Span { start: 0, end: 0 }
     }
@@ -222,13 +482,14 @@ pub(crate) struct EntryValue {
     pub(crate) kind: ValueKind,
 }
 
+/// An access of a path, such as `.x`, `{x}`, `[+]` or `[x]`.
 #[derive(Debug, Clone)]
-pub(crate) struct Access {
-    pub(crate) span: Span,
-    pub(crate) kind: AccessKind,
+pub struct Access {
+    pub span: Span,
+    pub kind: AccessKind,
 }
 #[derive(Debug, Clone)]
-pub(crate) enum AccessKind {
+pub enum AccessKind {
     ObjectAccess { key: Identifier },
     MapAccess { key: Identifier },
     ArrayAccessImplicit,
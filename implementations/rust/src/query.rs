@@ -0,0 +1,139 @@
+//! Queries over values, such as `.machines{*}.size`.
+//!
+//! A query is a path, as on the left of `=`, where any access can be replaced by a wildcard:
+//! `.*` for every key of an object, `{*}` for every key of a map and `[*]` for every item of an array,
+//! and `.**` for the value itself and every value under it.
+use crate::{
+    data::{Value, ValueScalarKind},
+    parser::{self, QueryAccess},
+    AccessKind, Error, MapKey, MapKind,
+};
+use itertools::Itertools;
+
+/// A value that a query matches.
+#[derive(Debug, Clone)]
+pub struct QueryMatch<'a> {
+    /// The path of the value, such as `.machines{api}.size`,
+    /// where implicit array items are referred to by their positions, such as `.excludes[0]`
+    pub path: String,
+    pub value: &'a Value,
+    /// The path of the value as written in MERC, where implicit array items are `[+]`
+    merc_path: String,
+}
+
+impl QueryMatch<'_> {
+    /// Print the entries under the value with their full paths,
+    /// where implicit array items are `[+]`, as in the source.
+    pub fn print(&self) -> String {
+        self.value.print_at(&self.merc_path)
+    }
+
+    /// Whether this match is under the given match, such as `.machines{api}.size` under `.machines`.
+    fn is_under(&self, other: &QueryMatch) -> bool {
+        self.path.len() > other.path.len()
+            && self.path.starts_with(&other.path)
+            && self.path[other.path.len()..].starts_with(['.', '{', '['])
+    }
+
+    /// The value without quotes, such as `t3-large` for `'t3-large'`,
+    /// or `None` if the value is an object, a map or an array.
+    pub fn raw(&self) -> Option<String> {
+        Some(match self.value.as_scalar()? {
+            ValueScalarKind::String(string) => string.as_str().to_string(),
+            ValueScalarKind::Enum(identifier) => identifier.string_value(),
+            scalar => scalar.display(),
+        })
+    }
+}
+
+/// The values under the given value that the query matches, in the order of their entries.
+///
+/// `.key` only matches objects, `{key}` only maps and `[key]` only arrays.
+/// `[key]` matches the item of that explicit key, or the item at that position if its key is implicit,
+/// while `[+]` matches every item of an implicit key.
+pub fn query<'a>(value: &'a Value, query: &str) -> Result<Vec<QueryMatch<'a>>, Error> {
+    let accesses = parser::parse_query(query)?;
+    let mut matches = vec![QueryMatch {
+        path: String::new(),
+        value,
+        merc_path: String::new(),
+    }];
+    for access in &accesses {
+        matches = matches
+            .iter()
+            .flat_map(|parent| children(parent, access))
+            .unique_by(|matched| matched.path.clone())
+            .collect();
+    }
+    Ok(matches)
+}
+
+/// Print the entries under every match, see [`QueryMatch::print`].
+///
+/// Every entry is printed once, as a match under another match, such as with `.**`,
+/// is already printed as part of the other one.
+pub fn print_matches(matches: &[QueryMatch]) -> String {
+    matches
+        .iter()
+        .filter(|matched| !matches.iter().any(|other| matched.is_under(other)))
+        .map(QueryMatch::print)
+        .join("\n")
+}
+
+fn children<'a>(parent: &QueryMatch<'a>, access: &QueryAccess) -> Vec<QueryMatch<'a>> {
+    let Some(map_like) = parent.value.as_map_like() else {
+        return match access {
+            QueryAccess::RecursiveDescent => vec![parent.clone()],
+            _ => Vec::new(),
+        };
+    };
+    let child = |index: usize, key: &MapKey, value: &'a Value| {
+        let access = match map_like.kind() {
+            MapKind::Object => format!(".{}", key.display()),
+            MapKind::Map => format!("{{{}}}", key.display()),
+            MapKind::Array => format!("[{}]", key.display()),
+        };
+        let position = match key {
+            MapKey::Implicit(_) => format!("[{index}]"),
+            MapKey::Explicit(_) => access.clone(),
+        };
+        QueryMatch {
+            path: format!("{}{position}", parent.path),
+            value,
+            merc_path: format!("{}{access}", parent.merc_path),
+        }
+    };
+    let entries = map_like.iter().enumerate();
+    match access {
+        QueryAccess::RecursiveDescent => std::iter::once(parent.clone())
+            .chain(
+                entries
+                    .flat_map(|(index, (key, value))| children(&child(index, key, value), access)),
+            )
+            .collect(),
+        QueryAccess::Wildcard(kind) if kind == map_like.kind() => entries
+            .map(|(index, (key, value))| child(index, key, value))
+            .collect(),
+        QueryAccess::Wildcard(_) => Vec::new(),
+        QueryAccess::Access(access) => {
+            let matches = |index: usize, key: &MapKey| match (access, map_like.kind()) {
+                (AccessKind::ObjectAccess { key: name }, MapKind::Object)
+                | (AccessKind::MapAccess { key: name }, MapKind::Map) => {
+                    key.string_value() == name.string_value()
+                }
+                (AccessKind::ArrayAccessExplicit { key: name }, MapKind::Array) => match key {
+                    MapKey::Explicit(key) => key.string_value() == name.string_value(),
+                    MapKey::Implicit(_) => name.string_value() == index.to_string(),
+                },
+                (AccessKind::ArrayAccessImplicit, MapKind::Array) => {
+                    matches!(key, MapKey::Implicit(_))
+                }
+                _ => false,
+            };
+            entries
+                .filter(|(index, (key, _))| matches(*index, key))
+                .map(|(index, (key, value))| child(index, key, value))
+                .collect()
+        }
+    }
+}
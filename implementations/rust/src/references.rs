@@ -0,0 +1,85 @@
+//! Navigation between the entries that share a path prefix.
+//!
+//! Because MERC repeats paths, a value such as `.materials{metal}` is usually spread over many entries.
+use crate::{parser::Entry, Access, Error, Span};
+use itertools::Itertools;
+
+/// The entries that share the path prefix under a cursor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathReferences {
+    /// The path prefix under the cursor, for example `.materials{metal}`
+    /// if the cursor is on `{metal}` of `.materials{metal}.reflectivity = 1.0`.
+    pub path: String,
+    /// Every entry whose path starts with the prefix, in source order.
+    pub references: Vec<Reference>,
+    /// The span where the type of the parent of the prefix was first inferred,
+    /// for example the first `{...}` access after `.materials` if the prefix is `.materials{metal}`.
+    pub parent_inferred_at: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+    /// The span of the entry, excluding its comments.
+    pub entry: Span,
+    /// The span of the last access of the prefix in this entry.
+    pub access: Span,
+}
+
+/// Find every entry that shares the path prefix ending at the access under the given byte offset.
+///
+/// Returns `None` if the offset is not on any access.
+/// Note that each `[+]` creates a new element,
+/// so a prefix that contains `[+]` is only shared by the entry under the cursor.
+pub fn find_references(merc: &str, offset: usize) -> Result<Option<PathReferences>, Error> {
+    let parsed = crate::parse(merc)?;
+    let value = crate::evaluate(crate::parse(merc)?)?;
+    let entries = parsed.entries().collect_vec();
+    let find_access = |is_under_cursor: &dyn Fn(&Span) -> bool| {
+        entries.iter().find_map(|entry| {
+            entry
+                .accesses
+                .iter()
+                .position(|access| is_under_cursor(&access.span))
+                .map(|index| (*entry, index))
+        })
+    };
+    // A cursor right after an access, such as at the end of a line, is also on the access
+    let Some((cursor_entry, index)) = find_access(&|span| span.byte_range().contains(&offset))
+        .or_else(|| find_access(&|span| span.end() == offset))
+    else {
+        return Ok(None);
+    };
+    let prefix = cursor_entry.accesses.iter().take(index + 1).collect_vec();
+
+    let references = entries
+        .iter()
+        .filter(|entry| std::ptr::eq(**entry, cursor_entry) || shares_prefix(entry, &prefix))
+        .map(|entry| Reference {
+            entry: Span::new(entry.accesses.first().span.start(), entry.value.span.end()),
+            access: entry.accesses[index].span.clone(),
+        })
+        .collect();
+
+    // The parent of an element created by `[+]` only exists in the entry under the cursor,
+    // so its type is inferred at the access that follows it
+    let parent_inferred_at = prefix[..index]
+        .iter()
+        .try_fold(&value, |value, access| value.child(&access.kind))
+        .map(|parent| parent.inferred_at().clone())
+        .unwrap_or_else(|| prefix[index].span.clone());
+
+    Ok(Some(PathReferences {
+        path: prefix.iter().map(|access| access.kind.display()).collect(),
+        references,
+        parent_inferred_at,
+    }))
+}
+
+fn shares_prefix(entry: &Entry, prefix: &[&Access]) -> bool {
+    entry.accesses.len() >= prefix.len()
+        && entry
+            .accesses
+            .iter()
+            .zip(prefix)
+            .all(|(a, b)| a.kind.refers_to_same_child(&b.kind))
+}
@@ -0,0 +1,607 @@
+//! Validation of MERC against a JSON Schema.
+//!
+//! Only a subset of draft 2020-12 is supported:
+//! `type`, `enum`, `const`, `$ref` (within the same document),
+//! `properties`, `patternProperties`, `additionalProperties`, `required`, `items`,
+//! `pattern`, `minLength`, `maxLength`, `minItems`, `maxItems`,
+//! `minimum`, `maximum`, `exclusiveMinimum` and `exclusiveMaximum`.
+//! Other keywords are ignored.
+use crate::{data::Value, AccessKind, MapKey, MapKind, Parsed, Span, ValueScalarKind};
+use annotate_snippets::Level;
+use itertools::Itertools;
+use regex::Regex;
+use std::{collections::HashMap, path::Path};
+
+/// A JSON Schema that MERC values can be validated against.
+#[derive(Debug, Clone)]
+pub struct Schema {
+    root: serde_json::Value,
+    /// The compiled regexes of `pattern` and `patternProperties`
+    patterns: HashMap<String, Regex>,
+}
+
+/// An error raised while loading a schema.
+#[derive(Debug)]
+pub struct SchemaError(String);
+
+impl std::fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+/// A value or an access that does not conform to the schema.
+///
+/// A value violation is located at the span where the offending value was inferred,
+/// while an access violation is located at the offending access.
+#[derive(Debug, Clone)]
+pub struct SchemaViolation {
+    keyword: &'static str,
+    message: String,
+    span: Span,
+    /// The replacement of the span that resolves this violation,
+    /// only access violations have one
+    fix: Option<String>,
+}
+
+impl SchemaViolation {
+    pub fn code(&self) -> &'static str {
+        match self.fix {
+            Some(_) => "E0302",
+            None => "E0301",
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self.fix {
+            Some(_) => "Wrong Access Kind",
+            None => "Schema Violation",
+        }
+    }
+
+    /// The schema keyword that is violated, such as `required`.
+    pub fn keyword(&self) -> &'static str {
+        self.keyword
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    /// The replacement of [`SchemaViolation::span`] that resolves this violation, if any.
+    pub fn fix(&self) -> Option<&str> {
+        self.fix.as_deref()
+    }
+
+    /// How to resolve this violation, if it has a fix.
+    pub fn help(&self) -> Option<String> {
+        self.fix.as_ref().map(|fix| format!("use `{fix}` instead"))
+    }
+
+    pub fn display(&self, source: &str) -> String {
+        let help = self.help();
+        crate::data::render(
+            self.code(),
+            self.title(),
+            vec![Level::Error
+                .span(self.span.byte_range())
+                .label(&self.message)],
+            help.as_deref(),
+            source,
+        )
+    }
+}
+
+impl std::fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for SchemaViolation {}
+
+impl std::str::FromStr for Schema {
+    type Err = SchemaError;
+
+    fn from_str(json: &str) -> Result<Self, Self::Err> {
+        let json = serde_json::from_str(json)
+            .map_err(|error| SchemaError(format!("Invalid JSON: {error}")))?;
+        Schema::from_json(json)
+    }
+}
+
+impl Schema {
+    pub fn from_json(root: serde_json::Value) -> Result<Schema, SchemaError> {
+        let mut patterns = HashMap::new();
+        compile_patterns(&root, &mut patterns)?;
+        Ok(Schema { root, patterns })
+    }
+
+    /// Load the schema from a local JSON file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Schema, SchemaError> {
+        let path = path.as_ref();
+        std::fs::read_to_string(path)
+            .map_err(|error| SchemaError(format!("Unable to read {}: {error}", path.display())))?
+            .parse()
+    }
+
+    /// Every violation of the given value, in the order of the schema traversal.
+    pub fn validate(&self, value: &Value) -> Vec<SchemaViolation> {
+        let mut violations = Vec::new();
+        self.validate_at(&self.root, value, "", &mut violations);
+        violations
+    }
+
+    fn validate_at(
+        &self,
+        schema: &serde_json::Value,
+        value: &Value,
+        path: &str,
+        violations: &mut Vec<SchemaViolation>,
+    ) {
+        let mut violate = |keyword, message| {
+            violations.push(SchemaViolation {
+                keyword,
+                message,
+                span: value.inferred_at().clone(),
+                fix: None,
+            })
+        };
+        let schema = match schema {
+            serde_json::Value::Bool(true) => return,
+            serde_json::Value::Bool(false) => {
+                return violate("false", format!("{} is not allowed", describe_path(path)))
+            }
+            serde_json::Value::Object(schema) => schema,
+            _ => return,
+        };
+
+        // The keywords next to `$ref` apply too, as of draft 2019-09
+        if let Some(referenced) = schema
+            .get("$ref")
+            .and_then(|reference| reference.as_str())
+            .and_then(|reference| self.resolve(reference))
+        {
+            self.validate_at(referenced, value, path, violations);
+        }
+        self.validate_keywords(schema, value, path, violations)
+    }
+
+    fn validate_keywords(
+        &self,
+        schema: &serde_json::Map<String, serde_json::Value>,
+        value: &Value,
+        path: &str,
+        violations: &mut Vec<SchemaViolation>,
+    ) {
+        let mut violate = |keyword, message| {
+            violations.push(SchemaViolation {
+                keyword,
+                message,
+                span: value.inferred_at().clone(),
+                fix: None,
+            })
+        };
+        let actual_type = json_type(value);
+
+        if let Some(expected) = schema.get("type") {
+            let expected = match expected {
+                serde_json::Value::String(typ) => vec![typ.as_str()],
+                serde_json::Value::Array(types) => {
+                    types.iter().filter_map(|typ| typ.as_str()).collect()
+                }
+                _ => Vec::new(),
+            };
+            if !expected.is_empty()
+                && !expected.iter().any(|typ| {
+                    *typ == actual_type || (*typ == "number" && actual_type == "integer")
+                })
+            {
+                // The other keywords are irrelevant to a value of the wrong type
+                return violate(
+                    "type",
+                    format!(
+                        "expected {}, found {actual_type}",
+                        expected.iter().map(|typ| format!("`{typ}`")).join(" or ")
+                    ),
+                );
+            }
+        }
+
+        if let Some(members) = schema.get("enum").and_then(|members| members.as_array()) {
+            let json = value.clone().into_json();
+            if !members.contains(&json) {
+                violate(
+                    "enum",
+                    format!(
+                        "expected one of {}, found {json}",
+                        members
+                            .iter()
+                            .map(|member| format!("`{member}`"))
+                            .join(", ")
+                    ),
+                )
+            }
+        }
+        if let Some(constant) = schema.get("const") {
+            let json = value.clone().into_json();
+            if &json != constant {
+                violate("const", format!("expected `{constant}`, found {json}"))
+            }
+        }
+
+        if let Some(string) = string_value(value) {
+            let length = string.chars().count();
+            if let Some(minimum) = schema.get("minLength").and_then(|length| length.as_u64()) {
+                if (length as u64) < minimum {
+                    violate(
+                        "minLength",
+                        format!("expected at least {minimum} characters, found {length}"),
+                    )
+                }
+            }
+            if let Some(maximum) = schema.get("maxLength").and_then(|length| length.as_u64()) {
+                if (length as u64) > maximum {
+                    violate(
+                        "maxLength",
+                        format!("expected at most {maximum} characters, found {length}"),
+                    )
+                }
+            }
+            if let Some(regex) = schema
+                .get("pattern")
+                .and_then(|pattern| pattern.as_str())
+                .and_then(|pattern| self.patterns.get(pattern))
+            {
+                if !regex.is_match(string) {
+                    violate(
+                        "pattern",
+                        format!("expected a string matching `{}`", regex.as_str()),
+                    )
+                }
+            }
+        }
+
+        if let Some(number) = value.as_f64() {
+            let bound = |keyword: &str| schema.get(keyword).and_then(|bound| bound.as_f64());
+            if let Some(minimum) = bound("minimum").filter(|minimum| number < *minimum) {
+                violate(
+                    "minimum",
+                    format!("expected at least {minimum}, found {number}"),
+                )
+            }
+            if let Some(maximum) = bound("maximum").filter(|maximum| number > *maximum) {
+                violate(
+                    "maximum",
+                    format!("expected at most {maximum}, found {number}"),
+                )
+            }
+            if let Some(minimum) = bound("exclusiveMinimum").filter(|minimum| number <= *minimum) {
+                violate(
+                    "exclusiveMinimum",
+                    format!("expected more than {minimum}, found {number}"),
+                )
+            }
+            if let Some(maximum) = bound("exclusiveMaximum").filter(|maximum| number >= *maximum) {
+                violate(
+                    "exclusiveMaximum",
+                    format!("expected less than {maximum}, found {number}"),
+                )
+            }
+        }
+
+        let Some(map_like) = value.as_map_like() else {
+            return;
+        };
+        match map_like.kind() {
+            MapKind::Object | MapKind::Map => {
+                if let Some(required) = schema
+                    .get("required")
+                    .and_then(|required| required.as_array())
+                {
+                    for key in required.iter().filter_map(|key| key.as_str()) {
+                        if map_like.get(key).is_none() {
+                            violate(
+                                "required",
+                                format!(
+                                    "missing required property `{key}` of {}",
+                                    describe_path(path)
+                                ),
+                            )
+                        }
+                    }
+                }
+                let properties = schema
+                    .get("properties")
+                    .and_then(|properties| properties.as_object());
+                let pattern_properties = schema
+                    .get("patternProperties")
+                    .and_then(|properties| properties.as_object());
+                let additional_properties = schema.get("additionalProperties");
+                for (key, child) in map_like.iter() {
+                    let key_string = key.string_value();
+                    let child_path = format!("{path}{}", child_access(map_like.kind(), key));
+                    let property = properties.and_then(|properties| properties.get(&key_string));
+                    let patterns = pattern_properties
+                        .into_iter()
+                        .flatten()
+                        .filter(|(pattern, _)| {
+                            self.patterns
+                                .get(*pattern)
+                                .is_some_and(|regex| regex.is_match(&key_string))
+                        })
+                        .map(|(_, schema)| schema)
+                        .collect_vec();
+                    if let Some(property) = property {
+                        self.validate_at(property, child, &child_path, violations)
+                    }
+                    for schema in &patterns {
+                        self.validate_at(schema, child, &child_path, violations)
+                    }
+                    if property.is_none() && patterns.is_empty() {
+                        match additional_properties {
+                            Some(serde_json::Value::Bool(false)) => {
+                                violations.push(SchemaViolation {
+                                    keyword: "additionalProperties",
+                                    message: format!(
+                                        "unexpected property `{key_string}` of {}",
+                                        describe_path(path)
+                                    ),
+                                    span: child.inferred_at().clone(),
+                                    fix: None,
+                                })
+                            }
+                            Some(schema) => {
+                                self.validate_at(schema, child, &child_path, violations)
+                            }
+                            None => {}
+                        }
+                    }
+                }
+            }
+            MapKind::Array => {
+                let length = map_like.iter().count();
+                if let Some(minimum) = schema.get("minItems").and_then(|items| items.as_u64()) {
+                    if (length as u64) < minimum {
+                        violate(
+                            "minItems",
+                            format!("expected at least {minimum} items, found {length}"),
+                        )
+                    }
+                }
+                if let Some(maximum) = schema.get("maxItems").and_then(|items| items.as_u64()) {
+                    if (length as u64) > maximum {
+                        violate(
+                            "maxItems",
+                            format!("expected at most {maximum} items, found {length}"),
+                        )
+                    }
+                }
+                if let Some(items) = schema.get("items") {
+                    for (index, (_, item)) in map_like.iter().enumerate() {
+                        self.validate_at(items, item, &format!("{path}[{index}]"), violations)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Every access whose kind disagrees with the schema.
+    ///
+    /// Keys listed in `properties` are defined by the schema, so they should be accessed as `.key`,
+    /// while keys that are only allowed by `patternProperties` or `additionalProperties`
+    /// are user-defined, so they should be accessed as `{key}`.
+    pub fn validate_accesses(&self, parsed: &Parsed) -> Vec<SchemaViolation> {
+        parsed
+            .entries()
+            .flat_map(|entry| {
+                let mut schema = Some(&self.root);
+                entry.accesses.iter().filter_map(move |access| {
+                    let (kind, keyword, child) = self.child_schema(schema?, &access.kind)?;
+                    schema = Some(child);
+                    let (expected, message) = match (&access.kind, kind) {
+                        (AccessKind::ObjectAccess { key }, MapKind::Map) => (
+                            AccessKind::MapAccess { key: key.clone() },
+                            format!("`{}` is a user-defined key", key.display()),
+                        ),
+                        (AccessKind::MapAccess { key }, MapKind::Object) => (
+                            AccessKind::ObjectAccess { key: key.clone() },
+                            format!("`{}` is a property defined by the schema", key.display()),
+                        ),
+                        _ => return None,
+                    };
+                    Some(SchemaViolation {
+                        keyword,
+                        message,
+                        span: access.span.clone(),
+                        fix: Some(expected.display()),
+                    })
+                })
+            })
+            .collect()
+    }
+
+    /// Change every object that the schema describes as a map, such as
+    /// `{"additionalProperties": {"type": "string"}}`, into a map.
+    pub(crate) fn assign_map_kinds(&self, value: &mut Value) {
+        self.assign_map_kinds_at(&self.root, value)
+    }
+
+    fn assign_map_kinds_at(&self, schema: &serde_json::Value, value: &mut Value) {
+        let Some(map_like) = value.as_map_like_mut() else {
+            return;
+        };
+        let kind = map_like.kind().clone();
+        // A map cannot contain properties, so only objects whose keys are all user-defined are changed
+        let mut is_map = kind == MapKind::Object;
+        for (key, child) in map_like.iter_mut() {
+            let access = match (&kind, key) {
+                (MapKind::Array, _) => AccessKind::ArrayAccessImplicit,
+                (_, MapKey::Explicit(key)) => AccessKind::ObjectAccess { key: key.clone() },
+                (_, MapKey::Implicit(_)) => continue,
+            };
+            match self.child_schema(schema, &access) {
+                Some((kind, _, child_schema)) => {
+                    is_map &= kind == MapKind::Map;
+                    self.assign_map_kinds_at(child_schema, child)
+                }
+                None => is_map = false,
+            }
+        }
+        if is_map && !map_like.is_empty() {
+            map_like.set_kind(MapKind::Map)
+        }
+    }
+
+    /// The schema of the child that the given access refers to,
+    /// along with the kind of map that the schema describes the parent as,
+    /// and the keyword that defines the child.
+    pub(crate) fn child_schema<'a>(
+        &'a self,
+        schema: &'a serde_json::Value,
+        access: &AccessKind,
+    ) -> Option<(MapKind, &'static str, &'a serde_json::Value)> {
+        let schema_object = schema.as_object()?;
+        let child = match access {
+            AccessKind::ArrayAccessImplicit | AccessKind::ArrayAccessExplicit { .. } => {
+                schema_object
+                    .get("items")
+                    .map(|items| (MapKind::Array, "items", items))
+            }
+            AccessKind::ObjectAccess { key } | AccessKind::MapAccess { key } => {
+                let key = key.as_str();
+                schema_object
+                    .get("properties")
+                    .and_then(|properties| properties.get(key))
+                    .map(|property| (MapKind::Object, "properties", property))
+                    .or_else(|| {
+                        schema_object
+                            .get("patternProperties")?
+                            .as_object()?
+                            .iter()
+                            .find(|(pattern, _)| {
+                                self.patterns
+                                    .get(*pattern)
+                                    .is_some_and(|regex| regex.is_match(key))
+                            })
+                            .map(|(_, property)| (MapKind::Map, "patternProperties", property))
+                    })
+                    .or_else(|| {
+                        schema_object
+                            .get("additionalProperties")
+                            .filter(|property| property.is_object())
+                            .map(|property| (MapKind::Map, "additionalProperties", property))
+                    })
+            }
+        };
+        child.or_else(|| {
+            let reference = schema_object.get("$ref")?.as_str()?;
+            self.child_schema(self.resolve(reference)?, access)
+        })
+    }
+
+    pub(crate) fn root(&self) -> &serde_json::Value {
+        &self.root
+    }
+
+    /// The keywords of the given schema, followed by those of the schemas that it refers to.
+    pub(crate) fn keywords<'a>(
+        &'a self,
+        schema: &'a serde_json::Value,
+    ) -> Vec<&'a serde_json::Map<String, serde_json::Value>> {
+        std::iter::successors(schema.as_object(), |schema| {
+            self.resolve(schema.get("$ref")?.as_str()?)?.as_object()
+        })
+        // Stop at a cycle of references
+        .take(16)
+        .collect()
+    }
+
+    /// Resolve a reference within this schema, such as `#/$defs/machine`.
+    fn resolve(&self, reference: &str) -> Option<&serde_json::Value> {
+        self.root.pointer(reference.strip_prefix('#')?)
+    }
+}
+
+fn compile_patterns(
+    schema: &serde_json::Value,
+    patterns: &mut HashMap<String, Regex>,
+) -> Result<(), SchemaError> {
+    let mut compile = |pattern: &str| -> Result<(), SchemaError> {
+        if !patterns.contains_key(pattern) {
+            let regex = Regex::new(pattern)
+                .map_err(|error| SchemaError(format!("Invalid pattern `{pattern}`: {error}")))?;
+            patterns.insert(pattern.to_string(), regex);
+        }
+        Ok(())
+    };
+    match schema {
+        serde_json::Value::Object(schema) => {
+            if let Some(pattern) = schema.get("pattern").and_then(|pattern| pattern.as_str()) {
+                compile(pattern)?
+            }
+            if let Some(properties) = schema
+                .get("patternProperties")
+                .and_then(|properties| properties.as_object())
+            {
+                for pattern in properties.keys() {
+                    compile(pattern)?
+                }
+            }
+            schema
+                .values()
+                .try_for_each(|schema| compile_patterns(schema, patterns))
+        }
+        serde_json::Value::Array(schemas) => schemas
+            .iter()
+            .try_for_each(|schema| compile_patterns(schema, patterns)),
+        _ => Ok(()),
+    }
+}
+
+/// The JSON type of the given value, as named by JSON Schema.
+fn json_type(value: &Value) -> &'static str {
+    if let Some(map_like) = value.as_map_like() {
+        return match map_like.kind() {
+            MapKind::Object | MapKind::Map => "object",
+            MapKind::Array => "array",
+        };
+    }
+    match value.as_scalar() {
+        // Datetimes are strings in JSON
+        Some(
+            ValueScalarKind::String(_) | ValueScalarKind::Enum(_) | ValueScalarKind::Datetime(_),
+        ) => "string",
+        Some(ValueScalarKind::Number(number)) if number.is_integer() => "integer",
+        Some(ValueScalarKind::Number(_)) => "number",
+        Some(ValueScalarKind::Boolean(_)) => "boolean",
+        Some(ValueScalarKind::Null) | None => "null",
+    }
+}
+
+/// The string value of strings and enums, as enums are strings in JSON.
+fn string_value(value: &Value) -> Option<&str> {
+    value.as_str().or_else(|| value.as_enum())
+}
+
+fn child_access(kind: &MapKind, key: &MapKey) -> String {
+    match kind {
+        MapKind::Object => format!(".{}", key.display()),
+        MapKind::Map => format!("{{{}}}", key.display()),
+        MapKind::Array => format!("[{}]", key.display()),
+    }
+}
+
+fn describe_path(path: &str) -> String {
+    if path.is_empty() {
+        "the root".to_string()
+    } else {
+        format!("`{path}`")
+    }
+}
@@ -0,0 +1,643 @@
+use crate::{
+    data::EvaluateError,
+    parser::{MercString, Span, StringKind},
+    Identifier, MapKey, MapKind, Number, Value, ValueScalarKind,
+};
+use serde::ser::{self, Serialize};
+
+/// Serialize the given data structure as MERC.
+///
+/// Struct fields are printed as object accesses (`.field`),
+/// while the keys of maps (e.g. `HashMap`, `BTreeMap`) are printed as map accesses (`{key}`).
+///
+/// Empty objects, maps and arrays, such as an empty `Vec`, are an error,
+/// because every entry of MERC assigns a leaf, so they cannot be represented.
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String, SerializeError> {
+    let value = to_value(value)?;
+    if value.as_map_like().is_none() {
+        return Err(SerializeError(
+            "Only objects, maps and arrays can be represented at the top level of MERC".to_string(),
+        ));
+    }
+    reject_empty(&value, "")?;
+    Ok(value.print())
+}
+
+fn reject_empty(value: &Value, path: &str) -> Result<(), SerializeError> {
+    let Some(map_like) = value.as_map_like() else {
+        return Ok(());
+    };
+    if map_like.is_empty() {
+        let kind = match map_like.kind() {
+            MapKind::Object => "object",
+            MapKind::Map => "map",
+            MapKind::Array => "array",
+        };
+        let at = if path.is_empty() {
+            "the top level".to_string()
+        } else {
+            format!("`{path}`")
+        };
+        return Err(SerializeError(format!(
+            "The empty {kind} at {at} cannot be represented in MERC"
+        )));
+    }
+    map_like.iter().try_for_each(|(key, value)| {
+        let path = match map_like.kind() {
+            MapKind::Object => format!("{path}.{}", key.display()),
+            MapKind::Map => format!("{path}{{{}}}", key.display()),
+            MapKind::Array => format!("{path}[{}]", key.display()),
+        };
+        reject_empty(value, &path)
+    })
+}
+
+/// Convert the given data structure into a MERC value.
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Value, SerializeError> {
+    value.serialize(ValueSerializer)
+}
+
+#[derive(Debug)]
+pub struct SerializeError(String);
+
+impl std::fmt::Display for SerializeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for SerializeError {}
+
+impl ser::Error for SerializeError {
+    fn custom<T: std::fmt::Display>(message: T) -> Self {
+        Self(message.to_string())
+    }
+}
+
+impl From<EvaluateError> for SerializeError {
+    fn from(error: EvaluateError) -> Self {
+        Self(error.title().to_string())
+    }
+}
+
+fn string(s: &str) -> Result<Value, SerializeError> {
+    Ok(Value::scalar(ValueScalarKind::String(MercString::new(
+        StringKind::SinglelineRaw,
+        Span::default(),
+        s,
+    )?)))
+}
+
+fn number(number: impl Into<Number>) -> Result<Value, SerializeError> {
+    Ok(Value::scalar(ValueScalarKind::Number(number.into())))
+}
+
+fn array(items: Vec<Value>) -> Value {
+    Value::map_like(
+        MapKind::Array,
+        items
+            .into_iter()
+            .enumerate()
+            .map(|(index, item)| (MapKey::array_item(index, &item), item)),
+    )
+}
+
+fn variant(variant: &str, value: Value) -> Result<Value, SerializeError> {
+    Ok(Value::map_like(
+        MapKind::Object,
+        [(MapKey::Explicit(Identifier::from_str(variant)?), value)],
+    ))
+}
+
+struct ValueSerializer;
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = SerializeError;
+
+    type SerializeSeq = SerializeArray;
+    type SerializeTuple = SerializeArray;
+    type SerializeTupleStruct = SerializeArray;
+    type SerializeTupleVariant = SerializeArray;
+    type SerializeMap = SerializeMap;
+    type SerializeStruct = SerializeMap;
+    type SerializeStructVariant = SerializeMap;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, SerializeError> {
+        Ok(Value::scalar(ValueScalarKind::Boolean(v)))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value, SerializeError> {
+        number(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value, SerializeError> {
+        number(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value, SerializeError> {
+        number(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value, SerializeError> {
+        number(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value, SerializeError> {
+        number(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value, SerializeError> {
+        number(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value, SerializeError> {
+        number(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value, SerializeError> {
+        number(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Value, SerializeError> {
+        number(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Value, SerializeError> {
+        number(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value, SerializeError> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value, SerializeError> {
+        Number::from_f64(v)
+            .ok_or_else(|| SerializeError(format!("{v} cannot be represented in MERC")))
+            .and_then(number)
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value, SerializeError> {
+        string(&v.to_string())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value, SerializeError> {
+        string(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, SerializeError> {
+        Ok(array(
+            v.iter()
+                .map(|byte| number(*byte))
+                .collect::<Result<_, _>>()?,
+        ))
+    }
+
+    fn serialize_none(self) -> Result<Value, SerializeError> {
+        self.serialize_unit()
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value, SerializeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value, SerializeError> {
+        Ok(Value::scalar(ValueScalarKind::Null))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, SerializeError> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Value, SerializeError> {
+        Ok(Value::scalar(ValueScalarKind::Enum(Identifier::from_str(
+            variant,
+        )?)))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value, SerializeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant_name: &'static str,
+        value: &T,
+    ) -> Result<Value, SerializeError> {
+        variant(variant_name, value.serialize(self)?)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeArray, SerializeError> {
+        Ok(SerializeArray {
+            variant: None,
+            items: Vec::with_capacity(len.unwrap_or_default()),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeArray, SerializeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeArray, SerializeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeArray, SerializeError> {
+        Ok(SerializeArray {
+            variant: Some(variant),
+            items: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<SerializeMap, SerializeError> {
+        Ok(SerializeMap {
+            kind: MapKind::Map,
+            variant: None,
+            entries: Vec::new(),
+            next_key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<SerializeMap, SerializeError> {
+        Ok(SerializeMap {
+            kind: MapKind::Object,
+            variant: None,
+            entries: Vec::new(),
+            next_key: None,
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<SerializeMap, SerializeError> {
+        Ok(SerializeMap {
+            kind: MapKind::Object,
+            variant: Some(variant),
+            entries: Vec::new(),
+            next_key: None,
+        })
+    }
+}
+
+struct SerializeArray {
+    /// The name of the enum variant that wraps this array, if any
+    variant: Option<&'static str>,
+    items: Vec<Value>,
+}
+
+impl SerializeArray {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerializeError> {
+        self.items.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value, SerializeError> {
+        let value = array(self.items);
+        match self.variant {
+            Some(variant_name) => variant(variant_name, value),
+            None => Ok(value),
+        }
+    }
+}
+
+impl ser::SerializeSeq for SerializeArray {
+    type Ok = Value;
+    type Error = SerializeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), SerializeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, SerializeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SerializeArray {
+    type Ok = Value;
+    type Error = SerializeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), SerializeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, SerializeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeArray {
+    type Ok = Value;
+    type Error = SerializeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerializeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, SerializeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeArray {
+    type Ok = Value;
+    type Error = SerializeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerializeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, SerializeError> {
+        self.finish()
+    }
+}
+
+struct SerializeMap {
+    kind: MapKind,
+    /// The name of the enum variant that wraps this map, if any
+    variant: Option<&'static str>,
+    entries: Vec<(MapKey, Value)>,
+    next_key: Option<MapKey>,
+}
+
+impl SerializeMap {
+    fn insert<T: Serialize + ?Sized>(
+        &mut self,
+        key: &str,
+        value: &T,
+    ) -> Result<(), SerializeError> {
+        self.entries.push((
+            MapKey::Explicit(Identifier::from_str(key)?),
+            value.serialize(ValueSerializer)?,
+        ));
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value, SerializeError> {
+        let value = Value::map_like(self.kind, self.entries);
+        match self.variant {
+            Some(variant_name) => variant(variant_name, value),
+            None => Ok(value),
+        }
+    }
+}
+
+impl ser::SerializeMap for SerializeMap {
+    type Ok = Value;
+    type Error = SerializeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SerializeError> {
+        let key = key.serialize(KeySerializer)?;
+        self.next_key = Some(MapKey::Explicit(Identifier::from_str(&key)?));
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerializeError> {
+        let key = self
+            .next_key
+            .take()
+            .expect("serialize_key should be called before serialize_value");
+        self.entries.push((key, value.serialize(ValueSerializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, SerializeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for SerializeMap {
+    type Ok = Value;
+    type Error = SerializeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), SerializeError> {
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Value, SerializeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeMap {
+    type Ok = Value;
+    type Error = SerializeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), SerializeError> {
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Value, SerializeError> {
+        self.finish()
+    }
+}
+
+/// Serializes the keys of maps, which must be representable as strings.
+struct KeySerializer;
+
+fn key_must_be_a_string() -> SerializeError {
+    SerializeError("Map keys must be strings, numbers, booleans or unit variants".to_string())
+}
+
+impl ser::Serializer for KeySerializer {
+    type Ok = String;
+    type Error = SerializeError;
+
+    type SerializeSeq = ser::Impossible<String, SerializeError>;
+    type SerializeTuple = ser::Impossible<String, SerializeError>;
+    type SerializeTupleStruct = ser::Impossible<String, SerializeError>;
+    type SerializeTupleVariant = ser::Impossible<String, SerializeError>;
+    type SerializeMap = ser::Impossible<String, SerializeError>;
+    type SerializeStruct = ser::Impossible<String, SerializeError>;
+    type SerializeStructVariant = ser::Impossible<String, SerializeError>;
+
+    fn serialize_bool(self, v: bool) -> Result<String, SerializeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<String, SerializeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<String, SerializeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<String, SerializeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<String, SerializeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<String, SerializeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<String, SerializeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<String, SerializeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<String, SerializeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<String, SerializeError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<String, SerializeError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_char(self, v: char) -> Result<String, SerializeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<String, SerializeError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<String, SerializeError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_none(self) -> Result<String, SerializeError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, _value: &T) -> Result<String, SerializeError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit(self) -> Result<String, SerializeError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String, SerializeError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<String, SerializeError> {
+        Ok(variant.to_string())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<String, SerializeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String, SerializeError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, SerializeError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, SerializeError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, SerializeError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, SerializeError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, SerializeError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, SerializeError> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, SerializeError> {
+        Err(key_must_be_a_string())
+    }
+}
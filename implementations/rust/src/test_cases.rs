@@ -1,4 +1,10 @@
-use crate::{format_merc, json_to_merc_string, merc_to_json, merc_to_json_string, parser::parse};
+use crate::{
+    check, check_with_includes, check_with_schema, complete, evaluate, find_references,
+    format_merc, json_to_merc_string, json_to_merc_string_with_schema, merc_diff, merc_to_json,
+    merc_to_json_string, merc_to_json_string_with, merc_to_toml_string, merc_to_yaml_string, merge,
+    parser::parse, print_matches, query, toml_to_merc_string, yaml_to_merc_string, EntryOrder,
+    Error, FormatOptions, JsonNumbers, MapKind, MemoryLoader, Overlay, Schema, Span, Type,
+};
 
 #[test]
 fn merc_to_json_1() {
@@ -138,6 +144,14 @@ fn top_level_object_1() {
     pretty_assertions::assert_eq!(merc_to_json(input).unwrap(), expected_json)
 }
 
+#[test]
+fn no_entries_evaluate_to_an_empty_object() {
+    let value = evaluate(crate::Parsed::from_entries(Vec::new())).unwrap();
+    pretty_assertions::assert_eq!(value.typ(), Type::Object);
+    pretty_assertions::assert_eq!(value.print(), "");
+    pretty_assertions::assert_eq!(value.into_json(), serde_json::json!({}));
+}
+
 #[test]
 fn top_level_map_1() {
     let input = r#"
@@ -194,12 +208,100 @@ fn parse_error_1() {
     .trim();
     pretty_assertions::assert_eq!(
         merc_to_json_string(input).err().unwrap(),
-        " --> 1:6
+        "
+error[E0002]: Missing Equals Sign
   |
 1 | .x.y 1
-  |      ^---
+  |      ^ expected `=` after the path `.x.y`, found `1`
+  |"
+        .trim_start()
+    )
+}
+
+#[test]
+fn parse_error_empty_file() {
+    pretty_assertions::assert_eq!(
+        merc_to_json_string("").err().unwrap(),
+        "
+error[E0007]: Missing Entry
+ = note: expected an entry, such as `.key = value`, found the end of the input"
+            .trim_start()
+    );
+    // The end of a file that ends with a newline is shown at the last character
+    pretty_assertions::assert_eq!(
+        merc_to_json_string("# Only a comment\n").err().unwrap(),
+        "
+error[E0007]: Missing Entry
+  |
+1 | # Only a comment
+  |                ^ expected an entry, such as `.key = value`, found the end of the input
+  |"
+        .trim_start()
+    );
+}
+
+#[test]
+fn parse_error_missing_value() {
+    let input = r#"
+.x.y = hello
+"#
+    .trim();
+    pretty_assertions::assert_eq!(
+        merc_to_json_string(input).err().unwrap(),
+        "
+error[E0003]: Missing Value
+  |
+1 | .x.y = hello
+  |        ^^^^^ expected a value after `=`, found `hello`
+  |
+  = help: strings must be quoted, for example `'hello'`"
+            .trim_start()
+    )
+}
+
+#[test]
+fn parse_error_unterminated_string() {
+    let input = r#"
+.x = 1
+.y = '''
+hello
+"#
+    .trim();
+    pretty_assertions::assert_eq!(
+        merc_to_json_string(input).err().unwrap(),
+        "
+error[E0006]: Unterminated String
+  |
+2 | .y = '''
+  |      ^^^ unterminated `'''` string
+  |"
+        .trim_start()
+    )
+}
+
+#[test]
+fn parse_error_missing_key() {
+    let input = r#"
+.x{y = 1
+.z = #
+"#
+    .trim();
+    pretty_assertions::assert_eq!(
+        merc_to_json_string(input).err().unwrap(),
+        "
+error[E0005]: Unclosed Access
+  |
+1 | .x{y = 1
+  |   ^^ expected `}` to close this access
+  |
+
+error[E0004]: Missing Key
+  |
+2 | .z = #
+  |       ^ expected a name after `#`, found the end of the input
   |
-  = expected array_access_implicit, array_access_explicit, object_access, or map_access"
+  = help: enums are written as `#name`, for example `#fast`"
+            .trim_start()
     )
 }
 
@@ -213,7 +315,7 @@ fn error_duplicate_assignment_1() {
     pretty_assertions::assert_eq!(
         merc_to_json_string(input).err().unwrap(),
         "
-error: Duplicate Assignment
+error[E0102]: Duplicate Assignment
   |
 1 | .x = 2
   |      - info: A value was previously assigned at this path.
@@ -234,7 +336,7 @@ fn error_type_mismatch_1() {
     pretty_assertions::assert_eq!(
         merc_to_json_string(input).err().unwrap(),
         "
-error: Type Mismatch
+error[E0101]: Type Mismatch
   |
 1 | .x.y = 2
   |   -- info: The type of the parent value was first inferred as Object due to this access.
@@ -245,6 +347,49 @@ error: Type Mismatch
     )
 }
 
+#[test]
+fn error_type_mismatch_scalar_accessed() {
+    let input = r#"
+.x = 1
+.x{y} = 2
+"#
+    .trim();
+    pretty_assertions::assert_eq!(
+        merc_to_json_string(input).err().unwrap(),
+        "
+error[E0101]: Type Mismatch
+  |
+1 | .x = 1
+  |      - info: The type of the parent value was first inferred as Integer due to this assignment.
+2 | .x{y} = 2
+  |   ^^^ Error: this access treats the parent value as Map, but it was inferred as a different type.
+  |"
+        .trim_start()
+    )
+}
+
+#[test]
+fn error_type_mismatch_map_like_assigned() {
+    // Assigning a scalar over an array does not silently drop its items
+    let input = r#"
+.x[+] = 1
+.x = 'a'
+"#
+    .trim();
+    pretty_assertions::assert_eq!(
+        merc_to_json_string(input).err().unwrap(),
+        "
+error[E0101]: Type Mismatch
+  |
+1 | .x[+] = 1
+  |   --- info: The type of this value was first inferred as Array due to this access.
+2 | .x = 'a'
+  |      ^^^ Error: this assignment treats the value as String, but it was inferred as a different type.
+  |"
+        .trim_start()
+    )
+}
+
 #[test]
 fn error_multiline_string_not_starting_with_newline() {
     let input = r#"
@@ -254,7 +399,7 @@ fn error_multiline_string_not_starting_with_newline() {
     pretty_assertions::assert_eq!(
         merc_to_json_string(input).err().unwrap(),
         " 
-error: Incorrect multi-line string format
+error[E0104]: Incorrect multi-line string format
   |
 1 |   [+] = '''hello
   |  _______^
@@ -275,7 +420,7 @@ hello'''"#
     pretty_assertions::assert_eq!(
         merc_to_json_string(input).err().unwrap(),
         "
-error: Incorrect multi-line string format
+error[E0104]: Incorrect multi-line string format
   |
 1 |   [+] = '''
   |  _______^
@@ -287,6 +432,134 @@ error: Incorrect multi-line string format
     )
 }
 
+#[test]
+fn error_invalid_datetime() {
+    let input = ".released = 2024-02-30";
+    pretty_assertions::assert_eq!(
+        merc_to_json_string(input).err().unwrap(),
+        "
+error[E0105]: Invalid Datetime
+  |
+1 | .released = 2024-02-30
+  |             ^^^^^^^^^^ This is not a valid date or time
+  |
+"
+        .trim()
+    )
+}
+
+#[test]
+fn error_number_out_of_range() {
+    let input = ".x = 1e99999999999999999999";
+    pretty_assertions::assert_eq!(
+        merc_to_json_string(input).err().unwrap(),
+        "
+error[E0106]: Number Out Of Range
+  |
+1 | .x = 1e99999999999999999999
+  |      ^^^^^^^^^^^^^^^^^^^^^^ The exponent of this number is out of range
+  |
+"
+        .trim()
+    )
+}
+
+#[test]
+fn numbers_keep_every_digit() {
+    let input = r#"
+.big = 123456789012345678901234567890
+.precise = 0.1000000000000000000000000000001
+.ratio = 1.0
+.sextillion = -6.02e+23
+"#
+    .trim();
+    pretty_assertions::assert_eq!(format_merc(input, None).unwrap(), input);
+    pretty_assertions::assert_eq!(
+        merc_to_json_string_with(input, JsonNumbers::Number).unwrap(),
+        r#"
+{
+  "big": 123456789012345678901234567890,
+  "precise": 0.1000000000000000000000000000001,
+  "ratio": 1.0,
+  "sextillion": -6.02e23
+}
+"#
+        .trim()
+    );
+    pretty_assertions::assert_eq!(
+        merc_to_json_string_with(input, JsonNumbers::String).unwrap(),
+        r#"
+{
+  "big": "123456789012345678901234567890",
+  "precise": "0.1000000000000000000000000000001",
+  "ratio": 1.0,
+  "sextillion": -6.02e23
+}
+"#
+        .trim()
+    );
+    pretty_assertions::assert_eq!(
+        merc_to_json_string_with(input, JsonNumbers::Error)
+            .err()
+            .unwrap(),
+        "
+error[E0107]: Inexact Number
+  |
+1 | .big = 123456789012345678901234567890
+  |        ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^ This number cannot be represented exactly by JSON readers
+  |
+"
+        .trim()
+    );
+}
+
+#[test]
+fn datetimes() {
+    let input = r#"
+.offset = 1979-05-27T07:32:00-08:00
+.local = 1979-05-27T07:32:00.5
+.date = 1979-05-27
+.time = 07:32:00
+"#
+    .trim();
+    pretty_assertions::assert_eq!(
+        merc_to_json_string(input).unwrap(),
+        r#"
+{
+  "date": "1979-05-27",
+  "local": "1979-05-27T07:32:00.5",
+  "offset": "1979-05-27T07:32:00-08:00",
+  "time": "07:32:00"
+}
+"#
+        .trim()
+    );
+    pretty_assertions::assert_eq!(
+        merc_to_toml_string(input).unwrap(),
+        r#"
+offset = 1979-05-27T07:32:00-08:00
+local = 1979-05-27T07:32:00.5
+date = 1979-05-27
+time = 07:32:00
+"#
+        .trim_start()
+    );
+    pretty_assertions::assert_eq!(
+        merc_to_yaml_string(input).unwrap(),
+        r#"
+offset: '1979-05-27T07:32:00-08:00'
+local: '1979-05-27T07:32:00.5'
+date: '1979-05-27'
+time: '07:32:00'
+"#
+        .trim_start()
+    );
+    pretty_assertions::assert_eq!(
+        toml_to_merc_string(&merc_to_toml_string(input).unwrap()).unwrap(),
+        input
+    );
+}
+
 #[test]
 fn format_merc_1() {
     let input = r#"
@@ -354,7 +627,7 @@ They are stored in C:\SolarSystem:\Earth
 
 "#
     .trim();
-    let actual = format_merc(input).unwrap();
+    let actual = format_merc(input, None).unwrap();
 
     pretty_assertions::assert_eq!(actual, expected);
 
@@ -362,20 +635,20 @@ They are stored in C:\SolarSystem:\Earth
     // format(parse(format(merc))) === format(merc)
     assert_eq!(
         format_merc(
-            &parse(&format_merc(input).unwrap())
-                .unwrap()
-                .into_string()
+            &evaluate(parse(&format_merc(input, None).unwrap()).unwrap())
                 .unwrap()
+                .print(),
+            None
         )
         .unwrap(),
-        format_merc(input).unwrap()
+        format_merc(input, None).unwrap()
     );
 
     // Test idempotency:
     // format(format(merc)) === format(merc)
     assert_eq!(
-        format_merc(&format_merc(input).unwrap()).unwrap(),
-        format_merc(input).unwrap()
+        format_merc(&format_merc(input, None).unwrap(), None).unwrap(),
+        format_merc(input, None).unwrap()
     );
 }
 
@@ -393,7 +666,7 @@ fn format_string_as_singleline_raw_string_if_possible() {
 .'c hello world' = 'hello world'
 "#
     .trim();
-    let actual = format_merc(input).unwrap();
+    let actual = format_merc(input, None).unwrap();
 
     pretty_assertions::assert_eq!(actual, expected);
 }
@@ -422,7 +695,7 @@ world
 '''
 "#
     .trim();
-    let actual = format_merc(input).unwrap();
+    let actual = format_merc(input, None).unwrap();
 
     pretty_assertions::assert_eq!(actual, expected);
 }
@@ -441,7 +714,7 @@ Somebody says: '''To be or not to be?'''
 ."Somebody says: '''To be or not to be?'''" = "Somebody says: '''To be or not to be?'''"
 "#
     .trim();
-    let actual = format_merc(input).unwrap();
+    let actual = format_merc(input, None).unwrap();
 
     pretty_assertions::assert_eq!(actual, expected);
 }
@@ -462,7 +735,1784 @@ Somebody says:
 """
 "#
     .trim();
-    let actual = format_merc(input).unwrap();
+    let actual = format_merc(input, None).unwrap();
+
+    pretty_assertions::assert_eq!(actual, expected);
+}
+
+#[test]
+fn public_api_typed_accessors() {
+    let input = r#"
+.name = 'merc'
+.version = 1
+.materials{metal}.reflectivity = 1.0
+.excludes[+] = 'target/'
+.excludes[+] = null
+"#
+    .trim();
+    let value = evaluate(crate::parse(input).unwrap()).unwrap();
+    assert_eq!(value.typ(), Type::Object);
+    assert_eq!(value.get("name").unwrap().as_str(), Some("merc"));
+    assert_eq!(value.get("version").unwrap().as_i64(), Some(1));
+
+    let materials = value.get("materials").unwrap();
+    assert!(materials.as_map().is_some());
+    assert!(materials.as_object().is_none());
+    assert_eq!(materials.as_map().unwrap().kind(), &MapKind::Map);
+    assert_eq!(
+        materials
+            .get("metal")
+            .unwrap()
+            .get("reflectivity")
+            .unwrap()
+            .as_f64(),
+        Some(1.0)
+    );
+
+    let excludes = value.get("excludes").unwrap().as_array().unwrap();
+    assert_eq!(excludes.len(), 2);
+    let excludes = excludes.values().collect::<Vec<_>>();
+    assert_eq!(excludes[0].as_str(), Some("target/"));
+    assert!(excludes[1].is_null());
+}
+
+#[test]
+fn public_api_error_span() {
+    let input = r#"
+.x = 2
+.x = 3
+"#
+    .trim();
+    let error = evaluate(crate::parse(input).unwrap()).err().unwrap();
+    assert!(matches!(error, Error::EvaluationError(_)));
+    assert_eq!(error.span().byte_range(), 12..13);
+    assert_eq!(error.to_string(), "Duplicate Assignment");
+}
+
+#[derive(serde::Deserialize, Debug, PartialEq)]
+struct Config {
+    name: String,
+    machines: std::collections::BTreeMap<String, Machine>,
+    excludes: Vec<String>,
+    #[serde(default)]
+    description: Option<String>,
+}
+
+#[derive(serde::Deserialize, Debug, PartialEq)]
+struct Machine {
+    size: Size,
+    replicas: u32,
+}
+
+#[derive(serde::Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+enum Size {
+    T3Medium,
+    T3Large,
+}
+
+#[test]
+fn deserialize_1() {
+    let input = r#"
+.name = 'production'
+.machines{api}.size = 't3-large'
+.machines{api}.replicas = 3
+.machines{worker}.size = 't3-medium'
+.machines{worker}.replicas = 1
+.excludes[+] = 'target/'
+"#
+    .trim();
+    let config: Config = crate::from_str(input).unwrap();
+    pretty_assertions::assert_eq!(
+        config,
+        Config {
+            name: "production".to_string(),
+            machines: [
+                (
+                    "api".to_string(),
+                    Machine {
+                        size: Size::T3Large,
+                        replicas: 3
+                    }
+                ),
+                (
+                    "worker".to_string(),
+                    Machine {
+                        size: Size::T3Medium,
+                        replicas: 1
+                    }
+                )
+            ]
+            .into_iter()
+            .collect(),
+            excludes: vec!["target/".to_string()],
+            description: None
+        }
+    )
+}
+
+#[test]
+fn deserialize_error_invalid_type() {
+    let input = r#"
+.name = 'production'
+.machines{api}.size = 't3-large'
+.machines{api}.replicas = 'three'
+.excludes[+] = 'target/'
+"#
+    .trim();
+    let error = crate::from_str::<Config>(input).err().unwrap();
+    pretty_assertions::assert_eq!(
+        error.display(input),
+        r#"
+error[E0201]: Deserialization Error
+  |
+3 | .machines{api}.replicas = 'three'
+  |                           ^^^^^^^ invalid type: string "three", expected u32
+  |"#
+        .trim_start()
+    )
+}
+
+#[test]
+fn deserialize_error_number_out_of_range() {
+    let input = ".ratio = 1e400";
+    let error = crate::from_str::<serde_json::Value>(input).err().unwrap();
+    pretty_assertions::assert_eq!(
+        error.display(input),
+        r#"
+error[E0201]: Deserialization Error
+  |
+1 | .ratio = 1e400
+  |          ^^^^^ The number 1e400 is too large to be deserialized
+  |"#
+        .trim_start()
+    )
+}
+
+#[test]
+fn deserialize_error_unknown_variant() {
+    let input = r#"
+.name = 'production'
+.machines{api}.size = 't3-huge'
+.machines{api}.replicas = 3
+.excludes[+] = 'target/'
+"#
+    .trim();
+    let error = crate::from_str::<Config>(input).err().unwrap();
+    pretty_assertions::assert_eq!(
+        error.display(input),
+        r#"
+error[E0201]: Deserialization Error
+  |
+2 | .machines{api}.size = 't3-huge'
+  |                       ^^^^^^^^^ unknown variant `t3-huge`, expected `t3-medium` or `t3-large`
+  |"#
+        .trim_start()
+    )
+}
+
+#[test]
+fn deserialize_error_missing_field() {
+    let input = r#"
+.name = 'production'
+.machines{api}.size = 't3-large'
+.excludes[+] = 'target/'
+"#
+    .trim();
+    let error = crate::from_str::<Config>(input).err().unwrap();
+    pretty_assertions::assert_eq!(
+        error.display(input),
+        r#"
+error[E0201]: Deserialization Error
+  |
+2 | .machines{api}.size = 't3-large'
+  |               ^^^^^ missing field `replicas`
+  |"#
+        .trim_start()
+    )
+}
+
+#[derive(serde::Serialize)]
+struct Deployment {
+    name: String,
+    replicas: u32,
+    machines: std::collections::BTreeMap<String, MachineSpec>,
+    labels: std::collections::HashMap<String, String>,
+    excludes: Vec<&'static str>,
+    mounts: Vec<Mount>,
+    strategy: Strategy,
+}
+
+#[derive(serde::Serialize)]
+struct MachineSpec {
+    size: &'static str,
+    spot: bool,
+}
+
+#[derive(serde::Serialize)]
+struct Mount {
+    from: &'static str,
+    to: &'static str,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Strategy {
+    RollingUpdate { max_surge: u32 },
+}
 
+#[test]
+fn serialize_1() {
+    let deployment = Deployment {
+        name: "api server".to_string(),
+        replicas: 3,
+        machines: [
+            (
+                "api".to_string(),
+                MachineSpec {
+                    size: "t3-large",
+                    spot: false,
+                },
+            ),
+            (
+                "worker".to_string(),
+                MachineSpec {
+                    size: "t3-medium",
+                    spot: true,
+                },
+            ),
+        ]
+        .into_iter()
+        .collect(),
+        labels: [("team".to_string(), "infra".to_string())]
+            .into_iter()
+            .collect(),
+        excludes: vec!["node_modules/", "target/"],
+        mounts: vec![
+            Mount {
+                from: "/data",
+                to: "/mnt/data",
+            },
+            Mount {
+                from: "/logs",
+                to: "/mnt/logs",
+            },
+        ],
+        strategy: Strategy::RollingUpdate { max_surge: 1 },
+    };
+    let expected = r#"
+.excludes[+] = 'node_modules/'
+.excludes[+] = 'target/'
+.labels{team} = 'infra'
+.machines{api}.size = 't3-large'
+.machines{api}.spot = false
+.machines{worker}.size = 't3-medium'
+.machines{worker}.spot = true
+.mounts[0].from = '/data'
+.mounts[0].to = '/mnt/data'
+.mounts[1].from = '/logs'
+.mounts[1].to = '/mnt/logs'
+.name = 'api server'
+.replicas = 3
+.strategy.rolling-update.max_surge = 1
+"#
+    .trim();
+    let actual = crate::to_string(&deployment).unwrap();
     pretty_assertions::assert_eq!(actual, expected);
+
+    // The serialized MERC should evaluate to the same JSON as the data structure
+    pretty_assertions::assert_eq!(
+        merc_to_json(&actual).unwrap(),
+        serde_json::to_value(&deployment).unwrap()
+    );
+}
+
+#[test]
+fn serialize_scalar_at_top_level_is_an_error() {
+    assert!(crate::to_string(&1).is_err())
+}
+
+#[test]
+fn serialize_empty_collections_is_an_error() {
+    #[derive(serde::Serialize)]
+    struct Config {
+        name: &'static str,
+        tags: Vec<&'static str>,
+    }
+    let error = crate::to_string(&Config {
+        name: "api",
+        tags: Vec::new(),
+    })
+    .unwrap_err();
+    pretty_assertions::assert_eq!(
+        error.to_string(),
+        "The empty array at `.tags` cannot be represented in MERC"
+    );
+
+    let error = crate::to_string(&std::collections::HashMap::<String, u32>::new()).unwrap_err();
+    pretty_assertions::assert_eq!(
+        error.to_string(),
+        "The empty map at the top level cannot be represented in MERC"
+    );
+}
+
+#[test]
+fn enum_1() {
+    let input = r#"
+.mode = #fast
+# Enum members can be quoted
+.level = #'very high'
+.levels[+] = #low
+"#
+    .trim();
+    pretty_assertions::assert_eq!(
+        merc_to_json(input).unwrap(),
+        serde_json::json!({"mode": "fast", "level": "very high", "levels": ["low"]})
+    );
+    let value = evaluate(crate::parse(input).unwrap()).unwrap();
+    assert_eq!(value.get("mode").unwrap().as_enum(), Some("fast"));
+    assert_eq!(value.get("mode").unwrap().typ(), Type::Enum);
+    pretty_assertions::assert_eq!(
+        format_merc(input, None).unwrap(),
+        r#"
+# Enum members can be quoted
+.level = #'very high'
+.levels[+] = #low
+.mode = #fast
+"#
+        .trim()
+    );
+}
+
+#[test]
+fn enum_is_not_a_comment() {
+    let input = r#"
+.mode = #fast
+# A comment
+.speed = 1
+"#
+    .trim();
+    pretty_assertions::assert_eq!(
+        merc_to_json(input).unwrap(),
+        serde_json::json!({"mode": "fast", "speed": 1})
+    );
+}
+
+#[test]
+fn error_type_mismatch_enum_accessed() {
+    let input = r#"
+.x = #fast
+.x.y = 3
+"#
+    .trim();
+    pretty_assertions::assert_eq!(
+        merc_to_json_string(input).err().unwrap(),
+        "
+error[E0101]: Type Mismatch
+  |
+1 | .x = #fast
+  |      ----- info: The type of the parent value was first inferred as Enum due to this assignment.
+2 | .x.y = 3
+  |   ^^ Error: this access treats the parent value as Object, but it was inferred as a different type.
+  |"
+        .trim_start()
+    )
+}
+
+#[test]
+fn error_type_mismatch_enum_assigned() {
+    let input = r#"
+.x.y = 3
+.x = #fast
+"#
+    .trim();
+    pretty_assertions::assert_eq!(
+        merc_to_json_string(input).err().unwrap(),
+        "
+error[E0101]: Type Mismatch
+  |
+1 | .x.y = 3
+  |   -- info: The type of this value was first inferred as Object due to this access.
+2 | .x = #fast
+  |      ^^^^^ Error: this assignment treats the value as Enum, but it was inferred as a different type.
+  |"
+        .trim_start()
+    )
+}
+
+#[test]
+fn enum_serde() {
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Settings {
+        size: Size,
+    }
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "kebab-case")]
+    enum Size {
+        T3Large,
+    }
+    let settings = Settings {
+        size: Size::T3Large,
+    };
+    let merc = crate::to_string(&settings).unwrap();
+    assert_eq!(merc, ".size = #t3-large");
+    assert_eq!(crate::from_str::<Settings>(&merc).unwrap(), settings);
+}
+
+#[test]
+fn cst_is_lossless() {
+    let inputs = [
+        r#"
+# Section header
+
+# Comment of x
+.x{"y z"}[+] = 'hello'   
+	.x{'y z'}[+]   =   """
+multiline ሴ
+"""
+
+
+.mode = #fast
+.levels[0] = -6.02e+23
+.enabled = true
+.nothing = null
+
+# Trailing comment
+# of the file
+"#,
+        ".a = 1",
+        ".a = 1\r\n\r\n# windows\r\n.b = 2\r\n",
+    ];
+    for input in inputs {
+        let cst = crate::parse_cst(input).unwrap();
+        pretty_assertions::assert_eq!(cst.to_string(), input);
+        pretty_assertions::assert_eq!(
+            cst.tokens().map(|token| token.text()).collect::<String>(),
+            input
+        );
+    }
+}
+
+#[test]
+fn cst_entries() {
+    let input = r#"
+# Comment of x
+.x{y}[+] = 'hello'
+.mode = #fast
+
+# Trailing comment
+"#
+    .trim();
+    let cst = crate::parse_cst(input).unwrap();
+    let entries = cst.entries().collect::<Vec<_>>();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(
+        entries[0]
+            .comments()
+            .map(|comment| comment.text())
+            .collect::<Vec<_>>(),
+        ["# Comment of x"]
+    );
+    assert_eq!(
+        entries[0]
+            .accesses()
+            .map(|access| (access.kind(), access.to_string()))
+            .collect::<Vec<_>>(),
+        [
+            (crate::NodeKind::ObjectAccess, ".x".to_string()),
+            (crate::NodeKind::MapAccess, "{y}".to_string()),
+            (crate::NodeKind::ArrayAccessImplicit, "[+]".to_string())
+        ]
+    );
+    assert_eq!(&input[entries[0].accesses_span().byte_range()], ".x{y}[+]");
+    assert_eq!(
+        entries[1]
+            .path()
+            .unwrap()
+            .iter()
+            .map(|access| (access.kind.display(), &input[access.span.byte_range()]))
+            .collect::<Vec<_>>(),
+        [(".mode".to_string(), ".mode")]
+    );
+    assert_eq!(&input[entries[0].value().span().byte_range()], "'hello'");
+    assert_eq!(&input[entries[1].value().span().byte_range()], "#fast");
+    assert_eq!(
+        cst.trailing_comments()
+            .map(|comment| comment.text())
+            .collect::<Vec<_>>(),
+        ["# Trailing comment"]
+    );
+}
+
+#[test]
+fn trailing_comments_are_parsed() {
+    let input = r#"
+.x = 1
+# The end
+"#
+    .trim();
+    let parsed = parse(input).unwrap();
+    assert_eq!(parsed.comments().collect::<Vec<_>>(), ["# The end"]);
+    pretty_assertions::assert_eq!(merc_to_json(input).unwrap(), serde_json::json!({"x": 1}));
+}
+
+#[test]
+fn edit_set_existing_entry() {
+    let input = r#"
+# Machines
+.machines{api}.size = 't3-medium'   # kept after the edit
+.machines{api}.replicas = 1
+
+# Trailing comment
+"#
+    .trim_start();
+    pretty_assertions::assert_eq!(
+        crate::set(input, ".machines{api}.size", "'t3-large'").unwrap(),
+        r#"
+# Machines
+.machines{api}.size = 't3-large'   # kept after the edit
+.machines{api}.replicas = 1
+
+# Trailing comment
+"#
+        .trim_start()
+    );
+}
+
+#[test]
+fn edit_set_new_entry_next_to_siblings() {
+    let input = r#"
+.machines{api}.size = 't3-medium'
+
+# Workers
+.machines{worker}.size = 't3-small'
+.machines{worker}.replicas = 2
+
+# Excludes
+.excludes[+] = 'node_modules/'
+"#
+    .trim_start();
+    pretty_assertions::assert_eq!(
+        crate::set(input, ".machines{api}.replicas", "3").unwrap(),
+        r#"
+.machines{api}.size = 't3-medium'
+.machines{api}.replicas = 3
+
+# Workers
+.machines{worker}.size = 't3-small'
+.machines{worker}.replicas = 2
+
+# Excludes
+.excludes[+] = 'node_modules/'
+"#
+        .trim_start()
+    );
+    pretty_assertions::assert_eq!(
+        crate::set(input, ".excludes[+]", "'x'").unwrap(),
+        r#"
+.machines{api}.size = 't3-medium'
+
+# Workers
+.machines{worker}.size = 't3-small'
+.machines{worker}.replicas = 2
+
+# Excludes
+.excludes[+] = 'node_modules/'
+.excludes[+] = 'x'
+"#
+        .trim_start()
+    );
+}
+
+#[test]
+fn edit_set_rejects_type_mismatch() {
+    let input = ".machines{api}.size = 't3-medium'";
+    let error = crate::set(input, ".machines.api", "1").err().unwrap();
+    pretty_assertions::assert_eq!(
+        error.display(input),
+        "
+The edit is rejected because it makes the source invalid:
+error[E0101]: Type Mismatch
+  |
+1 | .machines{api}.size = 't3-medium'
+  |          ----- info: The type of the parent value was first inferred as Map due to this access.
+2 | .machines.api = 1
+  |          ^^^^ Error: this access treats the parent value as Object, but it was inferred as a different type.
+  |"
+        .trim_start()
+    );
+}
+
+#[test]
+fn edit_unset_removes_entries_and_their_comments() {
+    let input = r#"
+# API
+.machines{api}.size = 't3-medium'
+.machines{api}.replicas = 1
+
+# Workers
+.machines{worker}.size = 't3-small'
+"#
+    .trim_start();
+    pretty_assertions::assert_eq!(
+        crate::unset(input, ".machines{api}").unwrap(),
+        "\n# Workers\n.machines{worker}.size = 't3-small'\n"
+    );
+    assert!(matches!(
+        crate::unset(input, ".machines{db}"),
+        Err(crate::EditError::NotFound { .. })
+    ));
+    pretty_assertions::assert_eq!(
+        crate::unset(input, ".machines")
+            .err()
+            .unwrap()
+            .to_string(),
+        "Removing the entries under the path .machines leaves no entry, but a MERC file needs at least one"
+    );
+}
+
+#[test]
+fn edit_mv() {
+    let input = r#"
+.machines{api}.size = 't3-medium'
+.machines{api}.replicas = 1
+.machines{worker}.size = 't3-small'
+"#
+    .trim_start();
+    pretty_assertions::assert_eq!(
+        crate::mv(input, ".machines{api}", ".machines{'web server'}").unwrap(),
+        r#"
+.machines{'web server'}.size = 't3-medium'
+.machines{'web server'}.replicas = 1
+.machines{worker}.size = 't3-small'
+"#
+        .trim_start()
+    );
+    pretty_assertions::assert_eq!(
+        crate::mv(input, ".machines{api}", ".machines{worker}")
+            .err()
+            .unwrap()
+            .to_string(),
+        "The path .machines{worker} already exists"
+    );
+    assert!(matches!(
+        crate::mv(input, ".machines{api}.size", ".machines{worker}.size"),
+        Err(crate::EditError::AlreadyExists { .. })
+    ));
+    // The kinds of accesses are still checked by evaluating the edited source
+    assert!(matches!(
+        crate::mv(input, ".machines{api}", ".machines.web"),
+        Err(crate::EditError::Rejected { .. })
+    ));
+}
+
+#[test]
+fn format_merc_keeps_array_items_in_order() {
+    let input = (0..12)
+        .map(|index| format!(".x[+] = {index}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    pretty_assertions::assert_eq!(format_merc(&input, None).unwrap(), input);
+}
+
+#[test]
+fn format_merc_preserve_order() {
+    let input = r#"
+# Machines
+.machines{worker}.size = 't3-small'
+.name = 'production'
+.machines{api}.size = 't3-medium'
+
+# Array
+.excludes[+] = 'b'
+.machines{worker}.replicas = 2
+.excludes[+] = 'a'
+"#
+    .trim();
+    let options = FormatOptions::new(EntryOrder::Preserve);
+    pretty_assertions::assert_eq!(
+        format_merc(input, Some(options)).unwrap(),
+        r#"
+# Machines
+.machines{worker}.size = 't3-small'
+.name = 'production'
+.machines{api}.size = 't3-medium'
+
+# Array
+.excludes[+] = 'b'
+.machines{worker}.replicas = 2
+.excludes[+] = 'a'
+"#
+        .trim()
+    );
+
+    let options = FormatOptions::new(EntryOrder::Grouped);
+    pretty_assertions::assert_eq!(
+        format_merc(input, Some(options)).unwrap(),
+        r#"
+# Machines
+.machines{worker}.size = 't3-small'
+.machines{worker}.replicas = 2
+.machines{api}.size = 't3-medium'
+.name = 'production'
+
+# Array
+.excludes[+] = 'b'
+.excludes[+] = 'a'
+"#
+        .trim()
+    );
+
+    // Idempotency holds for every order
+    for order in [
+        EntryOrder::Sorted,
+        EntryOrder::Preserve,
+        EntryOrder::Grouped,
+    ] {
+        let options = FormatOptions::new(order);
+        let formatted = format_merc(input, Some(options)).unwrap();
+        pretty_assertions::assert_eq!(format_merc(&formatted, Some(options)).unwrap(), formatted);
+    }
+}
+
+#[test]
+fn format_merc_keeps_blank_lines_and_trailing_comments() {
+    let input = r#"
+.name = 'production'
+.region = 'eu'
+
+
+.machines{api}.size = 't3-medium'
+.machines{api}.replicas = 2
+
+# End of the machines
+# Keep this
+"#
+    .trim();
+    let options = FormatOptions::new(EntryOrder::Preserve);
+    pretty_assertions::assert_eq!(
+        format_merc(input, Some(options)).unwrap(),
+        r#"
+.name = 'production'
+.region = 'eu'
+
+.machines{api}.size = 't3-medium'
+.machines{api}.replicas = 2
+
+# End of the machines
+# Keep this
+"#
+        .trim()
+    );
+
+    // Blank lines are dropped once entries are reordered
+    pretty_assertions::assert_eq!(
+        format_merc(input, None).unwrap(),
+        r#"
+.machines{api}.replicas = 2
+.machines{api}.size = 't3-medium'
+.name = 'production'
+.region = 'eu'
+
+# End of the machines
+# Keep this
+"#
+        .trim()
+    );
+}
+
+#[test]
+fn report_every_error() {
+    let input = r#"
+.x = 1
+.x = 2
+.y.z 3
+.name = hello
+.w = 4
+.w.v = 5
+"#
+    .trim();
+    let errors = check(input).err().unwrap();
+    pretty_assertions::assert_eq!(
+        errors.iter().map(|error| error.title()).collect::<Vec<_>>(),
+        [
+            "Duplicate Assignment",
+            "Missing Equals Sign",
+            "Missing Value",
+            "Type Mismatch"
+        ]
+    );
+    pretty_assertions::assert_eq!(
+        errors.display(input),
+        "
+error[E0102]: Duplicate Assignment
+  |
+1 | .x = 1
+  |      - info: A value was previously assigned at this path.
+2 | .x = 2
+  |      ^ Attempting to assign a new value at the same path is not allowed.
+  |
+
+error[E0002]: Missing Equals Sign
+  |
+3 | .y.z 3
+  |      ^ expected `=` after the path `.y.z`, found `3`
+  |
+
+error[E0003]: Missing Value
+  |
+4 | .name = hello
+  |         ^^^^^ expected a value after `=`, found `hello`
+  |
+  = help: strings must be quoted, for example `'hello'`
+
+error[E0101]: Type Mismatch
+  |
+5 | .w = 4
+  |      - info: The type of the parent value was first inferred as Integer due to this assignment.
+6 | .w.v = 5
+  |   ^^ Error: this access treats the parent value as Object, but it was inferred as a different type.
+  |"
+        .trim_start()
+    );
+
+    // Entries after the errors are still evaluated
+    let diagnostics = crate::merc_diagnostics(".a = 'é'\n.a = 1\n.b = 2");
+    pretty_assertions::assert_eq!(
+        diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.start, diagnostic.end, diagnostic.code.as_str()))
+            .collect::<Vec<_>>(),
+        [(14, 15, "E0102")]
+    );
+}
+
+#[test]
+fn find_references_of_path_prefix() {
+    let input = r#"
+.materials{metal}.reflectivity = 1.0
+.materials{plastic}.reflectivity = 0.5
+# Metal
+.materials{metal}.metallic = true
+.entities[+].name = 'hero'
+.entities[+].name = 'monster'
+"#
+    .trim();
+    let references = |offset: usize| {
+        let references = find_references(input, offset).unwrap().unwrap();
+        let texts = |span: &Span| input[span.byte_range()].to_string();
+        (
+            references.path,
+            references
+                .references
+                .iter()
+                .map(|reference| (texts(&reference.entry), texts(&reference.access)))
+                .collect::<Vec<_>>(),
+            texts(&references.parent_inferred_at),
+        )
+    };
+
+    // On `{metal}`
+    pretty_assertions::assert_eq!(
+        references(12),
+        (
+            ".materials{metal}".to_string(),
+            vec![
+                (
+                    ".materials{metal}.reflectivity = 1.0".to_string(),
+                    "{metal}".to_string()
+                ),
+                (
+                    ".materials{metal}.metallic = true".to_string(),
+                    "{metal}".to_string()
+                )
+            ],
+            "{metal}".to_string()
+        )
+    );
+
+    // On `.materials`
+    let (path, references_of_materials, parent_inferred_at) = references(1);
+    pretty_assertions::assert_eq!(path, ".materials");
+    pretty_assertions::assert_eq!(references_of_materials.len(), 3);
+    pretty_assertions::assert_eq!(parent_inferred_at, ".materials");
+
+    // On `.name` of the second `[+]`, which is not shared by the first one
+    let offset = input.rfind(".name").unwrap();
+    pretty_assertions::assert_eq!(
+        references(offset),
+        (
+            ".entities[+].name".to_string(),
+            vec![(
+                ".entities[+].name = 'monster'".to_string(),
+                ".name".to_string()
+            )],
+            ".name".to_string()
+        )
+    );
+
+    // Not on any access
+    assert!(find_references(input, input.find("1.0").unwrap())
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn edit_rename() {
+    let input = r#"
+.materials{metal}.reflectivity = 1.0
+.materials{plastic}.reflectivity = 0.5
+# Metal
+.materials{metal}.metallic = true
+.metal = 'not renamed'
+"#
+    .trim();
+    pretty_assertions::assert_eq!(
+        crate::rename(input, ".materials{metal}", "stainless steel").unwrap(),
+        r#"
+.materials{'stainless steel'}.reflectivity = 1.0
+.materials{plastic}.reflectivity = 0.5
+# Metal
+.materials{'stainless steel'}.metallic = true
+.metal = 'not renamed'
+"#
+        .trim()
+    );
+    pretty_assertions::assert_eq!(
+        crate::rename(input, ".materials{metal}", "plastic")
+            .err()
+            .unwrap()
+            .to_string(),
+        "The path .materials{plastic} already exists"
+    );
+    assert!(matches!(
+        crate::rename(".x[+] = 1", ".x[+]", "y"),
+        Err(crate::EditError::NoKey { .. })
+    ));
+    assert!(matches!(
+        crate::rename(input, ".materials{metal}", ""),
+        Err(crate::EditError::EmptyKey { .. })
+    ));
+    // Only ASCII letters and digits are left unquoted
+    pretty_assertions::assert_eq!(
+        crate::rename(".metal = 1", ".metal", "métal").unwrap(),
+        ".'métal' = 1"
+    );
+}
+
+#[test]
+fn schema_violations_are_located_at_entries() {
+    let schema: Schema = r##"
+{
+  "type": "object",
+  "required": ["name", "machines"],
+  "properties": {
+    "name": { "type": "string", "pattern": "^[a-z-]+$" },
+    "machines": {
+      "type": "object",
+      "additionalProperties": { "$ref": "#/$defs/machine" }
+    }
+  },
+  "$defs": {
+    "machine": {
+      "type": "object",
+      "additionalProperties": false,
+      "properties": {
+        "size": { "enum": ["small", "large"] },
+        "replicas": { "type": "integer", "minimum": 1 }
+      }
+    }
+  }
+}
+"##
+    .parse()
+    .unwrap();
+    let input = r#"
+.name = 'My App'
+.machines{api}.size = #medium
+.machines{api}.replicas = 0
+.machines{web}.size = 'small'
+.machines{web}.region = 'eu'
+"#
+    .trim();
+    let errors = check_with_schema(input, &schema).err().unwrap();
+    pretty_assertions::assert_eq!(
+        errors.display(input),
+        "
+error[E0301]: Schema Violation
+  |
+1 | .name = 'My App'
+  |         ^^^^^^^^ expected a string matching `^[a-z-]+$`
+  |
+
+error[E0301]: Schema Violation
+  |
+2 | .machines{api}.size = #medium
+  |                       ^^^^^^^ expected one of `\"small\"`, `\"large\"`, found \"medium\"
+  |
+
+error[E0301]: Schema Violation
+  |
+3 | .machines{api}.replicas = 0
+  |                           ^ expected at least 1, found 0
+  |
+
+error[E0301]: Schema Violation
+  |
+5 | .machines{web}.region = 'eu'
+  |                         ^^^^ unexpected property `region` of `.machines{web}`
+  |"
+        .trim()
+    );
+
+    let input = ".name = 'my-app'\n.machines{api}.size = 'large'";
+    assert!(check_with_schema(input, &schema).is_ok());
+}
+
+#[test]
+fn schema_type_and_required() {
+    let schema: Schema = r#"
+{
+  "properties": {
+    "ports": { "type": "array", "items": { "type": "integer" }, "maxItems": 2 },
+    "debug": { "type": "boolean" }
+  },
+  "required": ["name"]
+}
+"#
+    .parse()
+    .unwrap();
+    let input = r#"
+.ports[+] = 80
+.ports[+] = 'https'
+.ports[+] = 8080
+.debug = 1
+"#
+    .trim();
+    let errors = check_with_schema(input, &schema).err().unwrap();
+    pretty_assertions::assert_eq!(
+        errors
+            .iter()
+            .map(|error| (error.span().start(), error.message().to_string()))
+            .collect::<Vec<_>>(),
+        [
+            (
+                0,
+                "missing required property `name` of the root".to_string()
+            ),
+            (6, "expected at most 2 items, found 3".to_string()),
+            (27, "expected `integer`, found string".to_string()),
+            (61, "expected `boolean`, found integer".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn schema_invalid_pattern() {
+    let error = r#"{"pattern": "("}"#.parse::<Schema>().err().unwrap();
+    assert!(error.to_string().starts_with("Invalid pattern `(`"));
+}
+
+fn machines_schema() -> Schema {
+    r#"
+{
+  "properties": {
+    "machines": {
+      "patternProperties": {
+        "^[a-z]+$": {
+          "properties": { "size": { "type": "string" }, "tags": { "items": { "type": "string" } } }
+        }
+      }
+    }
+  }
+}
+"#
+    .parse()
+    .unwrap()
+}
+
+#[test]
+fn schema_wrong_access_kind() {
+    let input = ".machines.api{size} = 'small'";
+    let errors = check_with_schema(input, &machines_schema()).err().unwrap();
+    pretty_assertions::assert_eq!(
+        errors.display(input),
+        "
+error[E0302]: Wrong Access Kind
+  |
+1 | .machines.api{size} = 'small'
+  |          ^^^^ `api` is a user-defined key
+  |
+  = help: use `{api}` instead
+
+error[E0302]: Wrong Access Kind
+  |
+1 | .machines.api{size} = 'small'
+  |              ^^^^^^ `size` is a property defined by the schema
+  |
+  = help: use `.size` instead"
+            .trim()
+    );
+
+    // Fixing also resolves the type mismatch caused by mixing both kinds of accesses
+    let input = r#"
+.machines.api{size} = 'small'
+.machines{web}.size = 'large'
+"#
+    .trim();
+    pretty_assertions::assert_eq!(
+        crate::fix_accesses(input, &machines_schema()).unwrap(),
+        r#"
+.machines{api}.size = 'small'
+.machines{web}.size = 'large'
+"#
+        .trim()
+    );
+}
+
+#[test]
+fn json_to_merc_with_schema_chooses_maps() {
+    let json = r#"{"machines": {"api": {"size": "small", "tags": ["a"]}}}"#;
+    pretty_assertions::assert_eq!(
+        json_to_merc_string_with_schema(json, &machines_schema()).unwrap(),
+        r#"
+.machines{api}.size = 'small'
+.machines{api}.tags[+] = 'a'
+"#
+        .trim()
+    );
+}
+
+#[test]
+fn complete_paths_and_values() {
+    let schema: Schema = r##"
+{
+  "properties": {
+    "debug": { "type": "boolean", "description": "Log every request" },
+    "machines": { "additionalProperties": { "$ref": "#/$defs/machine" } }
+  },
+  "$defs": {
+    "machine": {
+      "properties": {
+        "size": { "type": "string" },
+        "disk size": { "type": "integer" }
+      }
+    }
+  }
+}
+"##
+    .parse()
+    .unwrap();
+    let complete = |input: &str| {
+        complete(input, input.len(), &schema)
+            .into_iter()
+            .map(|completion| {
+                (
+                    completion.insert_text,
+                    completion.detail.unwrap_or_default(),
+                    completion.replace.byte_range(),
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    pretty_assertions::assert_eq!(
+        complete(".debug = true\n.machines{api}.s"),
+        [
+            ("'disk size'".to_string(), "integer".to_string(), 29..30),
+            ("size".to_string(), "string".to_string(), 29..30)
+        ]
+    );
+    pretty_assertions::assert_eq!(
+        complete(".debug ="),
+        [
+            (" true".to_string(), "boolean".to_string(), 8..8),
+            (" false".to_string(), "boolean".to_string(), 8..8)
+        ]
+    );
+    pretty_assertions::assert_eq!(
+        complete(".machines{api}.size = "),
+        [("''".to_string(), "string".to_string(), 22..22)]
+    );
+    pretty_assertions::assert_eq!(complete("# .machines{api}."), []);
+}
+
+#[test]
+fn yaml_to_merc_keeps_comments_and_order() {
+    let yaml = r#"
+# The name of the service
+name: api # must be unique
+replicas: 3
+ratio: 0.5
+quoted: "true"
+# Machines by region
+machines:
+  eu-west:
+    size: small
+  us-east:
+    size: large
+excludes:
+  # Dependencies
+  - node_modules/
+  - dist/
+"#;
+    pretty_assertions::assert_eq!(
+        yaml_to_merc_string(yaml).unwrap(),
+        r#"
+# The name of the service
+# must be unique
+.name = 'api'
+.replicas = 3
+.ratio = 0.5
+.quoted = 'true'
+
+# Machines by region
+.machines.eu-west.size = 'small'
+.machines.us-east.size = 'large'
+
+# Dependencies
+.excludes[+] = 'node_modules/'
+.excludes[+] = 'dist/'
+"#
+        .trim()
+    );
+}
+
+#[test]
+fn toml_to_merc_keeps_comments_and_order() {
+    let toml = r#"
+# The name of the service
+name = "api" # must be unique
+released = 1979-05-27
+
+# Machines
+[machines.eu]
+size = "small"
+
+[[workers]]
+# The first worker
+id = 1
+"#;
+    pretty_assertions::assert_eq!(
+        toml_to_merc_string(toml).unwrap(),
+        r#"
+# The name of the service
+# must be unique
+.name = 'api'
+.released = 1979-05-27
+
+# Machines
+.machines.eu.size = 'small'
+
+# The first worker
+.workers[+].id = 1
+"#
+        .trim()
+    );
+}
+
+#[test]
+fn merc_to_yaml_and_toml_keep_comments_and_order() {
+    let merc = r#"
+# The region
+.region = 'eu'
+# Web servers
+.machines{web}.size = 'large'
+.machines{web}.ports[+] = 80
+# TLS
+.machines{web}.ports[+] = 443
+.note = """
+first
+second
+"""
+
+# End of the settings
+"#;
+    pretty_assertions::assert_eq!(
+        merc_to_yaml_string(merc).unwrap(),
+        r#"
+# End of the settings
+
+# The region
+region: eu
+machines:
+  web:
+    # Web servers
+    size: large
+    ports:
+    - 80
+    # TLS
+    - 443
+note: |-
+  first
+  second
+"#
+        .trim_start()
+    );
+    pretty_assertions::assert_eq!(
+        merc_to_toml_string(merc).unwrap(),
+        r#"
+# End of the settings
+
+# The region
+region = "eu"
+note = """
+first
+second"""
+
+[machines.web]
+# Web servers
+size = "large"
+ports = [
+    80,
+    # TLS
+    443,
+]
+"#
+        .trim_start()
+    );
+}
+
+#[test]
+fn merc_to_toml_keeps_numbers_exact() {
+    pretty_assertions::assert_eq!(
+        merc_to_toml_string(".count = 3\n.ratio = 0.5").unwrap(),
+        "count = 3\nratio = 0.5\n"
+    );
+    pretty_assertions::assert_eq!(
+        merc_to_toml_string(".n = 99999999999999999999999999")
+            .err()
+            .unwrap(),
+        "The number 99999999999999999999999999 cannot be represented exactly in TOML"
+    );
+}
+
+#[test]
+fn diff_reports_changed_leaves() {
+    let old = r#"
+.mode = #fast
+.excludes[+] = 'dist/'
+.machines{api}.size = 't3-medium'
+.machines{api}.ports[+] = 80
+"#
+    .trim();
+    let new = r#"
+.machines{api}.ports[+] = 80
+.machines{api}.ports[+] = 443
+.machines{api}.size = "t3-large"
+.mode = #fast
+.debug = true
+"#
+    .trim();
+    pretty_assertions::assert_eq!(
+        merc_diff(old, new).unwrap(),
+        r#"
++ .debug = true
+- .excludes[0] = 'dist/'
++ .machines{api}.ports[1] = 443
+- .machines{api}.size = 't3-medium'
++ .machines{api}.size = 't3-large'
+"#
+        .trim()
+    );
+    pretty_assertions::assert_eq!(merc_diff(old, old).unwrap(), "");
+}
+
+#[test]
+fn diff_reports_changed_access_kinds() {
+    let old = r#"
+.machines{api}.size = 't3-medium'
+.mode = #fast
+"#
+    .trim();
+    let new = r#"
+.machines.api.size = 't3-medium'
+.mode = 'fast'
+"#
+    .trim();
+    pretty_assertions::assert_eq!(
+        merc_diff(old, new).unwrap(),
+        r#"
+- .machines{api}.size = 't3-medium'
++ .machines.api.size = 't3-medium'
+- .mode = #fast
++ .mode = 'fast'
+"#
+        .trim()
+    );
+}
+
+#[test]
+fn diff_across_formats_ignores_access_kinds() {
+    let old = check(
+        r#"
+.machines{api}.size = 't3-medium'
+.machines{api}.ports[+] = 80
+.mode = #fast
+"#,
+    )
+    .unwrap();
+    let new = check(
+        r#"
+.machines.api.size = 't3-large'
+.machines.api.ports.http = 80
+.mode = 'fast'
+"#,
+    )
+    .unwrap();
+    pretty_assertions::assert_eq!(
+        crate::diff_across_formats(&old, &new)
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n"),
+        r#"
+- .machines{api}.ports[0] = 80
++ .machines.api.ports.http = 80
+- .machines{api}.size = 't3-medium'
++ .machines.api.size = 't3-large'
+"#
+        .trim()
+    );
+}
+
+#[test]
+fn merge_keeps_changes_of_both_sides() {
+    let base = check(
+        r#"
+.excludes[+] = 'dist/'
+.machines{api}.size = 't3-medium'
+.machines{api}.replicas = 1
+.mode = #fast
+"#,
+    )
+    .unwrap();
+    let ours = check(
+        r#"
+.excludes[+] = 'dist/'
+.excludes[+] = 'target/'
+.machines{api}.size = 't3-large'
+.machines{api}.replicas = 1
+.mode = #slow
+"#,
+    )
+    .unwrap();
+    let theirs = check(
+        r#"
+.excludes[+] = 'dist/'
+.excludes[+] = 'node_modules/'
+.machines{api}.size = 't3-medium'
+.machines{api}.replicas = 3
+.machines{web}.size = 't3-small'
+.mode = #fast
+"#,
+    )
+    .unwrap();
+    let merged = merge(&base, &ours, &theirs);
+    assert!(merged.conflicts.is_empty());
+    pretty_assertions::assert_eq!(
+        merged.print(),
+        r#"
+.excludes[+] = 'dist/'
+.excludes[+] = 'target/'
+.excludes[+] = 'node_modules/'
+.machines{api}.replicas = 3
+.machines{api}.size = 't3-large'
+.machines{web}.size = 't3-small'
+.mode = #slow
+"#
+        .trim()
+    );
+
+    // Both sides change the size, and only the size conflicts
+    let theirs = check(".excludes[+] = 'dist/'\n.machines{api}.size = 't3-small'\n.machines{api}.replicas = 1\n.mode = #fast").unwrap();
+    let merged = merge(&base, &ours, &theirs);
+    pretty_assertions::assert_eq!(
+        merged.print(),
+        r#"
+.excludes[+] = 'dist/'
+.excludes[+] = 'target/'
+.machines{api}.replicas = 1
+.mode = #slow
+
+<<<<<<< ours
+.machines{api}.size = 't3-large'
+=======
+.machines{api}.size = 't3-small'
+>>>>>>> theirs
+"#
+        .trim()
+    );
+}
+
+#[test]
+fn overlay_overrides_earlier_layers() {
+    let base = r#"
+.mode = #fast
+.machines{api}.size = 't3-medium'
+.machines{api}.replicas = 1
+.excludes[+] = 'dist/'
+"#
+    .trim();
+    let prod = r#"
+.machines{api}.size = 't3-large'
+.excludes[+] = 'target/'
+.mode = #slow
+.mode = #safe
+"#
+    .trim();
+    let mut overlay = Overlay::new(false);
+    overlay.add_layer(base).unwrap();
+    overlay.add_layer(prod).unwrap();
+    pretty_assertions::assert_eq!(
+        overlay.value().unwrap().print(),
+        r#"
+.excludes[+] = 'dist/'
+.excludes[+] = 'target/'
+.machines{api}.replicas = 1
+.machines{api}.size = 't3-large'
+.mode = #safe
+"#
+        .trim()
+    );
+    pretty_assertions::assert_eq!(
+        overlay
+            .provenance()
+            .into_iter()
+            .map(|provenance| (
+                provenance.path,
+                provenance.layer,
+                provenance.span.byte_range()
+            ))
+            .collect::<Vec<_>>(),
+        vec![
+            (".excludes[+]".to_string(), 0, 91..98),
+            (".excludes[+]".to_string(), 1, 48..57),
+            (".machines{api}.replicas".to_string(), 0, 74..75),
+            (".machines{api}.size".to_string(), 1, 22..32),
+            (".mode".to_string(), 1, 80..85),
+        ]
+    );
+
+    // In strict mode, a layer cannot assign the same path twice
+    let mut overlay = Overlay::new(true);
+    overlay.add_layer(base).unwrap();
+    let errors = overlay.add_layer(prod).unwrap_err().into_vec();
+    assert!(matches!(
+        &errors[..],
+        [Error::EvaluationError(error)] if error.code() == "E0102"
+    ));
+}
+
+#[test]
+fn includes_are_loaded_relative_to_the_including_file() {
+    let main = r#"
+@include 'services/db.merc' at .services{db}
+@include 'common.merc'
+
+.name = 'app'
+"#
+    .trim();
+    let mut files = MemoryLoader::new();
+    files.insert(
+        "config/services/db.merc",
+        ".host = 'localhost'\n.port = 5432",
+    );
+    files.insert("config/common.merc", ".region = 'eu'");
+    pretty_assertions::assert_eq!(
+        check_with_includes("config/main.merc", main, &files)
+            .unwrap()
+            .print(),
+        r#"
+.name = 'app'
+.region = 'eu'
+.services{db}.host = 'localhost'
+.services{db}.port = 5432
+"#
+        .trim()
+    );
+    // The includes are kept by the formatter, but not loaded
+    pretty_assertions::assert_eq!(format_merc(main, None).unwrap(), main);
+
+    files.insert(
+        "config/common.merc",
+        "@include './main.merc'\n.name = 'common'",
+    );
+    let errors = check_with_includes("config/main.merc", main, &files).unwrap_err();
+    pretty_assertions::assert_eq!(
+        errors.display(),
+        r#"
+error[E0102]: Duplicate Assignment
+ --> config/main.merc:4:9
+  |
+4 | .name = 'app'
+  |         ^^^^^ Attempting to assign a new value at the same path is not allowed.
+  |
+ ::: config/common.merc:2:9
+  |
+2 | .name = 'common'
+  |         -------- info: A value was previously assigned at this path.
+  |
+
+error[E0109]: Include Cycle
+ --> config/common.merc:1:10
+  |
+1 | @include './main.merc'
+  |          ^^^^^^^^^^^^^ This include forms a cycle: `config/main.merc` -> `config/common.merc` -> `config/main.merc`
+  |
+"#
+        .trim_start()
+        .trim_end_matches('\n')
+    );
+}
+
+#[test]
+fn file_of_only_includes() {
+    let main = r#"
+# Shared settings
+@include 'common.merc'
+@include 'db.merc' at .services{db}
+"#
+    .trim();
+    let mut files = MemoryLoader::new();
+    files.insert("common.merc", ".region = 'eu'");
+    files.insert("db.merc", ".port = 5432");
+    pretty_assertions::assert_eq!(
+        check_with_includes("main.merc", main, &files)
+            .unwrap()
+            .print(),
+        r#"
+.region = 'eu'
+.services{db}.port = 5432
+"#
+        .trim()
+    );
+    pretty_assertions::assert_eq!(format_merc(main, None).unwrap(), main);
+    pretty_assertions::assert_eq!(
+        crate::set(main, ".name", "'app'").unwrap(),
+        format!("{main}\n.name = 'app'")
+    );
+    pretty_assertions::assert_eq!(
+        crate::unset(&format!("{main}\n.name = 'app'\n"), ".name").unwrap(),
+        format!("{main}\n")
+    );
+
+    // A blank included file is named in the note, unlike the first file
+    files.insert("db.merc", "");
+    let errors = check_with_includes("main.merc", main, &files).unwrap_err();
+    assert!(
+        errors
+            .display()
+            .contains("note: db.merc: expected an entry"),
+        "{}",
+        errors.display()
+    );
+}
+
+#[test]
+fn schema_is_checked_against_included_files() {
+    let main = "@include 'web.merc' at .machines.web\n.machines.api.size = 'small'";
+    let mut files = MemoryLoader::new();
+    files.insert("web.merc", ".size = 1");
+    let errors =
+        crate::check_with_schema_and_includes("main.merc", main, &machines_schema(), &files)
+            .unwrap_err();
+    pretty_assertions::assert_eq!(
+        errors.display(),
+        r#"
+error[E0302]: Wrong Access Kind
+ --> main.merc:1:33
+  |
+1 | @include 'web.merc' at .machines.web
+  |                                 ^^^^ `web` is a user-defined key
+  |
+  = help: use `{web}` instead
+
+error[E0302]: Wrong Access Kind
+ --> main.merc:2:10
+  |
+2 | .machines.api.size = 'small'
+  |          ^^^^ `api` is a user-defined key
+  |
+  = help: use `{api}` instead
+
+error[E0301]: Schema Violation
+ --> web.merc:1:9
+  |
+1 | .size = 1
+  |         ^ expected `string`, found integer
+  |
+"#
+        .trim_start()
+        .trim_end_matches('\n')
+    );
+}
+
+#[test]
+fn query_matches_wildcards_and_recursive_descent() {
+    let value = check(
+        r#"
+.machines{api}.size = 't3-large'
+.machines{api}.tags[+] = #fast
+.machines{web}.size = 't3-small'
+.machines{web}.disk.size = 20
+.excludes[+] = 'a'
+.excludes[+] = 'b'
+"#,
+    )
+    .unwrap();
+    let paths = |query_: &str| {
+        query(&value, query_)
+            .unwrap()
+            .into_iter()
+            .map(|matched| format!("{} {:?}", matched.path, matched.raw()))
+            .collect::<Vec<_>>()
+    };
+    pretty_assertions::assert_eq!(
+        paths(".machines{*}.size"),
+        [
+            r#".machines{api}.size Some("t3-large")"#,
+            r#".machines{web}.size Some("t3-small")"#
+        ]
+    );
+    pretty_assertions::assert_eq!(
+        paths(".**.size"),
+        [
+            r#".machines{api}.size Some("t3-large")"#,
+            r#".machines{web}.size Some("t3-small")"#,
+            r#".machines{web}.disk.size Some("20")"#
+        ]
+    );
+    pretty_assertions::assert_eq!(
+        paths(".excludes[*]"),
+        [r#".excludes[0] Some("a")"#, r#".excludes[1] Some("b")"#]
+    );
+    pretty_assertions::assert_eq!(paths(".excludes[1]"), [r#".excludes[1] Some("b")"#]);
+    pretty_assertions::assert_eq!(
+        paths(".machines{api}.tags[+]"),
+        [r#".machines{api}.tags[0] Some("fast")"#]
+    );
+    pretty_assertions::assert_eq!(paths(".machines{api}"), [".machines{api} None"]);
+    // Accesses only match their own kind
+    pretty_assertions::assert_eq!(paths(".machines.*"), Vec::<String>::new());
+
+    pretty_assertions::assert_eq!(
+        query(&value, ".machines{api}").unwrap()[0].print(),
+        ".machines{api}.size = 't3-large'\n.machines{api}.tags[+] = #fast"
+    );
+    // Implicit array items are printed as in the source
+    pretty_assertions::assert_eq!(
+        print_matches(&query(&value, ".excludes[*]").unwrap()),
+        ".excludes[+] = 'a'\n.excludes[+] = 'b'"
+    );
+    // Every entry is printed once, even though `.**` matches every value above it
+    pretty_assertions::assert_eq!(print_matches(&query(&value, ".**").unwrap()), value.print());
+    pretty_assertions::assert_eq!(
+        print_matches(&query(&value, ".machines{web}.**").unwrap()),
+        ".machines{web}.disk.size = 20\n.machines{web}.size = 't3-small'"
+    );
+    let error = query(&value, ".a b").unwrap_err();
+    pretty_assertions::assert_eq!(
+        error.to_string(),
+        "expected an access, such as `.key` or `.*`, found `b`"
+    );
 }